@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+// Aliases by name, each standing for the text that replaces it when it is
+// the first word of a command.
+pub struct Aliases {
+	aliases: HashMap<String, String>,
+}
+
+impl Aliases {
+	pub fn new() -> Self {
+		Aliases {
+			aliases: HashMap::new(),
+		}
+	}
+
+	pub fn set(&mut self, name: &str, value: &str) {
+		self.aliases.insert(name.to_string(), value.to_string());
+	}
+
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.aliases.get(name).map(String::as_str)
+	}
+
+	pub fn remove(&mut self, name: &str) -> bool {
+		self.aliases.remove(name).is_some()
+	}
+
+	pub fn clear(&mut self) {
+		self.aliases.clear();
+	}
+
+	// Every alias sorted by name.
+	pub fn list(&self) -> Vec<(&str, &str)> {
+		let mut aliases: Vec<(&str, &str)> = self
+			.aliases
+			.iter()
+			.map(|(name, value)| (name.as_str(), value.as_str()))
+			.collect();
+		aliases.sort();
+		aliases
+	}
+}
+
+// An alias the way `alias` shows it, ready to be read back in.
+pub fn format_alias(name: &str, value: &str) -> String {
+	format!("alias {}='{}'", name, value.replace('\'', "'\\''"))
+}
@@ -0,0 +1,58 @@
+use crate::alias::{self, Aliases};
+
+pub fn alias(aliases: &mut Aliases, args: &[String]) -> i32 {
+	let args = match args.first().map(String::as_str) {
+		Some("-p") => &args[1..],
+		_ => args,
+	};
+	if args.is_empty() {
+		for (name, value) in aliases.list() {
+			println!("{}", alias::format_alias(name, value));
+		}
+		return 0;
+	}
+
+	let mut status = 0;
+	for arg in args {
+		match arg.split_once('=') {
+			Some((name, _)) if !is_valid_name(name) => {
+				shell_error!("alias: `{}': invalid alias name", name);
+				status = 1;
+			}
+			Some((name, value)) => aliases.set(name, value),
+			None => match aliases.get(arg) {
+				Some(value) => println!("{}", alias::format_alias(arg, value)),
+				None => {
+					shell_error!("alias: {}: not found", arg);
+					status = 1;
+				}
+			},
+		}
+	}
+	status
+}
+
+pub fn unalias(aliases: &mut Aliases, args: &[String]) -> i32 {
+	if args.first().map(String::as_str) == Some("-a") {
+		aliases.clear();
+		return 0;
+	}
+	if args.is_empty() {
+		eprintln!("unalias: usage: unalias [-a] name [name ...]");
+		return 2;
+	}
+
+	let mut status = 0;
+	for name in args {
+		if !aliases.remove(name) {
+			shell_error!("unalias: {}: not found", name);
+			status = 1;
+		}
+	}
+	status
+}
+
+// An alias name cannot contain anything that would end or quote a word.
+fn is_valid_name(name: &str) -> bool {
+	!name.is_empty() && !name.contains(|ch: char| ch.is_whitespace() || "/$`'\"\\;|&<>()".contains(ch))
+}
@@ -0,0 +1,355 @@
+use crate::variables::Variables;
+
+// Binary operators from the loosest to the tightest binding. `**` binds
+// tighter than all of them and is handled on its own since it groups to the
+// right.
+const BINARY_LEVELS: [&[&str]; 10] = [
+	&["||"],
+	&["&&"],
+	&["|"],
+	&["^"],
+	&["&"],
+	&["==", "!="],
+	&["<=", ">=", "<", ">"],
+	&["<<", ">>"],
+	&["+", "-"],
+	&["*", "/", "%"],
+];
+
+// Operators that store their result in the variable on their left, with the
+// binary operator they apply first.
+const ASSIGNMENTS: [(&str, &str); 11] = [
+	("=", ""),
+	("+=", "+"),
+	("-=", "-"),
+	("*=", "*"),
+	("/=", "/"),
+	("%=", "%"),
+	("<<=", "<<"),
+	(">>=", ">>"),
+	("&=", "&"),
+	("|=", "|"),
+	("^=", "^"),
+];
+
+// Longest first, so `**` is not read as two `*`.
+const OPERATORS: [&str; 39] = [
+	"<<=", ">>=", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "++", "--", "+=", "-=",
+	"*=", "/=", "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "~",
+	"!", "(", ")", "=", "?", ":", ",",
+];
+
+// A variable holding an expression of its own is evaluated too, up to this
+// depth.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, PartialEq)]
+enum Token {
+	Number(i64),
+	Name(String),
+	Operator(&'static str),
+}
+
+// Evaluate an integer expression such as the one in a `$((...))`. Names
+// stand for the shell variable of that name, with an unset or empty variable
+// counting as 0, and the assignment operators store into them.
+pub fn evaluate(expr: &str, vars: &mut Variables) -> Result<i64, String> {
+	evaluate_at_depth(expr, vars, 0)
+}
+
+fn evaluate_at_depth(expr: &str, vars: &mut Variables, depth: usize) -> Result<i64, String> {
+	if depth > MAX_DEPTH {
+		return Err(format!("{}: expression recursion level exceeded", expr));
+	}
+	let mut parser = Parser {
+		expr,
+		tokens: tokenize(expr)?,
+		pos: 0,
+		vars,
+		depth,
+	};
+	if parser.tokens.is_empty() {
+		return Ok(0);
+	}
+	let value = parser.comma(false)?;
+	match parser.tokens.get(parser.pos) {
+		None => Ok(value),
+		Some(token) => Err(parser.syntax_error("syntax error in expression", Some(token))),
+	}
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+	let chars: Vec<char> = expr.chars().collect();
+	let mut tokens: Vec<Token> = Vec::new();
+
+	let mut i = 0;
+	while i < chars.len() {
+		let ch = chars[i];
+		if ch.is_whitespace() {
+			i += 1;
+		} else if ch.is_ascii_digit() {
+			let len = chars[i..].iter().take_while(|ch| ch.is_ascii_alphanumeric()).count();
+			let word: String = chars[i..i + len].iter().collect();
+			tokens.push(Token::Number(parse_number(&word).ok_or_else(|| {
+				format!("{}: value too great for base (error token is \"{}\")", expr, word)
+			})?));
+			i += len;
+		} else if ch.is_ascii_alphabetic() || ch == '_' {
+			let len = chars[i..]
+				.iter()
+				.take_while(|ch| ch.is_ascii_alphanumeric() || **ch == '_')
+				.count();
+			tokens.push(Token::Name(chars[i..i + len].iter().collect()));
+			i += len;
+		} else {
+			let rest: String = chars[i..].iter().collect();
+			let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+				return Err(format!(
+					"{}: syntax error: operand expected (error token is \"{}\")",
+					expr, rest
+				));
+			};
+			tokens.push(Token::Operator(op));
+			i += op.len();
+		}
+	}
+
+	Ok(tokens)
+}
+
+// Read a decimal, `0x` hexadecimal or `0` octal constant.
+fn parse_number(word: &str) -> Option<i64> {
+	let (digits, radix) = if let Some(hex) = word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+		(hex, 16)
+	} else if word.len() > 1 && word.starts_with('0') {
+		(&word[1..], 8)
+	} else {
+		(word, 10)
+	};
+	u64::from_str_radix(digits, radix).ok().map(|value| value as i64)
+}
+
+struct Parser<'a> {
+	expr: &'a str,
+	tokens: Vec<Token>,
+	pos: usize,
+	vars: &'a mut Variables,
+	depth: usize,
+}
+
+impl Parser<'_> {
+	fn peek_operator(&self) -> Option<&'static str> {
+		match self.tokens.get(self.pos) {
+			Some(Token::Operator(op)) => Some(op),
+			_ => None,
+		}
+	}
+
+	fn syntax_error(&self, message: &str, token: Option<&Token>) -> String {
+		let token = match token {
+			Some(Token::Number(value)) => value.to_string(),
+			Some(Token::Name(name)) => name.clone(),
+			Some(Token::Operator(op)) => op.to_string(),
+			None => return format!("{}: {}", self.expr, message),
+		};
+		format!("{}: {} (error token is \"{}\")", self.expr, message, token)
+	}
+
+	fn expect(&mut self, op: &str) -> Result<(), String> {
+		if self.peek_operator() != Some(op) {
+			let message = format!("`{}' expected", op);
+			return Err(self.syntax_error(&message, self.tokens.get(self.pos)));
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	// An unset or empty variable counts as 0, anything else is evaluated as
+	// an expression of its own.
+	fn value_of(&mut self, name: &str) -> Result<i64, String> {
+		match self.vars.get(name) {
+			Some(value) if !value.trim().is_empty() => {
+				evaluate_at_depth(&value, self.vars, self.depth + 1)
+			}
+			_ => Ok(0),
+		}
+	}
+
+	fn comma(&mut self, skip: bool) -> Result<i64, String> {
+		let mut value = self.assignment(skip)?;
+		while self.peek_operator() == Some(",") {
+			self.pos += 1;
+			value = self.assignment(skip)?;
+		}
+		Ok(value)
+	}
+
+	fn assignment(&mut self, skip: bool) -> Result<i64, String> {
+		let name = match self.tokens.get(self.pos) {
+			Some(Token::Name(name)) => name.clone(),
+			_ => return self.conditional(skip),
+		};
+		let assignment = match self.tokens.get(self.pos + 1) {
+			Some(Token::Operator(op)) => ASSIGNMENTS.iter().find(|(assign, _)| assign == op),
+			_ => None,
+		};
+		let Some(&(_, op)) = assignment else {
+			return self.conditional(skip);
+		};
+		self.pos += 2;
+
+		let right = self.assignment(skip)?;
+		if skip {
+			return Ok(0);
+		}
+		let value = match op {
+			"" => right,
+			_ => {
+				let left = self.value_of(&name)?;
+				self.apply(op, left, right, false)?
+			}
+		};
+		self.vars.set(&name, &value.to_string())?;
+		Ok(value)
+	}
+
+	fn conditional(&mut self, skip: bool) -> Result<i64, String> {
+		let condition = self.binary(0, skip)?;
+		if self.peek_operator() != Some("?") {
+			return Ok(condition);
+		}
+		self.pos += 1;
+		let then = self.comma(skip || condition == 0)?;
+		self.expect(":")?;
+		let otherwise = self.conditional(skip || condition != 0)?;
+		Ok(if condition != 0 { then } else { otherwise })
+	}
+
+	// Parse the operators of `BINARY_LEVELS[level]` and everything that binds
+	// tighter. With `skip` the operands are only parsed, which is how the right
+	// side of a `&&` or `||` that is already decided goes unevaluated.
+	fn binary(&mut self, level: usize, skip: bool) -> Result<i64, String> {
+		if level == BINARY_LEVELS.len() {
+			return self.power(skip);
+		}
+		let mut left = self.binary(level + 1, skip)?;
+		while let Some(op) = self.peek_operator().filter(|op| BINARY_LEVELS[level].contains(op)) {
+			self.pos += 1;
+			let skip_right = skip || (op == "&&" && left == 0) || (op == "||" && left != 0);
+			let right = self.binary(level + 1, skip_right)?;
+			if !skip {
+				left = self.apply(op, left, right, skip_right)?;
+			}
+		}
+		Ok(left)
+	}
+
+	fn apply(&self, op: &str, left: i64, right: i64, skipped_right: bool) -> Result<i64, String> {
+		let value = match op {
+			"||" => (left != 0 || (!skipped_right && right != 0)) as i64,
+			"&&" => (left != 0 && right != 0) as i64,
+			"|" => left | right,
+			"^" => left ^ right,
+			"&" => left & right,
+			"==" => (left == right) as i64,
+			"!=" => (left != right) as i64,
+			"<=" => (left <= right) as i64,
+			">=" => (left >= right) as i64,
+			"<" => (left < right) as i64,
+			">" => (left > right) as i64,
+			"<<" => left.wrapping_shl(right as u32),
+			">>" => left.wrapping_shr(right as u32),
+			"+" => left.wrapping_add(right),
+			"-" => left.wrapping_sub(right),
+			"*" => left.wrapping_mul(right),
+			"/" | "%" if right == 0 => {
+				return Err(self.syntax_error("division by 0", self.tokens.get(self.pos - 1)));
+			}
+			"/" => left.wrapping_div(right),
+			"%" => left.wrapping_rem(right),
+			_ => unreachable!("not a binary operator: {}", op),
+		};
+		Ok(value)
+	}
+
+	fn power(&mut self, skip: bool) -> Result<i64, String> {
+		let base = self.unary(skip)?;
+		if self.peek_operator() != Some("**") {
+			return Ok(base);
+		}
+		self.pos += 1;
+		let exponent = self.power(skip)?;
+		if skip {
+			return Ok(0);
+		}
+		if exponent < 0 {
+			return Err(self.syntax_error("exponent less than 0", self.tokens.get(self.pos - 1)));
+		}
+		Ok(base.wrapping_pow(exponent.min(u32::MAX as i64) as u32))
+	}
+
+	fn unary(&mut self, skip: bool) -> Result<i64, String> {
+		match self.peek_operator() {
+			Some(op @ ("++" | "--")) => {
+				let Some(Token::Name(name)) = self.tokens.get(self.pos + 1).cloned() else {
+					// without a variable to change these are two signs that cancel out
+					self.pos += 1;
+					return self.unary(skip);
+				};
+				self.pos += 2;
+				if skip {
+					return Ok(0);
+				}
+				let step = if op == "++" { 1 } else { -1 };
+				let value = self.value_of(&name)?.wrapping_add(step);
+				self.vars.set(&name, &value.to_string())?;
+				Ok(value)
+			}
+			Some(op @ ("!" | "~" | "-" | "+")) => {
+				self.pos += 1;
+				let value = self.unary(skip)?;
+				Ok(match op {
+					"!" => (value == 0) as i64,
+					"~" => !value,
+					"-" => value.wrapping_neg(),
+					_ => value,
+				})
+			}
+			_ => self.primary(skip),
+		}
+	}
+
+	fn primary(&mut self, skip: bool) -> Result<i64, String> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		match token {
+			Some(Token::Number(value)) => Ok(value),
+			Some(Token::Name(name)) => {
+				let step = match self.peek_operator() {
+					Some("++") => 1,
+					Some("--") => -1,
+					_ if skip => return Ok(0),
+					_ => return self.value_of(&name),
+				};
+				self.pos += 1;
+				if skip {
+					return Ok(0);
+				}
+				let value = self.value_of(&name)?;
+				self.vars.set(&name, &value.wrapping_add(step).to_string())?;
+				Ok(value)
+			}
+			Some(Token::Operator("(")) => {
+				let value = self.comma(skip)?;
+				match self.tokens.get(self.pos) {
+					Some(Token::Operator(")")) => {
+						self.pos += 1;
+						Ok(value)
+					}
+					token => Err(self.syntax_error("missing `)'", token)),
+				}
+			}
+			token => Err(self.syntax_error("syntax error: operand expected", token.as_ref())),
+		}
+	}
+}
@@ -0,0 +1,37 @@
+use nix::sys::signal::{killpg, Signal};
+
+use crate::jobs::{Jobs, State};
+
+pub fn bg(jobs: &mut Jobs, args: &[String]) -> i32 {
+	jobs.reap();
+	let id = match jobs.find(args.first().map(String::as_str)) {
+		Ok(id) => id,
+		Err(err) => {
+			shell_error!("bg: {}", err);
+			return 1;
+		}
+	};
+	let Some(job) = jobs.get_mut(id) else {
+		return 1;
+	};
+	match job.state {
+		State::Stopped => (),
+		State::Running => {
+			shell_error!("bg: job {} already in background", id);
+			return 0;
+		}
+		_ => {
+			shell_error!("bg: job has terminated");
+			jobs.remove(id);
+			return 1;
+		}
+	}
+
+	if let Err(err) = killpg(job.pgid, Signal::SIGCONT) {
+		shell_error!("bg: {}", err);
+		return 1;
+	}
+	job.state = State::Running;
+	println!("[{}]+ {} &", job.id, job.command);
+	0
+}
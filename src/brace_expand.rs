@@ -0,0 +1,99 @@
+use crate::utils::QuoteTracker;
+
+// Expand the braces of a raw word into the words they stand for, in order.
+// Only a `{` with a matching `}` is expanded, and only when there is an
+// unquoted comma between them at its own level or a `x..y` sequence inside.
+// The result keeps the quoting of the original, so the usual expansions
+// still apply to each word afterwards.
+pub fn expand(word: &str) -> Vec<String> {
+	let chars: Vec<char> = word.chars().collect();
+	let mut quotes = QuoteTracker::new();
+	let unquoted: Vec<bool> = chars.iter().map(|&ch| quotes.is_unquoted(ch)).collect();
+
+	for open in 0..chars.len() {
+		// `${` starts a variable reference
+		if chars[open] != '{' || !unquoted[open] || (open > 0 && chars[open - 1] == '$') {
+			continue;
+		}
+		let Some((close, commas)) = find_close(&chars, &unquoted, open) else {
+			continue;
+		};
+		let prefix: String = chars[..open].iter().collect();
+		let suffix: String = chars[close + 1..].iter().collect();
+		if commas.is_empty() {
+			let inner: String = chars[open + 1..close].iter().collect();
+			let Some(sequence) = sequence(&inner) else {
+				continue;
+			};
+			return sequence
+				.iter()
+				.flat_map(|item| expand(&format!("{}{}{}", prefix, item, suffix)))
+				.collect();
+		}
+
+		let mut bounds = vec![open];
+		bounds.extend(&commas);
+		bounds.push(close);
+		return bounds
+			.windows(2)
+			.flat_map(|pair| {
+				let alternative: String = chars[pair[0] + 1..pair[1]].iter().collect();
+				expand(&format!("{}{}{}", prefix, alternative, suffix))
+			})
+			.collect();
+	}
+
+	vec![word.to_string()]
+}
+
+// Find the `}` matching the `{` at `chars[open]` along with the commas that
+// separate its alternatives.
+fn find_close(chars: &[char], unquoted: &[bool], open: usize) -> Option<(usize, Vec<usize>)> {
+	let mut depth = 0;
+	let mut commas: Vec<usize> = Vec::new();
+	for i in open + 1..chars.len() {
+		if !unquoted[i] {
+			continue;
+		}
+		match chars[i] {
+			'{' => depth += 1,
+			'}' if depth == 0 => return Some((i, commas)),
+			'}' => depth -= 1,
+			',' if depth == 0 => commas.push(i),
+			_ => (),
+		}
+	}
+	None
+}
+
+// The items of a `x..y` or `x..y..step` sequence, where `x` and `y` are both
+// integers or both letters. The step defaults to 1 and the sequence counts
+// down when `y` comes before `x`.
+fn sequence(inner: &str) -> Option<Vec<String>> {
+	let parts: Vec<&str> = inner.split("..").collect();
+	let step = match parts.as_slice() {
+		[_, _] => 1,
+		[_, _, step] => step.parse::<i64>().ok()?.unsigned_abs().max(1) as usize,
+		_ => return None,
+	};
+
+	if let (Ok(start), Ok(end)) = (parts[0].parse::<i64>(), parts[1].parse::<i64>()) {
+		let items: Vec<i64> = if start <= end {
+			(start..=end).step_by(step).collect()
+		} else {
+			(end..=start).rev().step_by(step).collect()
+		};
+		return Some(items.iter().map(|item| item.to_string()).collect());
+	}
+
+	let (start, end) = match (parts[0].as_bytes(), parts[1].as_bytes()) {
+		([start], [end]) if start.is_ascii_alphabetic() && end.is_ascii_alphabetic() => (*start, *end),
+		_ => return None,
+	};
+	let items: Vec<u8> = if start <= end {
+		(start..=end).step_by(step).collect()
+	} else {
+		(end..=start).rev().step_by(step).collect()
+	};
+	Some(items.iter().map(|&item| (item as char).to_string()).collect())
+}
@@ -1,16 +1,87 @@
-
 use std::env;
+use std::path::Path;
+
+use crate::pwd_cmd;
+use crate::variables::Variables;
+
+// Change to a directory, `$HOME` without one. `cd -` goes back to `$OLDPWD`
+// and a relative name that is not in the current directory is looked for in
+// the directories of `$CDPATH`; both print where they ended up. The directory
+// left behind becomes `OLDPWD` and the new one `PWD`, kept as the path that
+// led there rather than with its symbolic links resolved.
+pub fn cd(vars: &mut Variables, args: &[String]) -> i32 {
+	let path = match args.first().map(String::as_str) {
+		None => match vars.get("HOME") {
+			Some(home) => home,
+			None => {
+				shell_error!("cd: HOME not set");
+				return 1;
+			}
+		},
+		Some("-") => match vars.get("OLDPWD") {
+			Some(previous) => previous,
+			None => {
+				shell_error!("cd: OLDPWD not set");
+				return 1;
+			}
+		},
+		Some(path) => path.to_string(),
+	};
+	let found = search_cdpath(vars, &path);
+	let old = pwd_cmd::logical_pwd(vars);
+	let status = change_directory(vars, found.as_deref().unwrap_or(&path));
+	if status != 0 {
+		return status;
+	}
+	vars.set("OLDPWD", &old.to_string_lossy()).ok();
+	vars.export("OLDPWD");
+	if args.first().is_some_and(|arg| arg == "-") {
+		println!("{}", path);
+	} else if found.is_some() {
+		println!("{}", pwd_cmd::logical_pwd(vars).display());
+	}
+	0
+}
 
-pub fn change_directory(path: &str) {
-	let path_expanded: String;
+// The first directory of `$CDPATH` holding `path`, for a path that starts
+// with neither `/`, `~` nor `.` and that the current directory does not have.
+// An empty entry stands for the current directory.
+fn search_cdpath(vars: &Variables, path: &str) -> Option<String> {
+	let cdpath = vars.get("CDPATH")?;
+	if path.starts_with(['/', '~', '.']) || Path::new(path).is_dir() {
+		return None;
+	}
+	cdpath
+		.split(':')
+		.map(|dir| if dir.is_empty() { "." } else { dir.trim_end_matches('/') })
+		.map(|dir| format!("{}/{}", dir, path))
+		.find(|candidate| Path::new(&expand_home(candidate)).is_dir())
+}
+
+// Change to `path` as reached from `$PWD`, with `..` taking off the name
+// before it, and fall back to following the links when that path does not
+// work out. `PWD` is updated to match.
+pub fn change_directory(vars: &mut Variables, path: &str) -> i32 {
+	let path_home = expand_home(path);
+	let logical = pwd_cmd::join_logical(&pwd_cmd::logical_pwd(vars), Path::new(&path_home));
+	let pwd = if env::set_current_dir(&logical).is_ok() {
+		logical
+	} else if env::set_current_dir(&path_home).is_ok() {
+		pwd_cmd::get_pwd()
+	} else {
+		shell_error!("cd: {}: No such file or directory", path);
+		return 1;
+	};
+	vars.set("PWD", &pwd.to_string_lossy()).ok();
+	vars.export("PWD");
+	0
+}
+
+fn expand_home(path: &str) -> String {
 	if path == "~" || path.starts_with("~/") {
 		let home = env::var("HOME").unwrap_or("/".to_string());
-		path_expanded = path.replacen("~", &home, 1);
+		path.replacen("~", &home, 1)
 	} else {
-		path_expanded = path.to_string()
-	}
-	match env::set_current_dir(path_expanded) {
-		Ok(()) => (),
-		Err(_) => println!("cd: {}: No such file or directory", path),
+		path.to_string()
 	}
-}
\ No newline at end of file
+}
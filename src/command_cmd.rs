@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::alias;
+use crate::compound::ControlFlow;
+use crate::executable_cmd;
+use crate::type_cmd::{self, TypeFlags};
+use crate::Shell;
+
+// The `PATH` `command -p` searches, which finds the standard utilities
+// whatever `$PATH` holds.
+const DEFAULT_PATH: &str = "/usr/bin:/bin:/usr/sbin:/sbin";
+
+// `command [-pvV] name [args]` runs `name` as a builtin or from `PATH`,
+// skipping any function with that name. `-v` prints how the name would be
+// found and `-V` describes it the way `type` does.
+pub fn command(shell: &mut Shell, args: &[String], env: &HashMap<String, String>) -> Result<i32, ControlFlow> {
+	let (mut default_path, mut short, mut verbose) = (false, false, false);
+	let mut rest = args;
+	while let Some(arg) = rest.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		rest = &rest[1..];
+		if arg == "--" {
+			break;
+		}
+		for flag in arg.chars().skip(1) {
+			match flag {
+				'p' => default_path = true,
+				'v' => short = true,
+				'V' => verbose = true,
+				_ => {
+					shell_error!("command: -{}: invalid option", flag);
+					eprintln!("command: usage: command [-pVv] command [arg ...]");
+					return Ok(2);
+				}
+			}
+		}
+	}
+
+	if verbose || short {
+		let mut status = 0;
+		for name in rest {
+			let found = if verbose {
+				type_cmd::check_type(&shell.aliases, &shell.functions, TypeFlags::default(), name) == 0
+			} else {
+				describe(shell, name, default_path)
+			};
+			if !found {
+				status = 1;
+			}
+		}
+		return Ok(status);
+	}
+
+	let Some(cmd) = rest.first() else {
+		return Ok(0);
+	};
+	if !default_path || type_cmd::BUILTIN_COMMANDS.contains(&cmd.as_str()) {
+		return crate::run_builtin(shell, rest, env);
+	}
+	match type_cmd::search_dirs(DEFAULT_PATH, cmd) {
+		Some(path) => Ok(executable_cmd::run_executable(&mut shell.jobs, &path, cmd, &rest[1..], env, true)),
+		None => {
+			shell_error!("{}: command not found", cmd);
+			Ok(127)
+		}
+	}
+}
+
+// Print what running `name` would run: an alias as it would be defined, the
+// name of a keyword, function or builtin, or the path of a file.
+fn describe(shell: &Shell, name: &str, default_path: bool) -> bool {
+	if let Some(value) = shell.aliases.get(name) {
+		println!("{}", alias::format_alias(name, value));
+		return true;
+	}
+	if type_cmd::KEYWORDS.contains(&name)
+		|| shell.functions.get(name).is_some()
+		|| type_cmd::BUILTIN_COMMANDS.contains(&name)
+	{
+		println!("{}", name);
+		return true;
+	}
+	let path = if default_path {
+		type_cmd::search_dirs(DEFAULT_PATH, name)
+	} else {
+		type_cmd::search_path(name)
+	};
+	match path {
+		Some(path) => {
+			println!("{}", path);
+			true
+		}
+		None => false,
+	}
+}
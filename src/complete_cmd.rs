@@ -0,0 +1,127 @@
+use crate::completion::{self, Action, CompletionOption, CompletionSpec, Completions};
+
+const USAGE: &str = "complete: usage: complete [-abcdfpr] [-o option] [-W wordlist] [-F function] [-C command] [name ...]";
+
+pub fn complete(completions: &mut Completions, args: &[String]) -> i32 {
+	let mut spec = CompletionSpec::default();
+	let mut print = false;
+	let mut remove = false;
+
+	// flags can be grouped, and the ones taking a value take the rest of
+	// their argument or the next one
+	let mut i = 0;
+	while i < args.len() {
+		let arg = &args[i];
+		i += 1;
+		if arg == "--" {
+			break;
+		}
+		let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+			i -= 1;
+			break;
+		};
+		for (at, flag) in flags.char_indices() {
+			let action = match flag {
+				'p' => {
+					print = true;
+					continue;
+				}
+				'r' => {
+					remove = true;
+					continue;
+				}
+				'a' => Action::Aliases,
+				'b' => Action::Builtins,
+				'c' => Action::Commands,
+				'd' => Action::Directories,
+				'f' => Action::Files,
+				'v' => Action::Variables,
+				'o' | 'W' | 'F' | 'C' => {
+					let rest = &flags[at + 1..];
+					let value = if !rest.is_empty() {
+						rest.to_string()
+					} else if let Some(value) = args.get(i) {
+						i += 1;
+						value.clone()
+					} else {
+						shell_error!("complete: -{}: option requires an argument", flag);
+						eprintln!("{}", USAGE);
+						return 2;
+					};
+					match flag {
+						'o' => match CompletionOption::parse(&value) {
+							Some(option) => {
+								if !spec.options.contains(&option) {
+									spec.options.push(option);
+								}
+							}
+							None => {
+								shell_error!("complete: {}: invalid option name", value);
+								return 2;
+							}
+						},
+						'W' => spec.actions.push(Action::Words(value)),
+						'F' => spec.actions.push(Action::Function(value)),
+						_ => spec.actions.push(Action::Command(value)),
+					}
+					break;
+				}
+				_ => {
+					shell_error!("complete: -{}: invalid option", flag);
+					eprintln!("{}", USAGE);
+					return 2;
+				}
+			};
+			spec.actions.push(action);
+		}
+	}
+	let names = &args[i..];
+
+	if remove {
+		if names.is_empty() {
+			completions.clear();
+			return 0;
+		}
+		return for_each_spec(names, |name| completions.remove(name));
+	}
+	if print || (names.is_empty() && spec.actions.is_empty() && spec.options.is_empty()) {
+		if names.is_empty() {
+			for (name, spec) in completions.list() {
+				if let Some(line) = completion::format_spec(name, spec) {
+					println!("{}", line);
+				}
+			}
+			return 0;
+		}
+		return for_each_spec(names, |name| match completions.get(name) {
+			Some(spec) => {
+				if let Some(line) = completion::format_spec(name, spec) {
+					println!("{}", line);
+				}
+				true
+			}
+			None => false,
+		});
+	}
+	if names.is_empty() {
+		eprintln!("{}", USAGE);
+		return 2;
+	}
+	for name in names {
+		completions.set(name, spec.clone());
+	}
+	0
+}
+
+// Run `found` for each command name, complaining about the ones without a
+// spec.
+fn for_each_spec(names: &[String], mut found: impl FnMut(&str) -> bool) -> i32 {
+	let mut status = 0;
+	for name in names {
+		if !found(name) {
+			shell_error!("complete: {}: no completion specification", name);
+			status = 1;
+		}
+	}
+	status
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::expand;
+use crate::functions;
+use crate::type_cmd::{self, BUILTIN_COMMANDS};
+use crate::Shell;
+
+// Where the candidates of a completion spec come from.
+#[derive(Clone)]
+pub enum Action {
+	// `-W`: the words of a list
+	Words(String),
+	// `-F`: a shell function that leaves the candidates in `COMPREPLY`
+	Function(String),
+	// `-C`: a command that prints one candidate per line
+	Command(String),
+	// `-a`
+	Aliases,
+	// `-b`
+	Builtins,
+	// `-c`: builtins, aliases, functions and executables
+	Commands,
+	// `-d`
+	Directories,
+	// `-f`
+	Files,
+	// `-v`
+	Variables,
+	// worked out by the shell itself
+	Native(fn(&Shell, &Request) -> Vec<String>),
+}
+
+// The `-o` options of a completion spec.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompletionOption {
+	// no space after a unique match
+	NoSpace,
+	// the candidates are file names: a directory gets a `/` and special
+	// characters are escaped
+	Filenames,
+	// complete file names when nothing else matches
+	Default,
+	// complete directory names when nothing else matches
+	Dirnames,
+	// add the directory names to the other candidates
+	PlusDirs,
+}
+
+impl CompletionOption {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name {
+			"nospace" => Some(CompletionOption::NoSpace),
+			"filenames" => Some(CompletionOption::Filenames),
+			"default" => Some(CompletionOption::Default),
+			"dirnames" => Some(CompletionOption::Dirnames),
+			"plusdirs" => Some(CompletionOption::PlusDirs),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			CompletionOption::NoSpace => "nospace",
+			CompletionOption::Filenames => "filenames",
+			CompletionOption::Default => "default",
+			CompletionOption::Dirnames => "dirnames",
+			CompletionOption::PlusDirs => "plusdirs",
+		}
+	}
+}
+
+// How the arguments of a command are completed.
+#[derive(Clone, Default)]
+pub struct CompletionSpec {
+	pub actions: Vec<Action>,
+	pub options: Vec<CompletionOption>,
+}
+
+impl CompletionSpec {
+	fn has(&self, option: CompletionOption) -> bool {
+		self.options.contains(&option)
+	}
+}
+
+// The completion specs by command name.
+pub struct Completions {
+	specs: HashMap<String, CompletionSpec>,
+}
+
+impl Completions {
+	// The shell completes the names `unalias` and `unset` take on its own.
+	pub fn new() -> Self {
+		let mut completions = Completions {
+			specs: HashMap::new(),
+		};
+		completions.set("unalias", native(|shell, _| alias_names(shell)));
+		completions.set("unset", native(|shell, _| shell.vars.names()));
+		completions
+	}
+
+	pub fn set(&mut self, command: &str, spec: CompletionSpec) {
+		self.specs.insert(command.to_string(), spec);
+	}
+
+	pub fn get(&self, command: &str) -> Option<&CompletionSpec> {
+		self.specs.get(command)
+	}
+
+	pub fn remove(&mut self, command: &str) -> bool {
+		self.specs.remove(command).is_some()
+	}
+
+	pub fn clear(&mut self) {
+		self.specs.clear();
+	}
+
+	// Every spec sorted by command name.
+	pub fn list(&self) -> Vec<(&str, &CompletionSpec)> {
+		let mut specs: Vec<(&str, &CompletionSpec)> =
+			self.specs.iter().map(|(command, spec)| (command.as_str(), spec)).collect();
+		specs.sort_by_key(|&(command, _)| command);
+		specs
+	}
+}
+
+// A spec the way `complete -p` shows it, ready to be read back in. None for
+// the ones the shell works out itself.
+pub fn format_spec(command: &str, spec: &CompletionSpec) -> Option<String> {
+	let mut line = "complete".to_string();
+	for option in &spec.options {
+		line.push_str(" -o ");
+		line.push_str(option.name());
+	}
+	for action in &spec.actions {
+		let quote = |text: &str| format!("'{}'", text.replace('\'', "'\\''"));
+		let flag = match action {
+			Action::Words(list) => format!("-W {}", quote(list)),
+			Action::Function(name) => format!("-F {}", name),
+			Action::Command(command) => format!("-C {}", quote(command)),
+			Action::Aliases => "-a".to_string(),
+			Action::Builtins => "-b".to_string(),
+			Action::Commands => "-c".to_string(),
+			Action::Directories => "-d".to_string(),
+			Action::Files => "-f".to_string(),
+			Action::Variables => "-v".to_string(),
+			Action::Native(_) => return None,
+		};
+		line.push(' ');
+		line.push_str(&flag);
+	}
+	line.push(' ');
+	line.push_str(command);
+	Some(line)
+}
+
+fn native(complete: fn(&Shell, &Request) -> Vec<String>) -> CompletionSpec {
+	CompletionSpec {
+		actions: vec![Action::Native(complete)],
+		options: Vec::new(),
+	}
+}
+
+// The command line being completed, split into the words of the command the
+// cursor is in. The last word is the one being completed, and may be empty.
+pub struct Request {
+	pub line: String,
+	pub point: usize,
+	pub words: Vec<String>,
+	// where the word being completed starts in the line
+	pub start: usize,
+}
+
+impl Request {
+	// Split the line up to `point`. A backslash keeps a blank or an operator
+	// character inside a word.
+	pub fn new(line: &str, point: usize) -> Self {
+		let mut words: Vec<String> = vec![String::new()];
+		let mut start = 0;
+		let mut escaped = false;
+		for (i, ch) in line[..point].char_indices() {
+			let last = words.len() - 1;
+			match ch {
+				_ if escaped => {
+					escaped = false;
+					words[last].push(ch);
+				}
+				'\\' => escaped = true,
+				// a new command starts
+				';' | '|' | '&' | '(' | ')' => {
+					words = vec![String::new()];
+					start = i + 1;
+				}
+				' ' | '\t' | '<' | '>' => {
+					if !words[last].is_empty() {
+						words.push(String::new());
+					}
+					start = i + 1;
+				}
+				_ => words[last].push(ch),
+			}
+		}
+		Request {
+			line: line.to_string(),
+			point,
+			words,
+			start,
+		}
+	}
+
+	pub fn word(&self) -> &str {
+		self.words.last().map_or("", String::as_str)
+	}
+
+	fn previous(&self) -> &str {
+		let len = self.words.len();
+		if len < 2 {
+			return "";
+		}
+		&self.words[len - 2]
+	}
+}
+
+// What Tab offers for a word.
+pub struct Matches {
+	pub words: Vec<String>,
+	// the words are file names
+	pub filenames: bool,
+	pub nospace: bool,
+}
+
+// Work out the candidates for the word being completed: a `$NAME` is a
+// variable, the first word is a command and the other words of a command
+// with a completion spec come from that spec. Anything else is a file name.
+pub fn complete(shell: &mut Shell, request: &Request) -> Matches {
+	let word = request.word();
+	if let Some(prefix) = word.strip_prefix('$') {
+		let names = shell.vars.names().into_iter().filter(|name| name.starts_with(prefix));
+		return matches(names.map(|name| format!("${}", name)).collect(), false);
+	}
+	if request.words.len() == 1 && !word.contains('/') {
+		return matches(command_names(shell, word), false);
+	}
+	let spec = shell.completions.get(&request.words[0]).cloned();
+	let Some(spec) = spec else {
+		return matches(files(word, false), true);
+	};
+
+	let mut words: Vec<String> = Vec::new();
+	let mut filenames = spec.has(CompletionOption::Filenames);
+	for action in &spec.actions {
+		if matches!(action, Action::Directories | Action::Files) {
+			filenames = true;
+		}
+		words.extend(generate(shell, action, request));
+	}
+	words.retain(|candidate| candidate.starts_with(word));
+	if spec.has(CompletionOption::PlusDirs) {
+		words.extend(files(word, true));
+		filenames = true;
+	}
+	if words.is_empty() && spec.has(CompletionOption::Default) {
+		words = files(word, false);
+		filenames = true;
+	} else if words.is_empty() && spec.has(CompletionOption::Dirnames) {
+		words = files(word, true);
+		filenames = true;
+	}
+	words.sort();
+	words.dedup();
+	Matches {
+		words,
+		filenames,
+		nospace: spec.has(CompletionOption::NoSpace),
+	}
+}
+
+fn matches(words: Vec<String>, filenames: bool) -> Matches {
+	Matches {
+		words,
+		filenames,
+		nospace: false,
+	}
+}
+
+// The candidates one action of a spec offers for the word being completed.
+fn generate(shell: &mut Shell, action: &Action, request: &Request) -> Vec<String> {
+	let word = request.word();
+	match action {
+		Action::Words(list) => list.split_whitespace().map(str::to_string).collect(),
+		Action::Function(name) => {
+			let Some(body) = shell.functions.get(name) else {
+				return Vec::new();
+			};
+			let cword = (request.words.len() - 1).to_string();
+			for (name, value) in [
+				("COMP_LINE", request.line.as_str()),
+				("COMP_POINT", &request.point.to_string()),
+				("COMP_CWORD", &cword),
+			] {
+				shell.vars.set(name, value).ok();
+			}
+			shell.vars.set_array("COMP_WORDS", &request.words).ok();
+			shell.vars.unset("COMPREPLY").ok();
+			let args = [request.words[0].clone(), word.to_string(), request.previous().to_string()];
+			functions::call(shell, &body, &args);
+			shell.vars.elements("COMPREPLY").unwrap_or_default()
+		}
+		Action::Command(command) => {
+			let args: Vec<String> = [&request.words[0], word, request.previous()]
+				.iter()
+				.map(|arg| format!("'{}'", arg.replace('\'', "'\\''")))
+				.collect();
+			let (output, _) = expand::command_substitution(shell, &format!("{} {}", command, args.join(" ")));
+			output.lines().map(str::to_string).collect()
+		}
+		Action::Aliases => alias_names(shell),
+		Action::Builtins => BUILTIN_COMMANDS.iter().map(|name| name.to_string()).collect(),
+		Action::Commands => command_names(shell, word),
+		Action::Directories => files(word, true),
+		Action::Files => files(word, false),
+		Action::Variables => shell.vars.names(),
+		Action::Native(complete) => complete(shell, request),
+	}
+}
+
+fn alias_names(shell: &Shell) -> Vec<String> {
+	shell.aliases.list().into_iter().map(|(name, _)| name.to_string()).collect()
+}
+
+// The builtins, aliases, functions and executables in `PATH` starting with
+// `prefix`, sorted.
+fn command_names(shell: &Shell, prefix: &str) -> Vec<String> {
+	let mut names: Vec<String> = BUILTIN_COMMANDS.iter().map(|name| name.to_string()).collect();
+	names.extend(alias_names(shell));
+	names.extend(shell.functions.names().map(str::to_string));
+	names.retain(|name| name.starts_with(prefix));
+	names.extend(type_cmd::executables_starting_with(prefix));
+	names.sort();
+	names.dedup();
+	names
+}
+
+// The paths starting with `word`, or only the directories among them. Names
+// starting with a dot only match when the word for the name does too.
+pub fn files(word: &str, directories: bool) -> Vec<String> {
+	let (dir, prefix) = match word.rfind('/') {
+		Some(i) => word.split_at(i + 1),
+		None => ("", word),
+	};
+	let Ok(entries) = fs::read_dir(search_dir(dir)) else {
+		return Vec::new();
+	};
+	let mut paths: Vec<String> = entries
+		.flatten()
+		.map(|entry| entry.file_name().to_string_lossy().to_string())
+		.filter(|name| name.starts_with(prefix) && (!name.starts_with('.') || prefix.starts_with('.')))
+		.map(|name| format!("{}{}", dir, name))
+		.filter(|path| !directories || is_dir(path))
+		.collect();
+	paths.sort();
+	paths
+}
+
+// Whether a path, which may start with `~/`, names a directory.
+pub fn is_dir(path: &str) -> bool {
+	let (dir, name) = match path.rfind('/') {
+		Some(i) => path.split_at(i + 1),
+		None => ("", path),
+	};
+	Path::new(&search_dir(dir)).join(name).is_dir()
+}
+
+fn search_dir(dir: &str) -> String {
+	match dir.strip_prefix("~/") {
+		Some(rest) => format!("{}/{}", env::var("HOME").unwrap_or_default(), rest),
+		None if dir.is_empty() => ".".to_string(),
+		None => dir.to_string(),
+	}
+}
@@ -0,0 +1,936 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use nix::unistd::{fork, setpgid, ForkResult};
+
+use crate::alias::Aliases;
+use crate::arith;
+use crate::cond_expr;
+use crate::glob;
+use crate::jobs;
+use crate::lexer::{self, ParseError, Token};
+use crate::pipeline::{self, Stage};
+use crate::proc_subst;
+use crate::redirect::{self, Redirect, RedirectKind};
+use crate::signals;
+use crate::traps;
+use crate::utils;
+use crate::variables;
+use crate::Shell;
+
+// Reserved words that continue or close a compound command, so they cannot
+// start a command of their own.
+const CLOSING_WORDS: [&str; 8] = ["then", "elif", "else", "fi", "do", "done", "esac", "}"];
+
+// Operators that end the commands of a `case` item.
+const CASE_TERMINATORS: [&str; 3] = [";;", ";&", ";;&"];
+
+// Commands separated by `;` or newlines, run one after the other.
+pub struct CommandList {
+	items: Vec<AndOr>,
+}
+
+// Pipelines joined by `&&` and `||`, each deciding whether the next one runs.
+struct AndOr {
+	first: Pipeline,
+	rest: Vec<(Connector, Pipeline)>,
+	// ended by `&`, so it runs as a job without the shell waiting for it
+	background: bool,
+	// the words it was parsed from, for the job table
+	text: String,
+}
+
+enum Connector {
+	And,
+	Or,
+}
+
+// Commands joined by `|`, with `!` in front when the status is inverted.
+struct Pipeline {
+	commands: Vec<Command>,
+	negated: bool,
+	// the words it was parsed from, for the job table when it stops
+	text: String,
+}
+
+// One command of a pipeline.
+pub enum Command {
+	Simple(Stage),
+	Compound(Compound, Vec<Redirect>),
+	// `name() body` or `function name body`
+	FunctionDef(String, Rc<CommandList>),
+}
+
+pub enum Compound {
+	// `{ list; }`
+	Group(CommandList),
+	// `if` and every `elif` with their bodies, then the `else` body
+	If {
+		branches: Vec<(CommandList, CommandList)>,
+		otherwise: Option<CommandList>,
+	},
+	// `while` runs the body as long as the condition succeeds, `until` as long
+	// as it fails
+	While {
+		condition: CommandList,
+		body: CommandList,
+		until: bool,
+	},
+	// `for NAME in WORDS`, with the words kept raw until the loop starts
+	For {
+		name: String,
+		words: Vec<String>,
+		body: CommandList,
+	},
+	// `for (( init; condition; step ))`
+	ArithmeticFor {
+		init: String,
+		condition: String,
+		step: String,
+		body: CommandList,
+	},
+	Case {
+		word: String,
+		items: Vec<CaseItem>,
+	},
+	// `(( expr ))`
+	Arithmetic(String),
+	// `[[ expr ]]`
+	Conditional(String),
+}
+
+// One `pattern | pattern) commands ;;` of a `case`.
+pub struct CaseItem {
+	patterns: Vec<String>,
+	body: CommandList,
+	terminator: CaseTerminator,
+}
+
+enum CaseTerminator {
+	// `;;` stops after this item
+	Break,
+	// `;&` runs the next item's commands without testing its patterns
+	FallThrough,
+	// `;;&` goes on testing the patterns of the items that follow
+	Continue,
+}
+
+// Parse command text into the list of commands it holds. Fails with
+// `ParseError::Incomplete` when the text ends in the middle of a command and
+// `at_eof` says more text could still follow.
+pub fn parse(text: &str, at_eof: bool, aliases: &Aliases) -> Result<CommandList, ParseError> {
+	parse_from(text, None, at_eof, aliases)
+}
+
+// Parse text from a script that starts on line `first_line`, so each simple
+// command knows the line it comes from.
+pub fn parse_lines(text: &str, first_line: usize, at_eof: bool, aliases: &Aliases) -> Result<CommandList, ParseError> {
+	parse_from(text, Some(first_line), at_eof, aliases)
+}
+
+fn parse_from(text: &str, first_line: Option<usize>, at_eof: bool, aliases: &Aliases) -> Result<CommandList, ParseError> {
+	let lexer::Tokens {
+		tokens,
+		lines,
+		heredocs,
+	} = lexer::tokenize(text, at_eof)?;
+	let mut parser = Parser {
+		tokens,
+		lines,
+		first_line,
+		pos: 0,
+		heredocs,
+		aliases,
+	};
+	match parser.parse_list(&[]) {
+		Err(ParseError::Incomplete) if at_eof => {
+			Err(ParseError::Syntax("syntax error: unexpected end of file".to_string()))
+		}
+		result => result,
+	}
+}
+
+// Why a command list stopped before running to its end. It travels up
+// through every enclosing command until something handles it.
+pub enum ControlFlow {
+	// `return` from the innermost function with this status
+	Return(i32),
+	// `break` and `continue` with the number of enclosing loops still to
+	// leave, the last of them being the one to stop or go on with
+	Break(usize),
+	Continue(usize),
+	// Ctrl+C, which stops everything up to the command line being run
+	Interrupt,
+}
+
+// Run a list outside of any function, where `return` leaves the shell the
+// way `exit` does. A trap run while the shell is being interrupted still runs
+// to its end.
+pub fn run_top_level(shell: &mut Shell, list: &CommandList) -> i32 {
+	let interrupted = signals::take_interrupt();
+	let status = match run_list(shell, list) {
+		Ok(status) => status,
+		Err(ControlFlow::Return(status)) => crate::exit(shell, status),
+		// only a subshell started inside a loop can see one
+		Err(ControlFlow::Break(_) | ControlFlow::Continue(_)) => 0,
+		Err(ControlFlow::Interrupt) => 130,
+	};
+	if interrupted {
+		signals::interrupt();
+	}
+	status
+}
+
+pub fn run_list(shell: &mut Shell, list: &CommandList) -> Result<i32, ControlFlow> {
+	let mut status = 0;
+	for and_or in &list.items {
+		status = match and_or.background {
+			true => run_background(shell, and_or),
+			false => run_and_or(shell, and_or)?,
+		};
+	}
+	Ok(status)
+}
+
+fn run_and_or(shell: &mut Shell, and_or: &AndOr) -> Result<i32, ControlFlow> {
+	let mut status = run_pipeline(shell, &and_or.first, and_or.rest.is_empty())?;
+	for (i, (connector, pipeline)) in and_or.rest.iter().enumerate() {
+		let runs = match connector {
+			Connector::And => status == 0,
+			Connector::Or => status != 0,
+		};
+		if runs {
+			status = run_pipeline(shell, pipeline, i + 1 == and_or.rest.len())?;
+		}
+	}
+	Ok(status)
+}
+
+// Run the condition of an `if`, `while` or `until`, where a failing command
+// does not make `set -e` leave the shell.
+fn run_condition(shell: &mut Shell, condition: &CommandList) -> Result<i32, ControlFlow> {
+	shell.condition_depth += 1;
+	let status = run_list(shell, condition);
+	shell.condition_depth -= 1;
+	status
+}
+
+// Start a job in a child of its own process group and leave it running,
+// printing its job number and process ID.
+fn run_background(shell: &mut Shell, and_or: &AndOr) -> i32 {
+	io::stdout().flush().ok();
+	match unsafe { fork() } {
+		Ok(ForkResult::Child) => {
+			jobs::start_job(None, false);
+			signals::reset();
+			let status = match run_and_or(shell, and_or) {
+				Ok(status) | Err(ControlFlow::Return(status)) => status,
+				Err(ControlFlow::Break(_) | ControlFlow::Continue(_)) => 0,
+				Err(ControlFlow::Interrupt) => signals::exit_interrupted(),
+			};
+			io::stdout().flush().ok();
+			std::process::exit(status);
+		}
+		Ok(ForkResult::Parent { child }) => {
+			// set here too so the group exists whichever process runs first
+			setpgid(child, child).ok();
+			let id = shell.jobs.add(vec![child], &and_or.text);
+			shell.last_bg_pid = Some(child.as_raw() as u32);
+			println!("[{}] {}", id, child);
+			0
+		}
+		Err(err) => {
+			shell_error!("fork: {}", err);
+			1
+		}
+	}
+}
+
+// Run a pipeline of an and-or list. Its status is that of its last command,
+// or with `set -o pipefail` that of the last command to fail. `PIPESTATUS`
+// gets the status of every command. Under `set -e` the shell leaves when the
+// pipeline fails, unless it is inverted with `!` or it is not the `last` of
+// the list.
+fn run_pipeline(shell: &mut Shell, pipeline: &Pipeline, last: bool) -> Result<i32, ControlFlow> {
+	let checked = pipeline.negated || !last;
+	if checked {
+		shell.condition_depth += 1;
+	}
+	let statuses = match pipeline.commands.as_slice() {
+		[command] => run_command(shell, command).map(|status| vec![status]),
+		commands => Ok(pipeline::run_pipeline(shell, commands, &pipeline.text)),
+	};
+	if checked {
+		shell.condition_depth -= 1;
+	}
+	let statuses = statuses?;
+	let pipestatus: Vec<String> = statuses.iter().map(i32::to_string).collect();
+	shell.vars.set_array("PIPESTATUS", &pipestatus).ok();
+	let mut status = match shell.options.pipefail {
+		true => statuses.iter().rev().find(|&&status| status != 0).copied().unwrap_or(0),
+		false => statuses.last().copied().unwrap_or(0),
+	};
+	if pipeline.negated {
+		status = (status == 0) as i32;
+	}
+	shell.last_status = status;
+	traps::run_pending(shell);
+	if signals::interrupted() {
+		shell.last_status = 130;
+		return Err(ControlFlow::Interrupt);
+	}
+	if status != 0 && !checked && shell.options.errexit && shell.condition_depth == 0 {
+		crate::exit(shell, status);
+	}
+	Ok(status)
+}
+
+pub fn run_command(shell: &mut Shell, command: &Command) -> Result<i32, ControlFlow> {
+	let (compound, redirects) = match command {
+		Command::Simple(stage) => return crate::run_simple_command(shell, stage),
+		Command::Compound(compound, redirects) => (compound, redirects),
+		Command::FunctionDef(name, body) => {
+			shell.functions.define(name, Rc::clone(body));
+			return Ok(0);
+		}
+	};
+
+	let mut substitutions = Vec::new();
+	let guard = match crate::expand_redirects(shell, redirects, &mut substitutions)
+		.and_then(|redirects| redirect::apply(&redirects))
+	{
+		Ok(guard) => guard,
+		Err(err) => {
+			shell_error!("{}", err);
+			proc_subst::reap(substitutions);
+			return Ok(1);
+		}
+	};
+	let status = run_compound(shell, compound);
+	drop(guard);
+	proc_subst::reap(substitutions);
+	status
+}
+
+fn run_compound(shell: &mut Shell, compound: &Compound) -> Result<i32, ControlFlow> {
+	match compound {
+		Compound::Group(list) => run_list(shell, list),
+		Compound::If {
+			branches,
+			otherwise,
+		} => {
+			for (condition, body) in branches {
+				if run_condition(shell, condition)? == 0 {
+					return run_list(shell, body);
+				}
+			}
+			otherwise.as_ref().map_or(Ok(0), |body| run_list(shell, body))
+		}
+		Compound::While { .. } | Compound::For { .. } | Compound::ArithmeticFor { .. } => {
+			shell.loop_depth += 1;
+			let status = run_loop(shell, compound);
+			shell.loop_depth -= 1;
+			status
+		}
+		Compound::Case { word, items } => run_case(shell, word, items),
+		// succeeds when the expression is non-zero
+		Compound::Arithmetic(expr) => match evaluate(shell, expr) {
+			Ok(value) => Ok(if value != 0 { 0 } else { 1 }),
+			Err(err) => {
+				shell_error!("{}", err);
+				Ok(1)
+			}
+		},
+		Compound::Conditional(expr) => match cond_expr::evaluate(shell, expr) {
+			Ok(true) => Ok(0),
+			Ok(false) => Ok(1),
+			Err(err) => {
+				shell_error!("{}", err);
+				Ok(2)
+			}
+		},
+	}
+}
+
+// Run a `while`, `until` or `for` loop.
+fn run_loop(shell: &mut Shell, compound: &Compound) -> Result<i32, ControlFlow> {
+	match compound {
+		Compound::While {
+			condition,
+			body,
+			until,
+		} => {
+			let mut status = 0;
+			while (run_condition(shell, condition)? == 0) != *until {
+				if !run_body(shell, body, &mut status)? {
+					break;
+				}
+			}
+			Ok(status)
+		}
+		Compound::For { name, words, body } => {
+			let items = match utils::parse_args(&words.join(" "), shell) {
+				Ok(items) => items,
+				Err(err) => {
+					shell_error!("{}", err);
+					return Ok(1);
+				}
+			};
+			let mut header = vec!["for".to_string(), name.clone(), "in".to_string()];
+			header.extend(items.iter().map(|item| crate::quote_for_trace(item)));
+			let mut status = 0;
+			for item in items {
+				crate::trace(shell, &header);
+				if let Err(err) = shell.vars.set(name, &item) {
+					shell_error!("{}", err);
+					return Ok(1);
+				}
+				if !run_body(shell, body, &mut status)? {
+					break;
+				}
+			}
+			Ok(status)
+		}
+		Compound::ArithmeticFor {
+			init,
+			condition,
+			step,
+			body,
+		} => {
+			let mut status = 0;
+			if let Err(err) = evaluate(shell, init) {
+				shell_error!("{}", err);
+				return Ok(1);
+			}
+			loop {
+				// an empty condition is always true
+				match evaluate(shell, condition) {
+					Ok(0) if !condition.trim().is_empty() => break,
+					Ok(_) => (),
+					Err(err) => {
+						shell_error!("{}", err);
+						return Ok(1);
+					}
+				}
+				if !run_body(shell, body, &mut status)? {
+					break;
+				}
+				if let Err(err) = evaluate(shell, step) {
+					shell_error!("{}", err);
+					return Ok(1);
+				}
+			}
+			Ok(status)
+		}
+		_ => unreachable!("not a loop"),
+	}
+}
+
+// Run one iteration of a loop body, leaving its status in `status`. Returns
+// whether the loop goes on; a `break` or `continue` meant for an outer loop
+// is passed up with one level fewer to go.
+fn run_body(shell: &mut Shell, body: &CommandList, status: &mut i32) -> Result<bool, ControlFlow> {
+	match run_list(shell, body) {
+		Ok(body_status) => {
+			*status = body_status;
+			Ok(true)
+		}
+		Err(ControlFlow::Break(1)) => {
+			*status = 0;
+			Ok(false)
+		}
+		Err(ControlFlow::Continue(1)) => {
+			*status = 0;
+			Ok(true)
+		}
+		Err(ControlFlow::Break(levels)) => Err(ControlFlow::Break(levels - 1)),
+		Err(ControlFlow::Continue(levels)) => Err(ControlFlow::Continue(levels - 1)),
+		Err(flow) => Err(flow),
+	}
+}
+
+fn run_case(shell: &mut Shell, word: &str, items: &[CaseItem]) -> Result<i32, ControlFlow> {
+	let word = match utils::expand_word(word, shell) {
+		Ok(word) => word,
+		Err(err) => {
+			shell_error!("{}", err);
+			return Ok(1);
+		}
+	};
+	let mut status = 0;
+	let mut falling_through = false;
+	for item in items {
+		if !falling_through {
+			let mut matched = false;
+			for pattern in &item.patterns {
+				match utils::expand_pattern(pattern, shell) {
+					Ok(pattern) if glob::is_match(&pattern, &word) => {
+						matched = true;
+						break;
+					}
+					Ok(_) => (),
+					Err(err) => {
+						shell_error!("{}", err);
+						return Ok(1);
+					}
+				}
+			}
+			if !matched {
+				continue;
+			}
+		}
+		status = run_list(shell, &item.body)?;
+		match item.terminator {
+			CaseTerminator::Break => break,
+			CaseTerminator::FallThrough => falling_through = true,
+			CaseTerminator::Continue => falling_through = false,
+		}
+	}
+	Ok(status)
+}
+
+// Expand and evaluate an arithmetic expression of a compound command.
+fn evaluate(shell: &mut Shell, expr: &str) -> Result<i64, String> {
+	let expr = utils::expand_word(expr, shell)?;
+	arith::evaluate(&expr, &mut shell.vars)
+}
+
+struct Parser<'a> {
+	tokens: Vec<Token>,
+	// the line of each token within the text
+	lines: Vec<usize>,
+	// the script line the text starts on, when it comes from a script
+	first_line: Option<usize>,
+	pos: usize,
+	// heredoc bodies not yet given to their redirect, in order
+	heredocs: VecDeque<String>,
+	aliases: &'a Aliases,
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos)
+	}
+
+	fn peek_word(&self) -> Option<&str> {
+		match self.peek() {
+			Some(Token::Word(word)) => Some(word),
+			_ => None,
+		}
+	}
+
+	fn skip_newlines(&mut self) {
+		while self.peek() == Some(&Token::Newline) {
+			self.pos += 1;
+		}
+	}
+
+	fn unexpected(&self) -> ParseError {
+		match self.peek() {
+			Some(Token::Word(word)) => syntax_error(word),
+			Some(Token::Operator(op)) => syntax_error(op),
+			Some(Token::Newline) => syntax_error("newline"),
+			Some(Token::Arithmetic(expr)) => syntax_error(&format!("(({}))", expr)),
+			Some(Token::Conditional(expr)) => syntax_error(&format!("[[{}]]", expr)),
+			None => ParseError::Incomplete,
+		}
+	}
+
+	fn expect(&mut self, word: &str) -> Result<(), ParseError> {
+		if self.peek_word() != Some(word) {
+			return Err(self.unexpected());
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	fn at_case_terminator(&self) -> bool {
+		matches!(self.peek(), Some(Token::Operator(op)) if CASE_TERMINATORS.contains(op))
+	}
+
+	// Parse commands up to one of the reserved words in `ends`, or the end of
+	// a `case` item, which is left for the caller. With no `ends` the list runs
+	// to the end of the input.
+	fn parse_list(&mut self, ends: &[&str]) -> Result<CommandList, ParseError> {
+		let mut items: Vec<AndOr> = Vec::new();
+		loop {
+			self.skip_newlines();
+			match self.peek_word() {
+				_ if self.peek().is_none() && ends.is_empty() => break,
+				_ if self.at_case_terminator() && !items.is_empty() => break,
+				Some(word) if ends.contains(&word) && !items.is_empty() => break,
+				Some(word) if ends.contains(&word) => return Err(self.unexpected()),
+				_ => (),
+			}
+			items.push(self.parse_and_or()?);
+			match self.peek() {
+				_ if self.at_case_terminator() => (),
+				Some(Token::Operator(";") | Token::Newline) => self.pos += 1,
+				Some(Token::Operator("&")) => {
+					self.pos += 1;
+					if let Some(and_or) = items.last_mut() {
+						and_or.background = true;
+					}
+				}
+				None => (),
+				Some(_) => return Err(self.unexpected()),
+			}
+		}
+		Ok(CommandList { items })
+	}
+
+	fn parse_and_or(&mut self) -> Result<AndOr, ParseError> {
+		let start = self.pos;
+		let first = self.parse_pipeline()?;
+		let mut rest: Vec<(Connector, Pipeline)> = Vec::new();
+		loop {
+			let connector = match self.peek() {
+				Some(Token::Operator("&&")) => Connector::And,
+				Some(Token::Operator("||")) => Connector::Or,
+				_ => break,
+			};
+			self.pos += 1;
+			self.skip_newlines();
+			rest.push((connector, self.parse_pipeline()?));
+		}
+		Ok(AndOr {
+			first,
+			rest,
+			background: false,
+			text: describe(&self.tokens[start..self.pos]),
+		})
+	}
+
+	fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+		let start = self.pos;
+		let negated = self.peek_word() == Some("!");
+		if negated {
+			self.pos += 1;
+		}
+		let mut commands = vec![self.parse_command()?];
+		while self.peek() == Some(&Token::Operator("|")) {
+			self.pos += 1;
+			self.skip_newlines();
+			commands.push(self.parse_command()?);
+		}
+		Ok(Pipeline {
+			commands,
+			negated,
+			text: describe(&self.tokens[start..self.pos]),
+		})
+	}
+
+	// Replace an alias at the start of a command by the tokens of its value,
+	// again as long as that starts with another alias. An alias is not
+	// expanded inside its own value, so aliases cannot loop.
+	fn expand_aliases(&mut self) -> Result<(), ParseError> {
+		let mut expanded: Vec<String> = Vec::new();
+		while let Some(word) = self.peek_word() {
+			if expanded.iter().any(|name| name == word) {
+				break;
+			}
+			let Some(value) = self.aliases.get(word) else {
+				break;
+			};
+			let tokens = lexer::tokenize(value, true)?.tokens;
+			expanded.push(word.to_string());
+			let line = self.lines[self.pos];
+			self.lines.splice(self.pos..self.pos + 1, vec![line; tokens.len()]);
+			self.tokens.splice(self.pos..self.pos + 1, tokens);
+		}
+		Ok(())
+	}
+
+	fn parse_command(&mut self) -> Result<Command, ParseError> {
+		self.expand_aliases()?;
+		let is_function = self.tokens.get(self.pos + 1) == Some(&Token::Operator("("))
+			&& self.tokens.get(self.pos + 2) == Some(&Token::Operator(")"));
+		let compound = match self.peek_word() {
+			Some("function") => return self.parse_function(),
+			Some(_) if is_function => return self.parse_function(),
+			Some("{") => {
+				self.pos += 1;
+				let list = self.parse_list(&["}"])?;
+				self.expect("}")?;
+				Compound::Group(list)
+			}
+			Some("if") => self.parse_if()?,
+			Some(keyword @ ("while" | "until")) => {
+				let until = keyword == "until";
+				self.pos += 1;
+				let condition = self.parse_list(&["do"])?;
+				let body = self.parse_do_group()?;
+				Compound::While {
+					condition,
+					body,
+					until,
+				}
+			}
+			Some("for") => self.parse_for()?,
+			Some("case") => self.parse_case()?,
+			Some(word) if CLOSING_WORDS.contains(&word) => return Err(self.unexpected()),
+			Some(_) => return Ok(Command::Simple(self.parse_simple()?)),
+			None => match self.peek() {
+				Some(Token::Arithmetic(expr)) => {
+					let expr = expr.clone();
+					self.pos += 1;
+					Compound::Arithmetic(expr)
+				}
+				Some(Token::Conditional(expr)) => {
+					let expr = expr.clone();
+					self.pos += 1;
+					Compound::Conditional(expr)
+				}
+				_ => return Err(self.unexpected()),
+			},
+		};
+
+		// redirects after the closing word apply to the whole compound command
+		let stage = self.parse_simple()?;
+		if !stage.command.trim().is_empty() {
+			let word = stage.command.split_whitespace().next().unwrap_or_default();
+			return Err(syntax_error(word));
+		}
+		Ok(Command::Compound(compound, stage.redirects))
+	}
+
+	// Take the words up to the next operator as a simple command, with its
+	// redirects taken out and their heredoc bodies filled in.
+	fn parse_simple(&mut self) -> Result<Stage, ParseError> {
+		let line = self
+			.first_line
+			.map(|first| first + self.lines.get(self.pos).or(self.lines.last()).copied().unwrap_or(0));
+		let mut words: Vec<&str> = Vec::new();
+		while let Some(Token::Word(word)) = self.tokens.get(self.pos) {
+			words.push(word);
+			self.pos += 1;
+		}
+		let (command, mut redirects) =
+			redirect::extract(&words.join(" ")).map_err(ParseError::Syntax)?;
+		for redirect in redirects.iter_mut() {
+			if let RedirectKind::Heredoc { body, .. } = &mut redirect.kind {
+				*body = self.heredocs.pop_front().unwrap_or_default();
+			}
+		}
+		Ok(Stage {
+			command,
+			redirects,
+			line,
+		})
+	}
+
+	fn parse_if(&mut self) -> Result<Compound, ParseError> {
+		self.expect("if")?;
+		let mut branches: Vec<(CommandList, CommandList)> = Vec::new();
+		let mut otherwise = None;
+		loop {
+			let condition = self.parse_list(&["then"])?;
+			self.expect("then")?;
+			let body = self.parse_list(&["elif", "else", "fi"])?;
+			branches.push((condition, body));
+			match self.peek_word() {
+				Some("elif") => self.pos += 1,
+				Some("else") => {
+					self.pos += 1;
+					otherwise = Some(self.parse_list(&["fi"])?);
+					self.expect("fi")?;
+					break;
+				}
+				_ => {
+					self.expect("fi")?;
+					break;
+				}
+			}
+		}
+		Ok(Compound::If {
+			branches,
+			otherwise,
+		})
+	}
+
+	// Parse `name() body` or `function name [()] body`, where the body is any
+	// compound command.
+	fn parse_function(&mut self) -> Result<Command, ParseError> {
+		if self.peek_word() == Some("function") {
+			self.pos += 1;
+		}
+		let name = match self.peek_word() {
+			Some(name) if is_function_name(name) => name.to_string(),
+			Some(name) => {
+				return Err(ParseError::Syntax(format!("`{}': not a valid identifier", name)));
+			}
+			None => return Err(self.unexpected()),
+		};
+		self.pos += 1;
+		if self.peek() == Some(&Token::Operator("(")) {
+			self.pos += 1;
+			if self.peek() != Some(&Token::Operator(")")) {
+				return Err(self.unexpected());
+			}
+			self.pos += 1;
+		}
+
+		self.skip_newlines();
+		let start = self.pos;
+		let body = match self.parse_command()? {
+			body @ Command::Compound(..) => body,
+			_ => return Err(syntax_error(&name)),
+		};
+		let text = describe(&self.tokens[start..self.pos]);
+		let body = CommandList {
+			items: vec![AndOr {
+				first: Pipeline {
+					commands: vec![body],
+					negated: false,
+					text: text.clone(),
+				},
+				rest: Vec::new(),
+				background: false,
+				text,
+			}],
+		};
+		Ok(Command::FunctionDef(name, Rc::new(body)))
+	}
+
+	fn parse_for(&mut self) -> Result<Compound, ParseError> {
+		self.expect("for")?;
+		if let Some(Token::Arithmetic(expr)) = self.peek() {
+			let parts: Vec<String> = expr.split(';').map(str::to_string).collect();
+			let [init, condition, step] = <[String; 3]>::try_from(parts)
+				.map_err(|_| syntax_error(&format!("(({}))", expr)))?;
+			self.pos += 1;
+			if self.peek() == Some(&Token::Operator(";")) {
+				self.pos += 1;
+			}
+			self.skip_newlines();
+			let body = self.parse_do_group()?;
+			return Ok(Compound::ArithmeticFor {
+				init,
+				condition,
+				step,
+				body,
+			});
+		}
+
+		let name = match self.peek_word() {
+			Some(name) if variables::is_valid_name(name) => name.to_string(),
+			Some(name) => {
+				return Err(ParseError::Syntax(format!("`{}': not a valid identifier", name)));
+			}
+			None => return Err(self.unexpected()),
+		};
+		self.pos += 1;
+
+		self.skip_newlines();
+		let mut words: Vec<String> = Vec::new();
+		if self.peek_word() == Some("in") {
+			self.pos += 1;
+			while let Some(Token::Word(word)) = self.peek() {
+				words.push(word.clone());
+				self.pos += 1;
+			}
+		}
+		if self.peek() == Some(&Token::Operator(";")) {
+			self.pos += 1;
+		}
+		self.skip_newlines();
+		let body = self.parse_do_group()?;
+		Ok(Compound::For { name, words, body })
+	}
+
+	fn parse_case(&mut self) -> Result<Compound, ParseError> {
+		self.expect("case")?;
+		let word = match self.peek() {
+			Some(Token::Word(word)) => word.clone(),
+			_ => return Err(self.unexpected()),
+		};
+		self.pos += 1;
+		self.skip_newlines();
+		self.expect("in")?;
+
+		let mut items: Vec<CaseItem> = Vec::new();
+		loop {
+			self.skip_newlines();
+			if self.peek_word() == Some("esac") {
+				break;
+			}
+			if self.peek() == Some(&Token::Operator("(")) {
+				self.pos += 1;
+			}
+			let mut patterns: Vec<String> = Vec::new();
+			loop {
+				match self.peek() {
+					Some(Token::Word(pattern)) => patterns.push(pattern.clone()),
+					_ => return Err(self.unexpected()),
+				}
+				self.pos += 1;
+				if self.peek() != Some(&Token::Operator("|")) {
+					break;
+				}
+				self.pos += 1;
+			}
+			if self.peek() != Some(&Token::Operator(")")) {
+				return Err(self.unexpected());
+			}
+			self.pos += 1;
+
+			self.skip_newlines();
+			let body = if self.at_case_terminator() || self.peek_word() == Some("esac") {
+				CommandList { items: Vec::new() }
+			} else {
+				self.parse_list(&["esac"])?
+			};
+			let terminator = match self.peek() {
+				Some(Token::Operator(";&")) => CaseTerminator::FallThrough,
+				Some(Token::Operator(";;&")) => CaseTerminator::Continue,
+				_ => CaseTerminator::Break,
+			};
+			if self.at_case_terminator() {
+				self.pos += 1;
+			}
+			items.push(CaseItem {
+				patterns,
+				body,
+				terminator,
+			});
+		}
+		self.expect("esac")?;
+		Ok(Compound::Case { word, items })
+	}
+
+	// Parse the `do ... done` body of a loop.
+	fn parse_do_group(&mut self) -> Result<CommandList, ParseError> {
+		self.expect("do")?;
+		let body = self.parse_list(&["done"])?;
+		self.expect("done")?;
+		Ok(body)
+	}
+}
+
+// Function names are more lenient than variable names but still cannot hold
+// anything that would be expanded or quoted.
+fn is_function_name(name: &str) -> bool {
+	!name.is_empty()
+		&& !CLOSING_WORDS.contains(&name)
+		&& name.chars().all(|ch| ch.is_alphanumeric() || "_-.:+@".contains(ch))
+}
+
+// Put tokens back together as command text.
+fn describe(tokens: &[Token]) -> String {
+	let words: Vec<String> = tokens
+		.iter()
+		.map(|token| match token {
+			Token::Word(word) => word.clone(),
+			Token::Operator(op) => op.to_string(),
+			Token::Newline => ";".to_string(),
+			Token::Arithmetic(expr) => format!("(({}))", expr),
+			Token::Conditional(expr) => format!("[[{}]]", expr),
+		})
+		.collect();
+	words.join(" ")
+}
+
+fn syntax_error(token: &str) -> ParseError {
+	ParseError::Syntax(format!("syntax error near unexpected token `{}'", token))
+}
@@ -0,0 +1,316 @@
+use std::fs::{self, Metadata};
+use std::io::IsTerminal;
+use std::os::fd::BorrowedFd;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+use nix::unistd::{access, getegid, geteuid, AccessFlags};
+use regex::Regex;
+
+use crate::arith;
+use crate::glob;
+use crate::param_expand;
+use crate::utils::{self, QuoteTracker};
+use crate::Shell;
+
+// Operators that test a single word, such as a file name.
+const UNARY_OPERATORS: [&str; 25] = [
+	"-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-p", "-r", "-s", "-t", "-u", "-w", "-x",
+	"-G", "-L", "-N", "-O", "-S", "-z", "-n", "-o", "-v",
+];
+
+// Operators that compare two words.
+const BINARY_OPERATORS: [&str; 14] = [
+	"==", "=", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+// Evaluate the expression of a `[[ ... ]]`. Its words are expanded without
+// being split into more words or replaced by the files they match, and an
+// operator only counts as one when it is not quoted. The right side of `==`
+// and `!=` is a pattern and that of `=~` a regular expression, while `&&` and
+// `||` leave out what they do not need.
+pub fn evaluate(shell: &mut Shell, expr: &str) -> Result<bool, String> {
+	let mut parser = Parser {
+		words: split(expr),
+		pos: 0,
+		shell,
+	};
+	let result = parser.or(false)?;
+	match parser.words.get(parser.pos) {
+		Some(word) => Err(syntax_error(word)),
+		None => Ok(result),
+	}
+}
+
+pub fn is_unary(op: &str) -> bool {
+	UNARY_OPERATORS.contains(&op)
+}
+
+pub fn is_binary(op: &str) -> bool {
+	BINARY_OPERATORS.contains(&op)
+}
+
+// Apply a unary operator to a word that is already expanded.
+pub fn test_unary(shell: &mut Shell, op: &str, operand: &str) -> bool {
+	match op {
+		"-z" => operand.is_empty(),
+		"-n" => !operand.is_empty(),
+		"-o" => shell.options.list().iter().any(|&(name, on)| name == operand && on),
+		"-v" => match operand.strip_suffix(']').and_then(|operand| operand.split_once('[')) {
+			Some((name, subscript)) => matches!(param_expand::get_element(shell, name, subscript), Ok(Some(_))),
+			None => shell.vars.get(operand).is_some(),
+		},
+		"-t" => operand.parse().is_ok_and(|fd| {
+			// only asked whether it is a terminal, never read or closed
+			let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+			fd.is_terminal()
+		}),
+		_ => test_file(op, operand),
+	}
+}
+
+// Compare two integers with one of the `-eq` family of operators.
+pub fn compare_integers(op: &str, left: i64, right: i64) -> bool {
+	match op {
+		"-eq" => left == right,
+		"-ne" => left != right,
+		"-lt" => left < right,
+		"-le" => left <= right,
+		"-gt" => left > right,
+		_ => left >= right,
+	}
+}
+
+// Compare two files with `-nt`, `-ot` or `-ef`. A file that does not exist
+// is older than any that does.
+pub fn compare_files(op: &str, left: &str, right: &str) -> bool {
+	let (left, right) = (fs::metadata(left).ok(), fs::metadata(right).ok());
+	let modified = |metadata: &Option<Metadata>| metadata.as_ref().and_then(|metadata| metadata.modified().ok());
+	match (op, &left, &right) {
+		("-nt", _, _) => modified(&left) > modified(&right),
+		("-ot", _, _) => modified(&left) < modified(&right),
+		(_, Some(left), Some(right)) => left.dev() == right.dev() && left.ino() == right.ino(),
+		_ => false,
+	}
+}
+
+fn test_file(op: &str, path: &str) -> bool {
+	let metadata = match op {
+		"-h" | "-L" => fs::symlink_metadata(path),
+		_ => fs::metadata(path),
+	};
+	let Ok(metadata) = metadata else {
+		return false;
+	};
+	let file_type = metadata.file_type();
+	match op {
+		"-f" => file_type.is_file(),
+		"-d" => file_type.is_dir(),
+		"-h" | "-L" => file_type.is_symlink(),
+		"-b" => file_type.is_block_device(),
+		"-c" => file_type.is_char_device(),
+		"-p" => file_type.is_fifo(),
+		"-S" => file_type.is_socket(),
+		"-s" => metadata.len() > 0,
+		"-u" => metadata.mode() & 0o4000 != 0,
+		"-g" => metadata.mode() & 0o2000 != 0,
+		"-k" => metadata.mode() & 0o1000 != 0,
+		"-r" => access(path, AccessFlags::R_OK).is_ok(),
+		"-w" => access(path, AccessFlags::W_OK).is_ok(),
+		"-x" => access(path, AccessFlags::X_OK).is_ok(),
+		"-O" => metadata.uid() == geteuid().as_raw(),
+		"-G" => metadata.gid() == getegid().as_raw(),
+		// modified since it was last read
+		"-N" => metadata.mtime() > metadata.atime(),
+		// `-a` and `-e`
+		_ => true,
+	}
+}
+
+// Split the expression into its words as written, with `&&`, `||` and
+// parentheses as words of their own unless they are quoted. The regular
+// expression after `=~` keeps its parentheses and `|`.
+fn split(expr: &str) -> Vec<String> {
+	let chars: Vec<char> = expr.chars().collect();
+	let mut quotes = QuoteTracker::new();
+	let mut words: Vec<String> = Vec::new();
+	let mut word = String::new();
+	// parentheses open in the regular expression being read
+	let mut depth = 0;
+	let mut i = 0;
+	while i < chars.len() {
+		let ch = chars[i];
+		let unquoted = quotes.is_unquoted(ch);
+		let next = chars.get(i + 1).copied();
+		let in_regex = words.last().is_some_and(|last| last == "=~");
+		let op_len = match ch {
+			// a regular expression runs on to a blank outside its parentheses
+			'(' if unquoted && in_regex => {
+				depth += 1;
+				None
+			}
+			')' if unquoted && in_regex && depth > 0 => {
+				depth -= 1;
+				None
+			}
+			_ if in_regex && depth > 0 => None,
+			'&' | '|' if unquoted && next == Some(ch) => Some(2),
+			'(' | ')' if unquoted => Some(1),
+			_ if unquoted && ch.is_whitespace() => Some(0),
+			_ => None,
+		};
+		let Some(op_len) = op_len else {
+			word.push(ch);
+			i += 1;
+			continue;
+		};
+		if !word.is_empty() {
+			words.push(std::mem::take(&mut word));
+		}
+		if op_len > 0 {
+			words.push(chars[i..i + op_len].iter().collect());
+		}
+		if op_len == 2 {
+			quotes.is_unquoted(chars[i + 1]);
+		}
+		i += op_len.max(1);
+	}
+	if !word.is_empty() {
+		words.push(word);
+	}
+	words
+}
+
+fn syntax_error(word: &str) -> String {
+	format!("syntax error in conditional expression: unexpected token `{}'", word)
+}
+
+// Evaluates the expression as it parses it. With `skip` a part is only parsed,
+// so nothing in it is expanded.
+struct Parser<'a> {
+	words: Vec<String>,
+	pos: usize,
+	shell: &'a mut Shell,
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<&str> {
+		self.words.get(self.pos).map(String::as_str)
+	}
+
+	fn next(&mut self) -> Result<String, String> {
+		let word = self.words.get(self.pos).cloned();
+		self.pos += 1;
+		word.ok_or_else(|| "syntax error in conditional expression".to_string())
+	}
+
+	fn or(&mut self, skip: bool) -> Result<bool, String> {
+		let mut result = self.and(skip)?;
+		while self.peek() == Some("||") {
+			self.pos += 1;
+			result |= self.and(skip || result)?;
+		}
+		Ok(result)
+	}
+
+	fn and(&mut self, skip: bool) -> Result<bool, String> {
+		let mut result = self.not(skip)?;
+		while self.peek() == Some("&&") {
+			self.pos += 1;
+			result &= self.not(skip || !result)?;
+		}
+		Ok(result)
+	}
+
+	fn not(&mut self, skip: bool) -> Result<bool, String> {
+		if self.peek() == Some("!") {
+			self.pos += 1;
+			return Ok(!self.not(skip)?);
+		}
+		self.primary(skip)
+	}
+
+	fn primary(&mut self, skip: bool) -> Result<bool, String> {
+		let word = self.next()?;
+		if word == "(" {
+			let result = self.or(skip)?;
+			return match self.next()?.as_str() {
+				")" => Ok(result),
+				other => Err(syntax_error(other)),
+			};
+		}
+		if is_operator(&word) {
+			return Err(syntax_error(&word));
+		}
+		if is_unary(&word) && self.peek().is_some_and(|next| !is_operator(next)) {
+			let operand = self.next()?;
+			if skip {
+				return Ok(false);
+			}
+			let operand = utils::expand_word(&operand, self.shell)?;
+			return Ok(test_unary(self.shell, &word, &operand));
+		}
+		match self.peek() {
+			Some(op) if is_binary(op) || op == "=~" => {
+				let op = self.next()?;
+				let right = self.next()?;
+				if skip {
+					return Ok(false);
+				}
+				self.binary(&word, &op, &right)
+			}
+			// a word on its own tests whether it is empty
+			_ if skip => Ok(false),
+			_ => Ok(!utils::expand_word(&word, self.shell)?.is_empty()),
+		}
+	}
+
+	// Expand both sides of a binary operator as it needs them and compare.
+	fn binary(&mut self, left: &str, op: &str, right: &str) -> Result<bool, String> {
+		let left = utils::expand_word(left, self.shell)?;
+		match op {
+			"=~" => {
+				let regex = utils::expand_regex(right, self.shell)?;
+				self.regex_match(&left, &regex)
+			}
+			"==" | "=" | "!=" => {
+				let pattern = utils::expand_pattern(right, self.shell)?;
+				Ok(glob::is_match(&pattern, &left) != (op == "!="))
+			}
+			"<" | ">" => {
+				let right = utils::expand_word(right, self.shell)?;
+				Ok(if op == "<" { left < right } else { left > right })
+			}
+			"-nt" | "-ot" | "-ef" => {
+				let right = utils::expand_word(right, self.shell)?;
+				Ok(compare_files(op, &left, &right))
+			}
+			_ => {
+				// both sides of an integer comparison are arithmetic
+				let right = utils::expand_word(right, self.shell)?;
+				let left = arith::evaluate(&left, &mut self.shell.vars)?;
+				let right = arith::evaluate(&right, &mut self.shell.vars)?;
+				Ok(compare_integers(op, left, right))
+			}
+		}
+	}
+
+	// Match a string against a regular expression, leaving the match and
+	// what each group of it matched in the `BASH_REMATCH` array.
+	fn regex_match(&mut self, text: &str, regex: &str) -> Result<bool, String> {
+		let regex = Regex::new(regex).map_err(|_| format!("{}: invalid regular expression", regex))?;
+		let groups: Vec<String> = match regex.captures(text) {
+			Some(captures) => {
+				captures.iter().map(|group| group.map_or("", |group| group.as_str()).to_string()).collect()
+			}
+			None => Vec::new(),
+		};
+		let matched = !groups.is_empty();
+		self.shell.vars.set_array("BASH_REMATCH", &groups)?;
+		Ok(matched)
+	}
+}
+
+// Whether a word is one of the words that join or group expressions.
+fn is_operator(word: &str) -> bool {
+	matches!(word, "&&" | "||" | "(" | ")")
+}
@@ -0,0 +1,266 @@
+use crate::variables::{self, Case, ShellValue};
+use crate::Shell;
+
+// The attributes `declare` can give a variable.
+#[derive(Default)]
+struct Attributes {
+	array: bool,
+	assoc: bool,
+	integer: bool,
+	readonly: bool,
+	export: bool,
+	case: Option<Case>,
+}
+
+impl Attributes {
+	fn from_flags(flags: &str) -> Self {
+		let mut attributes = Attributes::default();
+		for flag in flags.chars() {
+			match flag {
+				'a' => attributes.array = true,
+				'A' => attributes.assoc = true,
+				'i' => attributes.integer = true,
+				'r' => attributes.readonly = true,
+				'x' => attributes.export = true,
+				'l' => attributes.case = Some(Case::Lower),
+				'u' => attributes.case = Some(Case::Upper),
+				_ => (),
+			}
+		}
+		attributes
+	}
+}
+
+// `declare [-aAgilprux] [name[=value]...]`, also run as `typeset`: give
+// variables values and attributes. `-a` makes each name an indexed array and
+// `-A` an associative one, `-i` evaluates what it is assigned as arithmetic,
+// `-l` and `-u` convert it to lower or upper case, `-r` makes it readonly and
+// `-x` exports it. In a function the names are local unless `-g` is given.
+// `-p`, or no names at all, prints the variables as declarations that read
+// back in.
+pub fn declare(shell: &mut Shell, args: &[String]) -> i32 {
+	let usage = "declare [-aAgilprux] [name[=value] ...]";
+	let Some((flags, names)) = parse_flags("declare", usage, "aAgilprux", args) else {
+		return 2;
+	};
+	if names.is_empty() {
+		// only the variables with every attribute asked for
+		list(shell, &flags.replace(['g', 'p'], ""));
+		return 0;
+	}
+	if flags.contains('p') {
+		return print(shell, names);
+	}
+	assign_all(shell, "declare", names, &Attributes::from_flags(&flags), flags.contains('g'))
+}
+
+// `readonly [-aAp] [name[=value]...]`: give variables any value and make
+// them readonly, so they can be neither assigned nor unset again. The names
+// stay global in a function. `-p`, or no names at all, prints every readonly
+// variable.
+pub fn readonly(shell: &mut Shell, args: &[String]) -> i32 {
+	let usage = "readonly [-aAp] [name[=value] ...]";
+	let Some((flags, names)) = parse_flags("readonly", usage, "aAp", args) else {
+		return 2;
+	};
+	if names.is_empty() || flags.contains('p') {
+		list(shell, "r");
+		return 0;
+	}
+	let attributes = Attributes {
+		readonly: true,
+		..Attributes::from_flags(&flags)
+	};
+	assign_all(shell, "readonly", names, &attributes, true)
+}
+
+// `local [-aAilrux] [name[=value]...]`: make variables local to the function
+// running, taking the same flags as `declare`.
+pub fn local(shell: &mut Shell, args: &[String]) -> i32 {
+	let usage = "local [-aAilrux] [name[=value] ...]";
+	let Some((flags, names)) = parse_flags("local", usage, "aAilrux", args) else {
+		return 2;
+	};
+	if !shell.vars.in_function() {
+		shell_error!("local: can only be used in a function");
+		return 1;
+	}
+	assign_all(shell, "local", names, &Attributes::from_flags(&flags), false)
+}
+
+// Split the leading `-flags` words off the arguments of a builtin, returning
+// every flag given and the names after them. Prints an error and returns
+// None for a flag the builtin does not take.
+fn parse_flags<'a>(cmd: &str, usage: &str, allowed: &str, args: &'a [String]) -> Option<(String, &'a [String])> {
+	let mut flags = String::new();
+	let mut names = args;
+	while let Some(arg) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		names = &names[1..];
+		if arg == "--" {
+			break;
+		}
+		for flag in arg.chars().skip(1) {
+			if !allowed.contains(flag) {
+				shell_error!("{}: -{}: invalid option", cmd, flag);
+				eprintln!("{}: usage: {}", cmd, usage);
+				return None;
+			}
+			flags.push(flag);
+		}
+	}
+	Some((flags, names))
+}
+
+// Print the declaration of every variable that has all the attributes in
+// `attributes`.
+fn list(shell: &Shell, attributes: &str) {
+	for name in shell.vars.names() {
+		let has = shell.vars.attributes(&name);
+		if variables::is_valid_name(&name) && attributes.chars().all(|attribute| has.contains(attribute)) {
+			println!("{}", declaration(shell, &name).unwrap_or_default());
+		}
+	}
+}
+
+fn print(shell: &Shell, names: &[String]) -> i32 {
+	let mut status = 0;
+	for name in names {
+		match declaration(shell, name) {
+			Some(declaration) => println!("{}", declaration),
+			None => {
+				shell_error!("declare: {}: not found", name);
+				status = 1;
+			}
+		}
+	}
+	status
+}
+
+// Give each of the `name[=value]` words in `names` the attributes and its
+// value.
+fn assign_all(shell: &mut Shell, cmd: &str, names: &[String], attributes: &Attributes, global: bool) -> i32 {
+	let mut status = 0;
+	for arg in names {
+		let (name, value) = match arg.split_once('=') {
+			Some((name, value)) => (name, Some(value)),
+			None => (arg.as_str(), None),
+		};
+		let (name, append) = match name.strip_suffix('+') {
+			Some(name) if value.is_some() => (name, true),
+			_ => (name, false),
+		};
+		if !variables::is_assignable(name) {
+			shell_error!("{}: `{}': not a valid identifier", cmd, arg);
+			status = 1;
+			continue;
+		}
+		if let Err(err) = declare_one(shell, name, value, append, attributes, global) {
+			shell_error!("{}: {}", cmd, err);
+			status = 1;
+		}
+	}
+	status
+}
+
+// Give one variable the attributes, then any value, with `append` adding the
+// value on to what is there as `name+=value` does.
+fn declare_one(
+	shell: &mut Shell,
+	name: &str,
+	value: Option<&str>,
+	append: bool,
+	attributes: &Attributes,
+	global: bool,
+) -> Result<(), String> {
+	let base = name.split_once('[').map_or(name, |(base, _)| base);
+	if shell.vars.in_function() && !global {
+		shell.vars.make_local(base)?;
+	}
+	if attributes.assoc {
+		shell.vars.declare_assoc(base)?;
+	} else if attributes.array && !matches!(shell.vars.value(base), Some(ShellValue::IndexedArray(_))) {
+		let elements: Vec<String> = shell.vars.get(base).into_iter().collect();
+		shell.vars.set_array(base, &elements)?;
+	}
+	if attributes.integer {
+		shell.vars.set_integer(base);
+	}
+	if let Some(case) = attributes.case {
+		shell.vars.set_case(base, case);
+	}
+
+	if let Some(value) = value {
+		let operator = if append { "+" } else { "" };
+		match value.strip_prefix('(').and_then(|value| value.strip_suffix(')')) {
+			Some(words) if base == name => crate::assign_array(shell, name, words, append)?,
+			// the value is already expanded, so it is quoted to stay as it is
+			_ => crate::assign(shell, &format!("{}{}", name, operator), &quote(value))?,
+		}
+	}
+	if attributes.export {
+		shell.vars.export(base);
+	}
+	if attributes.readonly {
+		shell.vars.set_readonly(base);
+	}
+	Ok(())
+}
+
+// A variable as a `declare` command that gives it back its value and
+// attributes, or None when it has neither.
+fn declaration(shell: &Shell, name: &str) -> Option<String> {
+	let attributes = match shell.vars.attributes(name) {
+		attributes if attributes.is_empty() => "--".to_string(),
+		attributes => format!("-{}", attributes),
+	};
+	let value = match shell.vars.value(name) {
+		Some(value) => format_value(value),
+		None if attributes != "--" => return Some(format!("declare {} {}", attributes, name)),
+		None => return None,
+	};
+	Some(format!("declare {} {}={}", attributes, name, value))
+}
+
+// A value the way it is written after `name=` to give it back: quoted, and
+// an array as `([index]="element" ...)`.
+pub fn format_value(value: ShellValue) -> String {
+	match value {
+		ShellValue::Scalar(value) => quote(&value),
+		ShellValue::IndexedArray(elements) => {
+			let elements: Vec<String> = elements
+				.iter()
+				.enumerate()
+				.filter_map(|(index, element)| Some(format!("[{}]={}", index, quote(element.as_ref()?))))
+				.collect();
+			format!("({})", elements.join(" "))
+		}
+		ShellValue::AssocArray(elements) => {
+			let mut elements: Vec<(String, String)> = elements.into_iter().collect();
+			elements.sort();
+			let elements: Vec<String> =
+				elements.iter().map(|(key, value)| format!("[{}]={}", quote_key(key), quote(value))).collect();
+			format!("({})", elements.join(" "))
+		}
+	}
+}
+
+// Double-quote a value so it reads back as it is.
+fn quote(value: &str) -> String {
+	let mut quoted = String::from("\"");
+	for ch in value.chars() {
+		if "\"\\$`".contains(ch) {
+			quoted.push('\\');
+		}
+		quoted.push(ch);
+	}
+	quoted.push('"');
+	quoted
+}
+
+// A key of an associative array, quoted only when it has to be.
+fn quote_key(key: &str) -> String {
+	if !key.is_empty() && key.chars().all(|ch| ch.is_ascii_alphanumeric() || "_.-".contains(ch)) {
+		return key.to_string();
+	}
+	quote(key)
+}
@@ -0,0 +1,200 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::cd_cmd;
+use crate::dirstack::DirStack;
+use crate::variables::Variables;
+
+// `pushd dir` saves the current directory and changes to `dir`, `pushd +N`
+// brings entry N of the stack to the top and a bare `pushd` swaps the top
+// two. The stack is shown afterwards.
+pub fn pushd(dirs: &mut DirStack, vars: &mut Variables, args: &[String]) -> i32 {
+	let cwd = current_dir();
+	let saved = dirs.clone();
+	let status = match args.first() {
+		None => match dirs.pop() {
+			Some(top) => {
+				dirs.push(cwd);
+				change_to(dirs, vars, &top, saved)
+			}
+			None => {
+				shell_error!("pushd: no other directory");
+				return 1;
+			}
+		},
+		Some(arg) => match index(arg, dirs.len() + 1) {
+			Some(Ok(n)) => match dirs.rotate(cwd, n) {
+				Some(top) => change_to(dirs, vars, &top, saved),
+				None => out_of_range("pushd", arg),
+			},
+			Some(Err(err)) => {
+				shell_error!("pushd: {}", err);
+				return 2;
+			}
+			None => {
+				let status = cd_cmd::cd(vars, std::slice::from_ref(arg));
+				if status == 0 {
+					dirs.push(cwd);
+				}
+				status
+			}
+		},
+	};
+	if status == 0 {
+		print_stack(dirs, vars, false);
+	}
+	status
+}
+
+// `popd` drops the top of the stack and changes to the directory under it,
+// while `popd +N` takes out entry N and stays. The stack is shown afterwards.
+pub fn popd(dirs: &mut DirStack, vars: &mut Variables, args: &[String]) -> i32 {
+	let n = match args.first() {
+		None => 0,
+		Some(arg) => match index(arg, dirs.len() + 1) {
+			Some(Ok(n)) => n,
+			Some(Err(err)) => {
+				shell_error!("popd: {}", err);
+				return 2;
+			}
+			None => {
+				shell_error!("popd: {}: invalid argument", arg);
+				return 2;
+			}
+		},
+	};
+	if dirs.len() == 0 {
+		shell_error!("popd: directory stack empty");
+		return 1;
+	}
+	let saved = dirs.clone();
+	let status = match n {
+		0 => {
+			let top = dirs.pop().unwrap_or_default();
+			change_to(dirs, vars, &top, saved)
+		}
+		_ => {
+			dirs.remove(n);
+			0
+		}
+	};
+	if status == 0 {
+		print_stack(dirs, vars, false);
+	}
+	status
+}
+
+// Show the stack on one line, or with `-v` numbered one entry per line and
+// with `-p` one per line. `-l` keeps `$HOME` instead of writing it as `~`,
+// `-c` only clears the stack and `+N` or `-N` shows just that entry.
+pub fn dirs(dirs: &mut DirStack, vars: &Variables, args: &[String]) -> i32 {
+	let (mut clear, mut long, mut verbose, mut lines) = (false, false, false, false);
+	let mut entry = None;
+	for arg in args {
+		if let Some(found) = index(arg, dirs.len() + 1) {
+			match found {
+				Ok(n) => entry = Some((n, arg)),
+				Err(err) => {
+					shell_error!("dirs: {}", err);
+					return 2;
+				}
+			}
+			continue;
+		}
+		if !arg.starts_with('-') || arg == "-" {
+			shell_error!("dirs: {}: invalid argument", arg);
+			eprintln!("dirs: usage: dirs [-clpv] [+N] [-N]");
+			return 1;
+		}
+		for flag in arg.chars().skip(1) {
+			match flag {
+				'c' => clear = true,
+				'l' => long = true,
+				'v' => verbose = true,
+				'p' => lines = true,
+				_ => {
+					shell_error!("dirs: -{}: invalid option", flag);
+					eprintln!("dirs: usage: dirs [-clpv] [+N] [-N]");
+					return 2;
+				}
+			}
+		}
+	}
+
+	if clear {
+		dirs.clear();
+		return 0;
+	}
+	if let Some((n, arg)) = entry {
+		return match dirs.with(current_dir()).get(n) {
+			Some(dir) => {
+				println!("{}", display(dir, vars, long));
+				0
+			}
+			None => out_of_range("dirs", arg),
+		};
+	}
+	if verbose {
+		for (n, dir) in dirs.with(current_dir()).iter().enumerate() {
+			println!("{:2}  {}", n, display(dir, vars, long));
+		}
+	} else if lines {
+		for dir in dirs.with(current_dir()) {
+			println!("{}", display(&dir, vars, long));
+		}
+	} else {
+		print_stack(dirs, vars, long);
+	}
+	0
+}
+
+// Change to `dir`, putting the stack back to `saved` when that fails.
+fn change_to(dirs: &mut DirStack, vars: &mut Variables, dir: &Path, saved: DirStack) -> i32 {
+	let status = cd_cmd::cd(vars, &[dir.to_string_lossy().to_string()]);
+	if status != 0 {
+		*dirs = saved;
+	}
+	status
+}
+
+// The stack entry a `+N` or `-N` argument names, counting from the top or the
+// bottom of a stack of `len` entries. None when the argument is not one.
+fn index(arg: &str, len: usize) -> Option<Result<usize, String>> {
+	let (sign, digits) = arg.split_at_checked(1)?;
+	if !matches!(sign, "+" | "-") || digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+		return None;
+	}
+	let Ok(n) = digits.parse::<usize>() else {
+		return Some(Err(format!("{}: directory stack index out of range", arg)));
+	};
+	Some(match sign {
+		"+" if n < len => Ok(n),
+		"-" if n < len => Ok(len - 1 - n),
+		_ => Err(format!("{}: directory stack index out of range", arg)),
+	})
+}
+
+fn out_of_range(cmd: &str, arg: &str) -> i32 {
+	shell_error!("{}: {}: directory stack index out of range", cmd, arg);
+	1
+}
+
+fn print_stack(dirs: &DirStack, vars: &Variables, long: bool) {
+	let stack: Vec<String> = dirs.with(current_dir()).iter().map(|dir| display(dir, vars, long)).collect();
+	println!("{}", stack.join(" "));
+}
+
+// A directory with `$HOME` at its start written as `~`, unless `long`.
+fn display(dir: &Path, vars: &Variables, long: bool) -> String {
+	let path = dir.to_string_lossy().to_string();
+	match vars.get("HOME") {
+		Some(home) if !long && !home.is_empty() && (path == home || path.starts_with(&format!("{}/", home))) => {
+			format!("~{}", &path[home.len()..])
+		}
+		_ => path,
+	}
+}
+
+fn current_dir() -> PathBuf {
+	env::current_dir().unwrap_or_default()
+}
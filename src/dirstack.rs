@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+// The directories `pushd` saved, most recent first. With the current
+// directory in front of them they make the stack `dirs` shows, so entry 0 of
+// the stack is the current directory and entry N is the Nth one saved.
+#[derive(Clone)]
+pub struct DirStack {
+	dirs: Vec<PathBuf>,
+}
+
+impl DirStack {
+	pub fn new() -> Self {
+		DirStack { dirs: Vec::new() }
+	}
+
+	pub fn push(&mut self, dir: PathBuf) {
+		self.dirs.insert(0, dir);
+	}
+
+	pub fn pop(&mut self) -> Option<PathBuf> {
+		(!self.dirs.is_empty()).then(|| self.dirs.remove(0))
+	}
+
+	// Turn the stack headed by `cwd` until entry `n` is on top, returning it
+	// as the directory to change to.
+	pub fn rotate(&mut self, cwd: PathBuf, n: usize) -> Option<PathBuf> {
+		let mut stack = self.with(cwd);
+		if n >= stack.len() {
+			return None;
+		}
+		stack.rotate_left(n);
+		let top = stack.remove(0);
+		self.dirs = stack;
+		Some(top)
+	}
+
+	// Take entry `n` of the stack out. Entry 0 is the current directory, which
+	// `pop` leaves.
+	pub fn remove(&mut self, n: usize) -> Option<PathBuf> {
+		(n >= 1 && n <= self.dirs.len()).then(|| self.dirs.remove(n - 1))
+	}
+
+	pub fn clear(&mut self) {
+		self.dirs.clear();
+	}
+
+	pub fn len(&self) -> usize {
+		self.dirs.len()
+	}
+
+	// The whole stack with `cwd` on top.
+	pub fn with(&self, cwd: PathBuf) -> Vec<PathBuf> {
+		std::iter::once(cwd).chain(self.dirs.iter().cloned()).collect()
+	}
+}
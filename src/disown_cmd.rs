@@ -0,0 +1,70 @@
+use nix::unistd::Pid;
+
+use crate::jobs::{Jobs, State};
+
+pub fn disown(jobs: &mut Jobs, args: &[String]) -> i32 {
+	let mut keep_in_table = false;
+	let mut all = false;
+	let mut running_only = false;
+	let mut specs = args;
+	while let Some(flags) = specs.first().and_then(|arg| arg.strip_prefix('-')) {
+		for flag in flags.chars() {
+			match flag {
+				'h' => keep_in_table = true,
+				'a' => all = true,
+				'r' => running_only = true,
+				_ => {
+					shell_error!("disown: -{}: invalid option", flag);
+					eprintln!("disown: usage: disown [-h] [-ar] [jobspec ... | pid ...]");
+					return 2;
+				}
+			}
+		}
+		specs = &specs[1..];
+	}
+
+	jobs.reap();
+	let mut status = 0;
+	let ids: Vec<usize> = if all || (running_only && specs.is_empty()) {
+		jobs.ids()
+	} else if specs.is_empty() {
+		match jobs.find(None) {
+			Ok(id) => vec![id],
+			Err(err) => {
+				shell_error!("disown: {}", err);
+				return 1;
+			}
+		}
+	} else {
+		let mut ids = Vec::new();
+		for spec in specs {
+			let found = match spec.parse::<i32>() {
+				Ok(pid) => jobs.find_pid(Pid::from_raw(pid)).ok_or(format!("{}: no such job", spec)),
+				Err(_) => jobs.find(Some(spec)),
+			};
+			match found {
+				Ok(id) => ids.push(id),
+				Err(err) => {
+					shell_error!("disown: {}", err);
+					status = 1;
+				}
+			}
+		}
+		ids
+	};
+
+	for id in ids {
+		let Some(job) = jobs.get_mut(id) else {
+			continue;
+		};
+		if running_only && job.state != State::Running {
+			continue;
+		}
+		if keep_in_table {
+			job.keep_on_exit = true;
+		} else {
+			jobs.remove(id);
+		}
+	}
+	status
+}
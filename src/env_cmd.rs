@@ -0,0 +1,49 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+
+use crate::executable_cmd;
+use crate::jobs::Jobs;
+use crate::type_cmd;
+
+// `env [-i] [NAME=value]... [command [args]...]`. The variables assigned in
+// front of `env` itself, as in `NAME=value env`, come in `prefix`.
+pub fn env(jobs: &mut Jobs, args: &[String], prefix: &HashMap<String, String>) -> i32 {
+	let mut inherit_env = true;
+	let mut rest = args;
+	while let Some(flag) = rest.first() {
+		match flag.as_str() {
+			"-i" | "-" => inherit_env = false,
+			_ => break,
+		}
+		rest = &rest[1..];
+	}
+
+	let mut extra: HashMap<String, String> = HashMap::new();
+	if inherit_env {
+		extra.extend(prefix.clone());
+	}
+	while let Some((name, value)) = rest.first().and_then(|arg| arg.split_once('=')) {
+		extra.insert(name.to_string(), value.to_string());
+		rest = &rest[1..];
+	}
+
+	let Some(cmd) = rest.first() else {
+		let mut vars: BTreeMap<String, String> = BTreeMap::new();
+		if inherit_env {
+			vars.extend(env::vars());
+		}
+		vars.extend(extra);
+		for (name, value) in vars {
+			println!("{}={}", name, value);
+		}
+		return 0;
+	};
+
+	match type_cmd::search_path(cmd) {
+		Some(path) => executable_cmd::run_executable(jobs, &path, cmd, &rest[1..], &extra, inherit_env),
+		None => {
+			shell_error!("env: '{}': No such file or directory", cmd);
+			127
+		}
+	}
+}
\ No newline at end of file
@@ -1,7 +1,48 @@
+use std::collections::HashMap;
+use std::os::unix::process::CommandExt;
 use std::process::Command;
 
-pub fn run_executable(cmd: &str, args: &[String]) {
+use nix::unistd::{setpgid, Pid};
+
+use crate::jobs::{self, Jobs};
+use crate::signals;
+
+// Run the external program at `path`, which gets `cmd` as its name, with
+// `env` added to its environment. Without `inherit_env` the program only gets
+// `env`. Under job control it runs as a job of its own in the foreground.
+pub fn run_executable(
+	jobs: &mut Jobs,
+	path: &str,
+	cmd: &str,
+	args: &[String],
+	env: &HashMap<String, String>,
+	inherit_env: bool,
+) -> i32 {
 	// let mut parts = arg.split_whitespace();
 	// let program = parts.next().unwrap();
-	Command::new(cmd).args(args).status().ok();
+	let mut command = Command::new(path);
+	command.arg0(cmd);
+	if !inherit_env {
+		command.env_clear();
+	}
+	let job_control = jobs::job_control();
+	unsafe {
+		command.pre_exec(move || {
+			if job_control {
+				jobs::start_job(None, true);
+			}
+			signals::reset();
+			Ok(())
+		});
+	}
+	let child = match command.args(args).envs(env).spawn() {
+		Ok(child) => child,
+		Err(_) => return 126,
+	};
+	let pid = Pid::from_raw(child.id() as i32);
+	if job_control {
+		setpgid(pid, pid).ok();
+	}
+	let words: Vec<&str> = std::iter::once(cmd).chain(args.iter().map(String::as_str)).collect();
+	jobs.wait_foreground(vec![pid], &words.join(" "))[0]
 }
\ No newline at end of file
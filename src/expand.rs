@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+
+use nix::sys::wait::WaitStatus;
+use nix::unistd::{dup2, fork, pipe, ForkResult};
+
+use crate::signals::{self, waitpid};
+use crate::Shell;
+
+// Expand `$(command)`: its output, with the status of the command becoming
+// `$?` right away and the status of a command made of assignments alone.
+pub fn substitute(shell: &mut Shell, command: &str) -> String {
+	let (output, status) = command_substitution(shell, command);
+	shell.last_status = status;
+	shell.substitution_status = Some(status);
+	output
+}
+
+// Run `command` in a copy of the shell and return what it wrote to stdout,
+// without trailing newlines, along with its exit status.
+pub fn command_substitution(shell: &mut Shell, command: &str) -> (String, i32) {
+	let (read_end, write_end) = match pipe() {
+		Ok(fds) => fds,
+		Err(err) => {
+			shell_error!("pipe: {}", err.desc());
+			return (String::new(), 1);
+		}
+	};
+	io::stdout().flush().ok();
+
+	match unsafe { fork() } {
+		Ok(ForkResult::Child) => {
+			signals::reset();
+			// a failure inside the substitution does not end it early
+			shell.options.errexit = false;
+			dup2(write_end.as_raw_fd(), 1).ok();
+			drop(read_end);
+			drop(write_end);
+			let status = crate::run_line(shell, command);
+			io::stdout().flush().ok();
+			std::process::exit(status);
+		}
+		Ok(ForkResult::Parent { child }) => {
+			drop(write_end);
+			let mut output = Vec::new();
+			File::from(read_end).read_to_end(&mut output).ok();
+			let status = match waitpid(child, None) {
+				Ok(WaitStatus::Exited(_, code)) => code,
+				Ok(WaitStatus::Signaled(_, signal, _)) => 128 + signal as i32,
+				_ => 1,
+			};
+
+			let mut output = String::from_utf8_lossy(&output).into_owned();
+			output.truncate(output.trim_end_matches('\n').len());
+			(output, status)
+		}
+		Err(err) => {
+			shell_error!("fork: {}", err.desc());
+			(String::new(), 1)
+		}
+	}
+}
+
+// Run the command between a pair of backticks. Inside them a backslash only
+// escapes `$`, `` ` `` and another backslash.
+pub fn backtick_substitution(shell: &mut Shell, raw: &str) -> String {
+	let mut command = String::new();
+	let mut chars = raw.chars().peekable();
+	while let Some(ch) = chars.next() {
+		match (ch, chars.peek()) {
+			('\\', Some('$' | '`' | '\\')) => command.extend(chars.next()),
+			_ => command.push(ch),
+		}
+	}
+	substitute(shell, &command)
+}
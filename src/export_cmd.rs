@@ -0,0 +1,36 @@
+use crate::variables::{self, Variables};
+
+pub fn export(vars: &mut Variables, args: &[String]) -> i32 {
+	if args.is_empty() || args[0] == "-p" {
+		for (name, value) in vars.exported() {
+			match value {
+				Some(value) => println!("declare -x {}='{}'", name, value.replace('\'', "'\\''")),
+				None => println!("declare -x {}", name),
+			}
+		}
+		return 0;
+	}
+
+	let mut status = 0;
+	for arg in args {
+		let (name, value) = match arg.split_once('=') {
+			Some((name, value)) => (name, Some(value)),
+			None => (arg.as_str(), None),
+		};
+		if !variables::is_valid_name(name) {
+			shell_error!("export: `{}': not a valid identifier", arg);
+			status = 1;
+			continue;
+		}
+		if let Some(value) = value {
+			if let Err(err) = vars.set(name, value) {
+				shell_error!("export: {}", err);
+				status = 1;
+				continue;
+			}
+		}
+		vars.export(name);
+	}
+
+	status
+}
\ No newline at end of file
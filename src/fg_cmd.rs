@@ -0,0 +1,41 @@
+use std::io::{self, Write};
+
+use nix::sys::signal::{killpg, Signal};
+
+use crate::jobs::{self, Jobs, State};
+
+pub fn fg(jobs: &mut Jobs, args: &[String]) -> i32 {
+	jobs.reap();
+	let id = match jobs.find(args.first().map(String::as_str)) {
+		Ok(id) => id,
+		Err(err) => {
+			shell_error!("fg: {}", err);
+			return 1;
+		}
+	};
+	let Some(job) = jobs.get_mut(id) else {
+		return 1;
+	};
+	if job.is_finished() {
+		shell_error!("fg: job has terminated");
+		jobs.remove(id);
+		return 1;
+	}
+
+	println!("{}", job.command);
+	io::stdout().flush().ok();
+	jobs::set_foreground(job.pgid);
+	if job.state == State::Stopped {
+		killpg(job.pgid, Signal::SIGCONT).ok();
+	}
+	job.state = State::Running;
+	let status = jobs.wait(id, true);
+	jobs::reclaim_terminal();
+
+	// it stopped again rather than finishing
+	if let Some(job) = jobs.get_mut(id) {
+		println!();
+		println!("[{}]+  {:<24}{}", job.id, job.describe(), job.command);
+	}
+	status
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::compound::{self, CommandList, ControlFlow};
+use crate::Shell;
+
+// Shell functions by name. A body is shared with the command that defined it,
+// so it stays alive while it runs even if the function is redefined.
+pub struct Functions {
+	bodies: HashMap<String, Rc<CommandList>>,
+}
+
+impl Functions {
+	pub fn new() -> Self {
+		Functions {
+			bodies: HashMap::new(),
+		}
+	}
+
+	pub fn define(&mut self, name: &str, body: Rc<CommandList>) {
+		self.bodies.insert(name.to_string(), body);
+	}
+
+	pub fn get(&self, name: &str) -> Option<Rc<CommandList>> {
+		self.bodies.get(name).cloned()
+	}
+
+	pub fn remove(&mut self, name: &str) -> bool {
+		self.bodies.remove(name).is_some()
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.bodies.keys().map(String::as_str)
+	}
+}
+
+// Run a function body in its own variable scope with the call arguments as
+// its positional parameters, putting the caller's back afterwards.
+pub fn call(shell: &mut Shell, body: &CommandList, args: &[String]) -> i32 {
+	let saved = shell.vars.replace_positional(args.to_vec());
+	shell.vars.push_scope();
+	// the loops of the caller cannot be left from inside the function
+	let loop_depth = std::mem::replace(&mut shell.loop_depth, 0);
+	let status = match compound::run_list(shell, body) {
+		Ok(status) | Err(ControlFlow::Return(status)) => status,
+		Err(ControlFlow::Break(_) | ControlFlow::Continue(_)) => 0,
+		// the caller sees the interrupt once the call is over
+		Err(ControlFlow::Interrupt) => 130,
+	};
+	shell.loop_depth = loop_depth;
+	shell.vars.pop_scope();
+	shell.vars.replace_positional(saved);
+	status
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+
+pub fn is_wildcard(ch: char) -> bool {
+	ch == '*' || ch == '?' || ch == '['
+}
+
+// Characters that have to be escaped with a backslash to match themselves.
+pub fn is_special(ch: char) -> bool {
+	is_wildcard(ch) || ch == '\\'
+}
+
+// Expand a pattern into the sorted paths it matches, relative to the current
+// directory unless it starts with `/`. A backslash in the pattern makes the
+// next character match only itself. Matches nothing when no path fits. Names
+// starting with `.` only match when the pattern spells out the dot, unless
+// `dotglob` is set.
+pub fn expand(pattern: &str, dotglob: bool) -> Vec<String> {
+	let mut paths = vec![if pattern.starts_with('/') { "/".to_string() } else { String::new() }];
+	for component in pattern.split('/').filter(|component| !component.is_empty()) {
+		let component: Vec<char> = component.chars().collect();
+		if !component.iter().any(|&ch| is_wildcard(ch)) {
+			let name = unescape(&component);
+			paths = paths.iter().map(|path| join(path, &name)).collect();
+			continue;
+		}
+
+		let mut matched: Vec<String> = Vec::new();
+		for path in &paths {
+			let dir = if path.is_empty() { "." } else { path.as_str() };
+			let Ok(entries) = fs::read_dir(dir) else {
+				continue;
+			};
+			for entry in entries.flatten() {
+				let name = entry.file_name().to_string_lossy().into_owned();
+				// hidden files only match a pattern that spells out the dot
+				if name.starts_with('.') && !dotglob && !starts_with_dot(&component) {
+					continue;
+				}
+				let name_chars: Vec<char> = name.chars().collect();
+				if matches(&component, &name_chars) {
+					matched.push(join(path, &name));
+				}
+			}
+		}
+		paths = matched;
+	}
+
+	// a literal component after a wildcard may name something that is missing
+	paths.retain(|path| !path.is_empty() && Path::new(path).symlink_metadata().is_ok());
+	if pattern.ends_with('/') {
+		paths.retain(|path| Path::new(path).is_dir());
+		paths.iter_mut().filter(|path| !path.ends_with('/')).for_each(|path| path.push('/'));
+	}
+	paths.sort();
+	paths
+}
+
+// Whether the whole of `text` matches the pattern. Unlike with paths, `*` and
+// `?` match a `/` too.
+pub fn is_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	matches(&pattern, &text)
+}
+
+fn join(dir: &str, name: &str) -> String {
+	if dir.is_empty() || dir.ends_with('/') {
+		format!("{}{}", dir, name)
+	} else {
+		format!("{}/{}", dir, name)
+	}
+}
+
+fn starts_with_dot(pattern: &[char]) -> bool {
+	matches!(pattern, ['.', ..] | ['\\', '.', ..])
+}
+
+fn unescape(pattern: &[char]) -> String {
+	let mut name = String::new();
+	let mut chars = pattern.iter();
+	while let Some(&ch) = chars.next() {
+		match ch {
+			'\\' => name.extend(chars.next()),
+			_ => name.push(ch),
+		}
+	}
+	name
+}
+
+// Match a single path component against a pattern component.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+	match pattern {
+		[] => name.is_empty(),
+		['*', rest @ ..] => {
+			// a run of stars matches the same as one
+			let rest = &rest[rest.iter().take_while(|&&ch| ch == '*').count()..];
+			(0..=name.len()).any(|skip| matches(rest, &name[skip..]))
+		}
+		['?', rest @ ..] => !name.is_empty() && matches(rest, &name[1..]),
+		['[', ..] if name.is_empty() => false,
+		['[', ..] => match match_bracket(pattern, name[0]) {
+			Some((matched, len)) => matched && matches(&pattern[len..], &name[1..]),
+			// an unclosed `[` is an ordinary character
+			None => name[0] == '[' && matches(&pattern[1..], &name[1..]),
+		},
+		['\\', ch, rest @ ..] => name.first() == Some(ch) && matches(rest, &name[1..]),
+		[ch, rest @ ..] => name.first() == Some(ch) && matches(rest, &name[1..]),
+	}
+}
+
+// Match `ch` against the bracket expression at the start of `pattern`, giving
+// whether it matched and the length of the expression. None when the `[` is
+// never closed.
+fn match_bracket(pattern: &[char], ch: char) -> Option<(bool, usize)> {
+	let mut i = 1;
+	let negated = matches!(pattern.get(i), Some('!' | '^'));
+	if negated {
+		i += 1;
+	}
+
+	let mut matched = false;
+	let mut first = true;
+	loop {
+		let mut start = *pattern.get(i)?;
+		if start == ']' && !first {
+			return Some((matched != negated, i + 1));
+		}
+		first = false;
+
+		if start == '[' && pattern.get(i + 1) == Some(&':') {
+			let rest = &pattern[i + 2..];
+			if let Some(len) = rest.windows(2).position(|pair| pair == [':', ']']) {
+				let class: String = rest[..len].iter().collect();
+				matched |= in_class(&class, ch);
+				i += len + 4;
+				continue;
+			}
+		}
+		if start == '\\' {
+			i += 1;
+			start = *pattern.get(i)?;
+		}
+		i += 1;
+
+		// `a-z` is a range unless the `-` comes last
+		if pattern.get(i) == Some(&'-') && pattern.get(i + 1).is_some_and(|&end| end != ']') {
+			let mut end = pattern[i + 1];
+			i += 2;
+			if end == '\\' {
+				end = *pattern.get(i)?;
+				i += 1;
+			}
+			matched |= start <= ch && ch <= end;
+		} else {
+			matched |= start == ch;
+		}
+	}
+}
+
+// Whether `ch` belongs to the POSIX character class of that name.
+fn in_class(class: &str, ch: char) -> bool {
+	match class {
+		"alpha" => ch.is_alphabetic(),
+		"digit" => ch.is_ascii_digit(),
+		"alnum" => ch.is_alphanumeric(),
+		"upper" => ch.is_uppercase(),
+		"lower" => ch.is_lowercase(),
+		"space" => ch.is_whitespace(),
+		"blank" => ch == ' ' || ch == '\t',
+		"punct" => ch.is_ascii_punctuation(),
+		"xdigit" => ch.is_ascii_hexdigit(),
+		"cntrl" => ch.is_control(),
+		"print" => !ch.is_control(),
+		"graph" => !ch.is_control() && !ch.is_whitespace(),
+		"word" => ch.is_alphanumeric() || ch == '_',
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use std::fs;
+	use std::process;
+
+	use super::*;
+
+	#[test]
+	fn question_mark_needs_exactly_one_character() {
+		assert!(is_match("file?.txt", "file1.txt"));
+		assert!(is_match("?ile1.tx?", "file1.txt"));
+		assert!(!is_match("file?.txt", "file.txt"));
+		assert!(!is_match("?", ""));
+	}
+
+	#[test]
+	fn question_mark_never_matches_a_slash_in_paths() {
+		let dir = format!("{}/shell-glob-{}", env::temp_dir().display(), process::id());
+		fs::create_dir_all(format!("{}/a/b", dir)).unwrap();
+		fs::write(format!("{}/axb", dir), "").unwrap();
+		fs::write(format!("{}/a1", dir), "").unwrap();
+		assert_eq!(expand(&format!("{}/a?b", dir), false), vec![format!("{}/axb", dir)]);
+		assert_eq!(expand(&format!("{}/a?", dir), false), vec![format!("{}/a1", dir)]);
+		fs::remove_dir_all(&dir).ok();
+	}
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+use crate::type_cmd;
+
+// Where the commands run so far were found in `PATH`, so running one again
+// does not search every directory. Everything is forgotten once `PATH`
+// changes.
+pub struct HashTable {
+	entries: HashMap<String, Entry>,
+	// `PATH` as it was when the entries were found
+	path: String,
+}
+
+struct Entry {
+	path: String,
+	// how many times the command was run from this path
+	hits: u32,
+}
+
+impl HashTable {
+	pub fn new() -> Self {
+		HashTable {
+			entries: HashMap::new(),
+			path: env::var("PATH").unwrap_or_default(),
+		}
+	}
+
+	// The path of the command `cmd`, counting it as run. A command seen
+	// before is only looked up again when its file is gone.
+	pub fn lookup(&mut self, cmd: &str) -> Option<String> {
+		self.check_path();
+		match self.entries.get_mut(cmd) {
+			Some(entry) if Path::new(&entry.path).exists() => {
+				entry.hits += 1;
+				Some(entry.path.clone())
+			}
+			_ => {
+				let path = self.rehash(cmd)?;
+				if let Some(entry) = self.entries.get_mut(cmd) {
+					entry.hits = 1;
+				}
+				Some(path)
+			}
+		}
+	}
+
+	// Search `PATH` for `cmd` again, remembering it as not run yet.
+	pub fn rehash(&mut self, cmd: &str) -> Option<String> {
+		self.check_path();
+		let Some(path) = type_cmd::search_path(cmd) else {
+			self.entries.remove(cmd);
+			return None;
+		};
+		let entry = Entry {
+			path: path.clone(),
+			hits: 0,
+		};
+		self.entries.insert(cmd.to_string(), entry);
+		Some(path)
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+	}
+
+	// Every entry as its hits and path, sorted by command name.
+	pub fn list(&self) -> Vec<(u32, &str)> {
+		let mut entries: Vec<(&String, &Entry)> = self.entries.iter().collect();
+		entries.sort_by_key(|(name, _)| name.as_str());
+		entries.into_iter().map(|(_, entry)| (entry.hits, entry.path.as_str())).collect()
+	}
+
+	fn check_path(&mut self) {
+		let path = env::var("PATH").unwrap_or_default();
+		if path != self.path {
+			self.entries.clear();
+			self.path = path;
+		}
+	}
+}
@@ -0,0 +1,49 @@
+use crate::hash::HashTable;
+use crate::type_cmd;
+
+// `hash` lists the remembered commands, `hash -r` forgets them all and
+// `hash name` looks `name` up in `PATH` again.
+pub fn hash(table: &mut HashTable, args: &[String]) -> i32 {
+	let mut names = args;
+	while let Some(flag) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		match flag.as_str() {
+			"--" => {
+				names = &names[1..];
+				break;
+			}
+			"-r" => table.clear(),
+			_ => {
+				shell_error!("hash: {}: invalid option", flag);
+				eprintln!("hash: usage: hash [-r] [name ...]");
+				return 2;
+			}
+		}
+		names = &names[1..];
+	}
+
+	if args.is_empty() {
+		let entries = table.list();
+		if entries.is_empty() {
+			println!("hash: hash table empty");
+			return 0;
+		}
+		println!("hits\tcommand");
+		for (hits, path) in entries {
+			println!("{:4}\t{}", hits, path);
+		}
+		return 0;
+	}
+
+	let mut status = 0;
+	for name in names {
+		// builtins are never looked up in `PATH`
+		if type_cmd::BUILTIN_COMMANDS.contains(&name.as_str()) {
+			continue;
+		}
+		if table.rehash(name).is_none() {
+			shell_error!("hash: {}: not found", name);
+			status = 1;
+		}
+	}
+	status
+}
@@ -0,0 +1,255 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use nix::unistd::{getpid, Pid};
+
+use crate::variables::Variables;
+
+// How many entries are kept when `HISTSIZE` is not set.
+const DEFAULT_SIZE: usize = 500;
+
+// Commands entered at the prompt, oldest first. Entry numbers start at 1.
+pub struct History {
+	entries: Vec<String>,
+	// how many of the entries are already in the history file
+	written: usize,
+	// the shell itself, as its forked children get a copy of the list
+	owner: Pid,
+	// counts the changes that take entries away, so a copy of the list only
+	// has to start over after one of them
+	revision: usize,
+}
+
+impl History {
+	pub fn new() -> Self {
+		History {
+			entries: Vec::new(),
+			written: 0,
+			owner: getpid(),
+			revision: 0,
+		}
+	}
+
+	// Record a command, unless `HISTCONTROL` says to leave it out:
+	// `ignorespace` skips commands starting with a space, `ignoredups` skips
+	// a repeat of the previous entry and `ignoreboth` does both. Only the
+	// latest `HISTSIZE` entries are kept.
+	pub fn add(&mut self, command: &str, vars: &Variables) {
+		if command.trim().is_empty() {
+			return;
+		}
+		let control = vars.get("HISTCONTROL").unwrap_or_default();
+		let has = |option: &str| control.split(':').any(|item| item == option || item == "ignoreboth");
+		if has("ignorespace") && command.starts_with(' ') {
+			return;
+		}
+		if has("ignoredups") && self.entries.last().is_some_and(|last| last == command) {
+			return;
+		}
+		self.entries.push(command.to_string());
+		self.truncate(size(vars, "HISTSIZE"));
+	}
+
+	// Drop the oldest entries until at most `size` are left.
+	fn truncate(&mut self, size: usize) {
+		let excess = self.entries.len().saturating_sub(size);
+		if excess > 0 {
+			self.entries.drain(..excess);
+			self.revision += 1;
+		}
+		self.written = self.written.saturating_sub(excess);
+	}
+
+	// Start out with the entries of the history file, if there is one.
+	pub fn load(&mut self, vars: &Variables) {
+		if let Some(path) = file_path(vars) {
+			if self.read_file(&path).is_ok() {
+				self.truncate(size(vars, "HISTSIZE"));
+			}
+		}
+	}
+
+	// Add the entries of this session to the history file as the shell
+	// exits, so sessions running side by side do not overwrite each other,
+	// then drop the oldest lines past `HISTFILESIZE`.
+	pub fn save(&mut self, vars: &Variables) {
+		if getpid() != self.owner {
+			return;
+		}
+		let Some(path) = file_path(vars) else {
+			return;
+		};
+		if self.written >= self.entries.len() {
+			return;
+		}
+		if let Err(err) = self.append_file(&path) {
+			shell_error!("history: {}: {}", path.display(), err);
+			return;
+		}
+		let limit = match vars.get("HISTFILESIZE") {
+			Some(_) => size(vars, "HISTFILESIZE"),
+			None => size(vars, "HISTSIZE"),
+		};
+		let Ok(contents) = fs::read_to_string(&path) else {
+			return;
+		};
+		let lines: Vec<&str> = contents.lines().collect();
+		if lines.len() > limit {
+			let mut kept = lines[lines.len() - limit..].join("\n");
+			kept.push('\n');
+			fs::write(&path, kept).ok();
+		}
+	}
+
+	pub fn entries(&self) -> &[String] {
+		&self.entries
+	}
+
+	pub fn revision(&self) -> usize {
+		self.revision
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.written = 0;
+		self.revision += 1;
+	}
+
+	// Delete entry number `number`, returning whether there was one.
+	pub fn delete(&mut self, number: usize) -> bool {
+		if number == 0 || number > self.entries.len() {
+			return false;
+		}
+		self.entries.remove(number - 1);
+		self.revision += 1;
+		if number <= self.written {
+			self.written -= 1;
+		}
+		true
+	}
+
+	// Add the entries of a history file after the ones already here.
+	pub fn read_file(&mut self, path: &PathBuf) -> io::Result<()> {
+		let contents = fs::read_to_string(path)?;
+		self.entries.extend(contents.lines().filter(|line| !line.is_empty()).map(str::to_string));
+		self.written = self.entries.len();
+		Ok(())
+	}
+
+	// Replace the history file with every entry.
+	pub fn write_file(&mut self, path: &PathBuf) -> io::Result<()> {
+		let mut contents = self.entries.join("\n");
+		contents.push('\n');
+		fs::write(path, contents)?;
+		self.written = self.entries.len();
+		Ok(())
+	}
+
+	// Add the entries that are not in the history file yet to its end.
+	pub fn append_file(&mut self, path: &PathBuf) -> io::Result<()> {
+		let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+		for entry in &self.entries[self.written.min(self.entries.len())..] {
+			writeln!(file, "{}", entry)?;
+		}
+		self.written = self.entries.len();
+		Ok(())
+	}
+}
+
+// The number a size variable is set to. Unset or not a number means the
+// default, and a negative number means no limit.
+fn size(vars: &Variables, name: &str) -> usize {
+	match vars.get(name).map(|value| value.trim().parse::<i64>()) {
+		Some(Ok(size @ 0..)) => size as usize,
+		Some(Ok(_)) => usize::MAX,
+		_ => DEFAULT_SIZE,
+	}
+}
+
+// `HISTFILE`, or `.shell_history` in the home directory when it is not set.
+pub fn file_path(vars: &Variables) -> Option<PathBuf> {
+	if let Some(path) = vars.get("HISTFILE") {
+		return Some(PathBuf::from(path));
+	}
+	let home = env::var("HOME").ok()?;
+	Some(PathBuf::from(home).join(".shell_history"))
+}
+
+// Replace the history references in a line: `!!` is the previous command,
+// `!N` command number N, `!-N` the command N back and `!text` the latest
+// command starting with `text`. Single quotes and a backslash keep a `!`
+// literal, as does a blank, `=` or `(` after it. Returns None when the line
+// has no references.
+pub fn expand(line: &str, history: &History) -> Result<Option<String>, String> {
+	let chars: Vec<char> = line.chars().collect();
+	let mut expanded = String::new();
+	let mut changed = false;
+	let mut in_single = false;
+	let mut in_double = false;
+
+	let mut i = 0;
+	while i < chars.len() {
+		let ch = chars[i];
+		match ch {
+			'\\' if !in_single => {
+				expanded.push(ch);
+				expanded.extend(chars.get(i + 1));
+				i += 2;
+				continue;
+			}
+			'\'' if !in_double => in_single = !in_single,
+			'"' if !in_single => in_double = !in_double,
+			'!' if !in_single => {
+				if let Some((entry, end)) = event(&chars, i + 1, history)? {
+					expanded.push_str(&entry);
+					changed = true;
+					i = end;
+					continue;
+				}
+			}
+			_ => (),
+		}
+		expanded.push(ch);
+		i += 1;
+	}
+
+	Ok(changed.then_some(expanded))
+}
+
+// The entry a reference names along with the index just past it, when the
+// `!` before `chars[start]` starts a reference at all.
+fn event(chars: &[char], start: usize, history: &History) -> Result<Option<(String, usize)>, String> {
+	let entries = history.entries();
+	let (found, end) = match chars.get(start) {
+		None | Some(' ' | '\t' | '\n' | '=' | '(' | '"') => return Ok(None),
+		Some('!') => (entries.last(), start + 1),
+		Some(&ch) if ch.is_ascii_digit() || ch == '-' => {
+			let len = chars[start + 1..].iter().take_while(|ch| ch.is_ascii_digit()).count() + 1;
+			let text: String = chars[start..start + len].iter().collect();
+			let found = match text.parse::<i64>() {
+				Ok(n @ 1..) => entries.get(n as usize - 1),
+				Ok(n @ ..=-1) => entries.len().checked_sub(n.unsigned_abs() as usize).and_then(|i| entries.get(i)),
+				_ => None,
+			};
+			(found, start + len)
+		}
+		Some(_) => {
+			let len = chars[start..]
+				.iter()
+				.take_while(|ch| !ch.is_whitespace() && !";|&()<>\"'".contains(**ch))
+				.count();
+			let prefix: String = chars[start..start + len].iter().collect();
+			(entries.iter().rev().find(|entry| entry.starts_with(&prefix)), start + len)
+		}
+	};
+
+	match found {
+		Some(entry) => Ok(Some((entry.clone(), end))),
+		None => {
+			let text: String = chars[start - 1..end].iter().collect();
+			Err(format!("{}: event not found", text))
+		}
+	}
+}
@@ -0,0 +1,63 @@
+use crate::history::{self, History};
+use crate::variables::Variables;
+
+pub fn history(history: &mut History, vars: &Variables, args: &[String]) -> i32 {
+	match args.first().map(String::as_str) {
+		Some("-c") => {
+			history.clear();
+			0
+		}
+		Some("-d") => {
+			let Some(offset) = args.get(1) else {
+				shell_error!("history: -d: option requires an argument");
+				return 2;
+			};
+			match offset.parse::<usize>() {
+				Ok(number) if history.delete(number) => 0,
+				_ => {
+					shell_error!("history: {}: history position out of range", offset);
+					1
+				}
+			}
+		}
+		Some(flag @ ("-a" | "-w" | "-r")) => {
+			let Some(path) = args.get(1).map(Into::into).or_else(|| history::file_path(vars)) else {
+				shell_error!("history: no history file");
+				return 1;
+			};
+			let result = match flag {
+				"-a" => history.append_file(&path),
+				"-w" => history.write_file(&path),
+				_ => history.read_file(&path),
+			};
+			match result {
+				Ok(()) => 0,
+				Err(err) => {
+					shell_error!("history: {}: {}", path.display(), err);
+					1
+				}
+			}
+		}
+		Some(arg) if arg.starts_with('-') => {
+			shell_error!("history: {}: invalid option", arg);
+			eprintln!("history: usage: history [-c] [-d offset] [n] or history -awr [filename]");
+			2
+		}
+		count => {
+			let entries = history.entries();
+			let shown = match count.map(str::parse::<usize>) {
+				None => entries.len(),
+				Some(Ok(count)) => count.min(entries.len()),
+				Some(Err(_)) => {
+					shell_error!("history: {}: numeric argument required", args[0]);
+					return 1;
+				}
+			};
+			let first = entries.len() - shown;
+			for (i, entry) in entries.iter().enumerate().skip(first) {
+				println!("{:5}  {}", i + 1, entry);
+			}
+			0
+		}
+	}
+}
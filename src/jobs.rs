@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::os::fd::BorrowedFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::sys::signal::{killpg, signal, SigHandler, Signal};
+use nix::sys::wait::{WaitPidFlag, WaitStatus};
+use nix::unistd::{getpgrp, getpid, setpgid, tcsetpgrp, Pid};
+
+use crate::signals::{self, waitpid};
+
+// The signals that stop a process from the keyboard or for using the terminal
+// from the background.
+const STOP_SIGNALS: [Signal; 3] = [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU];
+
+// The terminal of a shell doing job control, kept open on a descriptor of its
+// own, and the shell itself, as its forked children get a copy of both.
+static TERMINAL: AtomicI32 = AtomicI32::new(-1);
+static CONTROLLER: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum State {
+	Running,
+	Stopped,
+	Done(i32),
+	Signaled(Signal),
+}
+
+// A background job: the processes started for one command line.
+pub struct Job {
+	pub id: usize,
+	pub pgid: Pid,
+	// the processes that have not finished yet
+	pub pids: Vec<Pid>,
+	// the last process of the pipeline, which decides how the job ends
+	last: Pid,
+	pub command: String,
+	pub state: State,
+	// `disown -h`: left running when the shell exits
+	pub keep_on_exit: bool,
+}
+
+impl Job {
+	pub fn is_finished(&self) -> bool {
+		matches!(self.state, State::Done(_) | State::Signaled(_))
+	}
+
+	// The exit status of a finished job.
+	pub fn status(&self) -> i32 {
+		match self.state {
+			State::Done(code) => code,
+			State::Signaled(signal) => 128 + signal as i32,
+			_ => 0,
+		}
+	}
+
+	// Take in what `waitpid` reported for one of the job's processes.
+	fn update(&mut self, pid: Pid, status: WaitStatus) {
+		let state = match status {
+			WaitStatus::Exited(_, code) => State::Done(code),
+			WaitStatus::Signaled(_, signal, _) => State::Signaled(signal),
+			WaitStatus::Stopped(..) => {
+				self.state = State::Stopped;
+				return;
+			}
+			WaitStatus::Continued(_) => {
+				self.state = State::Running;
+				return;
+			}
+			_ => return,
+		};
+		self.pids.retain(|&other| other != pid);
+		if pid == self.last {
+			self.state = state;
+		} else if self.pids.is_empty() && !self.is_finished() {
+			self.state = State::Done(0);
+		}
+	}
+
+	pub fn describe(&self) -> String {
+		match self.state {
+			State::Running => "Running".to_string(),
+			State::Stopped => "Stopped".to_string(),
+			State::Done(0) => "Done".to_string(),
+			State::Done(code) => format!("Exit {}", code),
+			State::Signaled(signal) => signal_description(signal).to_string(),
+		}
+	}
+}
+
+// The job table, in the order the jobs were started. Job numbers start at 1
+// and a new job gets one more than the highest number in use.
+pub struct Jobs {
+	jobs: Vec<Job>,
+	// statuses of the finished jobs already dropped from the table by their
+	// process group, so `wait` can still tell how they ended
+	finished: HashMap<Pid, i32>,
+	// the shell itself, as its forked children get a copy of the table
+	owner: Pid,
+}
+
+impl Jobs {
+	pub fn new() -> Self {
+		Jobs {
+			jobs: Vec::new(),
+			finished: HashMap::new(),
+			owner: getpid(),
+		}
+	}
+
+	// Add a job whose process group is led by its first process, returning
+	// its number.
+	pub fn add(&mut self, pids: Vec<Pid>, command: &str) -> usize {
+		let id = self.jobs.last().map_or(1, |job| job.id + 1);
+		self.jobs.push(Job {
+			id,
+			pgid: pids[0],
+			last: pids[pids.len() - 1],
+			pids,
+			command: command.to_string(),
+			state: State::Running,
+			keep_on_exit: false,
+		});
+		id
+	}
+
+	// Every job with the marker `jobs` shows for it.
+	pub fn list(&self) -> impl Iterator<Item = (char, &Job)> {
+		let [current, previous] = self.current();
+		self.jobs.iter().map(move |job| {
+			let marker = match Some(job.id) {
+				id if id == current => '+',
+				id if id == previous => '-',
+				_ => ' ',
+			};
+			(marker, job)
+		})
+	}
+
+	// The numbers of the current and the previous job: the most recently
+	// stopped jobs come first, then the most recently started ones.
+	fn current(&self) -> [Option<usize>; 2] {
+		let stopped = self.jobs.iter().rev().filter(|job| job.state == State::Stopped);
+		let others = self.jobs.iter().rev().filter(|job| job.state != State::Stopped);
+		let mut order = stopped.chain(others).map(|job| job.id);
+		[order.next(), order.next()]
+	}
+
+	// Find the job a `%` job spec names: `%N` by number, `%+` or `%%` for
+	// the current job, `%-` for the previous one, `%name` for the job whose
+	// command starts with `name` and `%?text` for the one that contains
+	// `text`. A bare number works like `%N` and no spec means the current job.
+	pub fn find(&self, spec: Option<&str>) -> Result<usize, String> {
+		let [current, previous] = self.current();
+		let Some(spec) = spec else {
+			return current.ok_or("current: no such job".to_string());
+		};
+		let name = spec.strip_prefix('%').unwrap_or(spec);
+		let found = match name {
+			"" | "%" | "+" => current,
+			"-" => previous,
+			_ if name.chars().all(|ch| ch.is_ascii_digit()) => name
+				.parse::<usize>()
+				.ok()
+				.filter(|&id| self.jobs.iter().any(|job| job.id == id)),
+			_ => {
+				let matches: Vec<usize> = self
+					.jobs
+					.iter()
+					.filter(|job| match name.strip_prefix('?') {
+						Some(text) => job.command.contains(text),
+						None => job.command.starts_with(name),
+					})
+					.map(|job| job.id)
+					.collect();
+				if matches.len() > 1 {
+					return Err(format!("{}: ambiguous job spec", spec));
+				}
+				matches.first().copied()
+			}
+		};
+		found.ok_or(format!("{}: no such job", spec))
+	}
+
+	pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+		self.jobs.iter_mut().find(|job| job.id == id)
+	}
+
+	pub fn remove(&mut self, id: usize) {
+		self.jobs.retain(|job| job.id != id);
+	}
+
+	// Collect every process that has finished or stopped without waiting for
+	// the ones still running.
+	pub fn reap(&mut self) {
+		let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+		for job in self.jobs.iter_mut() {
+			for pid in job.pids.clone() {
+				match waitpid(pid, Some(flags)) {
+					Ok(status) => job.update(pid, status),
+					// someone else already waited for it
+					Err(_) => job.update(pid, WaitStatus::Exited(pid, 0)),
+				}
+			}
+		}
+	}
+
+	// Print the jobs that have finished since the last report and forget them.
+	pub fn report_finished(&mut self) {
+		self.reap();
+		for (marker, job) in self.list() {
+			if job.is_finished() {
+				println!("{}", format_job(job, marker, false));
+			}
+		}
+		self.remove_finished();
+	}
+
+	pub fn remove_finished(&mut self) {
+		for job in self.jobs.iter().filter(|job| job.is_finished()) {
+			self.finished.insert(job.pgid, job.status());
+		}
+		self.jobs.retain(|job| !job.is_finished());
+	}
+
+	// The status of a job that finished and was reported already.
+	pub fn finished_status(&self, pgid: Pid) -> Option<i32> {
+		self.finished.get(&pgid).copied()
+	}
+
+	// Block until a job finishes, or with `stop` until it either finishes or
+	// stops, returning its status. A finished job leaves the table.
+	pub fn wait(&mut self, id: usize, stop: bool) -> i32 {
+		let Some(job) = self.get_mut(id) else {
+			return 127;
+		};
+		let flags = if stop { Some(WaitPidFlag::WUNTRACED) } else { None };
+		while let Some(&pid) = job.pids.first() {
+			match waitpid(pid, flags) {
+				Ok(WaitStatus::Stopped(_, signal)) => {
+					job.state = State::Stopped;
+					return 128 + signal as i32;
+				}
+				Ok(status) => job.update(pid, status),
+				Err(_) => job.update(pid, WaitStatus::Exited(pid, 0)),
+			}
+		}
+		let status = job.status();
+		self.remove(id);
+		status
+	}
+
+	// Wait for the processes of a command run in the foreground, returning
+	// the status of each. Under job control the command has the terminal
+	// meanwhile, and when Ctrl+Z stops it it joins the table as a stopped job.
+	pub fn wait_foreground(&mut self, pids: Vec<Pid>, command: &str) -> Vec<i32> {
+		let Some(&pgid) = pids.first() else {
+			return Vec::new();
+		};
+		let job_control = job_control();
+		if job_control {
+			set_foreground(pgid);
+		}
+		let flags = job_control.then_some(WaitPidFlag::WUNTRACED);
+		let results: Vec<(Pid, WaitStatus)> = pids
+			.iter()
+			.map(|&pid| (pid, waitpid(pid, flags).unwrap_or(WaitStatus::Exited(pid, 1))))
+			.collect();
+		if job_control {
+			reclaim_terminal();
+		}
+
+		let mut stopped = false;
+		let statuses = results
+			.iter()
+			.map(|&(_, status)| match status {
+				WaitStatus::Exited(_, code) => code,
+				WaitStatus::Signaled(_, signal, _) => {
+					// Ctrl+C went to the command alone, but stops the shell too
+					if job_control && signal == Signal::SIGINT {
+						signals::mark_pending(signal);
+					}
+					128 + signal as i32
+				}
+				WaitStatus::Stopped(_, signal) => {
+					stopped = true;
+					128 + signal as i32
+				}
+				_ => 1,
+			})
+			.collect();
+		if stopped {
+			let id = self.add(pids, command);
+			if let Some(job) = self.get_mut(id) {
+				for (pid, status) in results {
+					job.update(pid, status);
+				}
+				job.state = State::Stopped;
+				println!();
+				println!("{}", format_job(job, '+', false));
+			}
+		}
+		statuses
+	}
+
+	// Block until any job finishes, returning its number and status. None
+	// when there is no job left to wait for.
+	pub fn wait_any(&mut self) -> Option<(usize, i32)> {
+		loop {
+			if let Some(job) = self.jobs.iter().find(|job| job.is_finished()) {
+				let finished = (job.id, job.status());
+				self.remove(finished.0);
+				return Some(finished);
+			}
+			if self.jobs.iter().all(|job| job.pids.is_empty()) {
+				return None;
+			}
+			let status = waitpid(Pid::from_raw(-1), None).ok()?;
+			let pid = status.pid()?;
+			if let Some(job) = self.jobs.iter_mut().find(|job| job.pids.contains(&pid)) {
+				job.update(pid, status);
+			}
+		}
+	}
+
+	// Send SIGHUP to every job as the shell exits, waking stopped ones so
+	// they see it.
+	pub fn hang_up(&self) {
+		if getpid() != self.owner {
+			return;
+		}
+		for job in self.jobs.iter().filter(|job| !job.keep_on_exit) {
+			killpg(job.pgid, Signal::SIGHUP).ok();
+			if job.state == State::Stopped {
+				killpg(job.pgid, Signal::SIGCONT).ok();
+			}
+		}
+	}
+
+	// The number of the job a process belongs to.
+	pub fn find_pid(&self, pid: Pid) -> Option<usize> {
+		self.jobs
+			.iter()
+			.find(|job| job.pgid == pid || job.pids.contains(&pid))
+			.map(|job| job.id)
+	}
+
+	// Every job number, oldest first.
+	pub fn ids(&self) -> Vec<usize> {
+		self.jobs.iter().map(|job| job.id).collect()
+	}
+}
+
+// Take on job control in an interactive shell: keep the terminal open away
+// from the descriptors commands use, move the shell to a process group of its
+// own that has the terminal, and ignore the signals that would stop it.
+pub fn enable_job_control() {
+	let Ok(terminal) = fcntl(0, FcntlArg::F_DUPFD_CLOEXEC(255)) else {
+		return;
+	};
+	for stop_signal in STOP_SIGNALS {
+		signals::ignore(stop_signal);
+	}
+	setpgid(Pid::from_raw(0), Pid::from_raw(0)).ok();
+	TERMINAL.store(terminal, Ordering::SeqCst);
+	CONTROLLER.store(getpid().as_raw(), Ordering::SeqCst);
+	reclaim_terminal();
+}
+
+// Whether the shell runs each foreground command as a job of its own. Its
+// forked children never do.
+pub fn job_control() -> bool {
+	TERMINAL.load(Ordering::SeqCst) >= 0 && CONTROLLER.load(Ordering::SeqCst) == getpid().as_raw()
+}
+
+// Set up a forked child that runs a job: put it in the process group `pgid`,
+// or in a new one of its own with None, and give the group the terminal when
+// it runs in the `foreground`. The shell puts the child in the group from its
+// side too, so the group exists whichever of them runs first. The signals
+// the shell ignores for job control get their default action back.
+pub fn start_job(pgid: Option<Pid>, foreground: bool) {
+	setpgid(Pid::from_raw(0), pgid.unwrap_or(Pid::from_raw(0))).ok();
+	if foreground {
+		set_foreground(getpgrp());
+	}
+	if TERMINAL.swap(-1, Ordering::SeqCst) >= 0 {
+		for stop_signal in STOP_SIGNALS {
+			unsafe { signal(stop_signal, SigHandler::SigDfl) }.ok();
+		}
+	}
+}
+
+// Give the terminal to a process group. The shell ignores SIGTTOU meanwhile,
+// as taking the terminal back while it is not the foreground group would
+// otherwise stop it. Without job control there is nothing to hand over.
+pub fn set_foreground(pgid: Pid) {
+	let terminal = TERMINAL.load(Ordering::SeqCst);
+	if terminal < 0 {
+		return;
+	}
+	let previous = unsafe { signal(Signal::SIGTTOU, SigHandler::SigIgn) };
+	tcsetpgrp(unsafe { BorrowedFd::borrow_raw(terminal) }, pgid).ok();
+	if let Ok(previous) = previous {
+		unsafe { signal(Signal::SIGTTOU, previous) }.ok();
+	}
+}
+
+// Take the terminal back for the shell.
+pub fn reclaim_terminal() {
+	set_foreground(getpgrp());
+}
+
+// A job the way `jobs` shows it, with `show_pid` for `jobs -l`.
+pub fn format_job(job: &Job, marker: char, show_pid: bool) -> String {
+	let pid = if show_pid { format!("{} ", job.pgid) } else { String::new() };
+	format!("[{}]{}  {}{:<24}{}", job.id, marker, pid, job.describe(), job.command)
+}
+
+fn signal_description(signal: Signal) -> &'static str {
+	match signal {
+		Signal::SIGHUP => "Hangup",
+		Signal::SIGINT => "Interrupt",
+		Signal::SIGQUIT => "Quit",
+		Signal::SIGABRT => "Aborted",
+		Signal::SIGKILL => "Killed",
+		Signal::SIGSEGV => "Segmentation fault",
+		Signal::SIGPIPE => "Broken pipe",
+		Signal::SIGTERM => "Terminated",
+		_ => signal.as_str(),
+	}
+}
@@ -0,0 +1,41 @@
+use crate::jobs::{self, Jobs, State};
+
+pub fn jobs(jobs: &mut Jobs, args: &[String]) -> i32 {
+	let mut show_pid = false;
+	let mut pids_only = false;
+	let mut only: Option<State> = None;
+	for arg in args {
+		let Some(flags) = arg.strip_prefix('-') else {
+			shell_error!("jobs: {}: no such job", arg);
+			return 1;
+		};
+		for flag in flags.chars() {
+			match flag {
+				'l' => show_pid = true,
+				'p' => pids_only = true,
+				'r' => only = Some(State::Running),
+				's' => only = Some(State::Stopped),
+				_ => {
+					shell_error!("jobs: -{}: invalid option", flag);
+					eprintln!("jobs: usage: jobs [-lprs]");
+					return 2;
+				}
+			}
+		}
+	}
+
+	jobs.reap();
+	for (marker, job) in jobs.list() {
+		if only.is_some_and(|state| job.state != state) {
+			continue;
+		}
+		if pids_only {
+			println!("{}", job.pgid);
+		} else {
+			println!("{}", jobs::format_job(job, marker, show_pid));
+		}
+	}
+	// finished jobs are only ever reported once
+	jobs.remove_finished();
+	0
+}
@@ -0,0 +1,115 @@
+use nix::sys::signal::{self, killpg, Signal};
+use nix::unistd::Pid;
+
+use crate::jobs::Jobs;
+use crate::signals;
+
+pub fn kill(jobs: &mut Jobs, args: &[String]) -> i32 {
+	if args.first().map(String::as_str) == Some("-l") {
+		return list(&args[1..]);
+	}
+
+	// None stands for signal 0, which only checks that the process exists
+	let mut chosen: Option<Signal> = Some(Signal::SIGTERM);
+	let mut targets = args;
+	match targets.first().map(String::as_str) {
+		Some("-s" | "-n") => {
+			let Some(name) = targets.get(1) else {
+				shell_error!("kill: {}: option requires an argument", targets[0]);
+				return 2;
+			};
+			match parse_signal(name) {
+				Ok(signal) => chosen = signal,
+				Err(err) => {
+					shell_error!("kill: {}", err);
+					return 1;
+				}
+			}
+			targets = &targets[2..];
+		}
+		Some("--") => targets = &targets[1..],
+		Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+			match parse_signal(&arg[1..]) {
+				Ok(signal) => chosen = signal,
+				Err(err) => {
+					shell_error!("kill: {}", err);
+					return 1;
+				}
+			}
+			targets = &targets[1..];
+			if targets.first().map(String::as_str) == Some("--") {
+				targets = &targets[1..];
+			}
+		}
+		_ => (),
+	}
+	if targets.is_empty() {
+		eprintln!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]");
+		return 2;
+	}
+
+	let mut status = 0;
+	for target in targets {
+		let result = if target.starts_with('%') {
+			jobs.reap();
+			match jobs.find(Some(target)).map(|id| jobs.get_mut(id).map(|job| job.pgid)) {
+				Ok(Some(pgid)) => killpg(pgid, chosen).map_err(|err| format!("{}: {}", target, err.desc())),
+				Ok(None) | Err(_) => Err(format!("{}: no such job", target)),
+			}
+		} else {
+			match target.parse::<i32>() {
+				Ok(pid) => signal::kill(Pid::from_raw(pid), chosen)
+					.map_err(|err| format!("({}) - {}", pid, err.desc())),
+				Err(_) => Err(format!("{}: arguments must be process or job IDs", target)),
+			}
+		};
+		if let Err(err) = result {
+			shell_error!("kill: {}", err);
+			status = 1;
+		}
+	}
+	status
+}
+
+fn parse_signal(name: &str) -> Result<Option<Signal>, String> {
+	if name == "0" {
+		return Ok(None);
+	}
+	signals::parse(name)
+		.map(Some)
+		.ok_or(format!("{}: invalid signal specification", name))
+}
+
+// `kill -l` shows every signal with its number; given numbers or names it
+// translates each to the other.
+fn list(names: &[String]) -> i32 {
+	if names.is_empty() {
+		let entries: Vec<String> = Signal::iterator()
+			.map(|signal| format!("{:2}) {}", signal as i32, signal.as_str()))
+			.collect();
+		for row in entries.chunks(5) {
+			println!("{}", row.join("\t"));
+		}
+		return 0;
+	}
+
+	let mut status = 0;
+	for name in names {
+		match (name.parse::<i32>(), signals::parse(name)) {
+			// exit statuses of processes killed by a signal work too
+			(Ok(number), _) => match Signal::try_from(number & 0x7f) {
+				Ok(signal) => println!("{}", &signal.as_str()[3..]),
+				Err(_) => {
+					shell_error!("kill: {}: invalid signal specification", name);
+					status = 1;
+				}
+			},
+			(Err(_), Some(signal)) => println!("{}", signal as i32),
+			(Err(_), None) => {
+				shell_error!("kill: {}: invalid signal specification", name);
+				status = 1;
+			}
+		}
+	}
+	status
+}
@@ -0,0 +1,26 @@
+use crate::arith;
+use crate::variables::Variables;
+
+// `let expr...`: evaluate each argument as an arithmetic expression in turn.
+// Succeeds when the last one comes out non-zero and fails when it is zero.
+pub fn let_builtin(vars: &mut Variables, args: &[String]) -> i32 {
+	if args.is_empty() {
+		shell_error!("let: expression expected");
+		return 1;
+	}
+	let mut last = 0;
+	for expr in args {
+		match arith::evaluate(expr, vars) {
+			Ok(value) => last = value,
+			Err(err) => {
+				shell_error!("let: {}", err);
+				return 1;
+			}
+		}
+	}
+	if last != 0 {
+		0
+	} else {
+		1
+	}
+}
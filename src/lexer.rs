@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+
+use crate::utils::{self, QuoteTracker};
+use crate::variables;
+
+// Reserved words after which another command starts.
+const COMMAND_STARTS: [&str; 9] = ["if", "then", "elif", "else", "while", "until", "do", "!", "{"];
+
+// Operators that end a word, longest first.
+const OPERATORS: [&str; 10] = [";;&", ";;", ";&", "&&", "||", ";", "|", "&", "(", ")"];
+
+// Words are kept exactly as written, quotes included, and only expanded once
+// the command they belong to runs.
+#[derive(Clone, PartialEq)]
+pub enum Token {
+	Word(String),
+	Operator(&'static str),
+	Newline,
+	// the expression of a `((...))` that starts a word
+	Arithmetic(String),
+	// the expression between the `[[` and `]]` of a conditional command
+	Conditional(String),
+}
+
+pub enum ParseError {
+	// the input stops in the middle of a command and more lines can finish it
+	Incomplete,
+	Syntax(String),
+}
+
+// The words and operators of some command text.
+pub struct Tokens {
+	pub tokens: Vec<Token>,
+	// the line each token is on, counted from 0
+	pub lines: Vec<usize>,
+	// the heredoc bodies, in the order their operators appear
+	pub heredocs: VecDeque<String>,
+}
+
+// Split command text into words and operators. The body of every heredoc is
+// taken from the lines following the line its operator is on. With `at_eof`
+// no more input will follow, so a missing heredoc delimiter ends the body
+// instead of leaving the input incomplete.
+pub fn tokenize(text: &str, at_eof: bool) -> Result<Tokens, ParseError> {
+	let chars: Vec<char> = text.chars().collect();
+	let mut tokens: Vec<Token> = Vec::new();
+	let mut lines: Vec<usize> = Vec::new();
+	// the line of the character looked at last, and how far it was counted
+	let mut line = 0;
+	let mut counted = 0;
+	let mut heredocs: VecDeque<String> = VecDeque::new();
+	// delimiters of the heredocs whose body starts after the current line
+	let mut pending: Vec<(String, bool)> = Vec::new();
+	let mut quotes = QuoteTracker::new();
+	let mut word = String::new();
+
+	let mut i = 0;
+	while i < chars.len() {
+		// a token belongs to the line of the character that ended it, even
+		// when heredoc bodies were skipped right after
+		lines.resize(tokens.len(), line);
+		line += chars[counted..i].iter().filter(|&&ch| ch == '\n').count();
+		counted = i;
+
+		let ch = chars[i];
+		let is_unquoted = quotes.is_unquoted(ch);
+		// a backslash before a newline joins the two lines
+		if ch == '\\' && quotes.is_escaping() && chars.get(i + 1) == Some(&'\n') {
+			if i + 2 == chars.len() && !at_eof {
+				return Err(ParseError::Incomplete);
+			}
+			quotes.is_unquoted('\n');
+			i += 2;
+			continue;
+		}
+		if !is_unquoted {
+			word.push(ch);
+			i += 1;
+			continue;
+		}
+
+		match ch {
+			' ' | '\t' => end_word(&mut word, &mut tokens),
+			'#' if word.is_empty() => {
+				while i + 1 < chars.len() && chars[i + 1] != '\n' {
+					i += 1;
+				}
+			}
+			'\n' => {
+				end_word(&mut word, &mut tokens);
+				tokens.push(Token::Newline);
+				i = read_heredocs(&chars, i + 1, &mut pending, &mut heredocs, at_eof)?;
+				continue;
+			}
+			'(' if word.is_empty() && chars.get(i + 1) == Some(&'(') => {
+				match arithmetic_end(&chars, i) {
+					Some(end) => {
+						tokens.push(Token::Arithmetic(chars[i + 2..end - 1].iter().collect()));
+						i = end + 1;
+						continue;
+					}
+					None if !at_eof => return Err(ParseError::Incomplete),
+					None => word.push(ch),
+				}
+			}
+			// `[[` only opens a conditional where a command starts
+			'[' if word.is_empty()
+				&& chars.get(i + 1) == Some(&'[')
+				&& chars.get(i + 2).is_some_and(|ch| ch.is_whitespace())
+				&& at_command_start(&tokens) =>
+			{
+				match conditional_end(&chars, i) {
+					Some(end) => {
+						tokens.push(Token::Conditional(chars[i + 2..end - 1].iter().collect()));
+						i = end + 1;
+						continue;
+					}
+					None if !at_eof => return Err(ParseError::Incomplete),
+					None => word.push(ch),
+				}
+			}
+			// the parentheses of `name=(...)` hold the elements of an array
+			'(' if variables::is_array_assignment(&word) => {
+				let Some(end) = array_end(&chars, i) else {
+					if !at_eof {
+						return Err(ParseError::Incomplete);
+					}
+					return Err(ParseError::Syntax("syntax error: unexpected end of file".to_string()));
+				};
+				for &ch in &chars[i..=end] {
+					quotes.is_unquoted(ch);
+					word.push(ch);
+				}
+				i = end + 1;
+				continue;
+			}
+			// `&>` and the `&` of `>&` belong to a redirect
+			'&' if chars.get(i + 1) == Some(&'>') || word.ends_with(['>', '<']) => {
+				word.push(ch);
+			}
+			_ => {
+				let rest = &chars[i..];
+				if let Some(op) = OPERATORS.iter().find(|op| starts_with(rest, op)) {
+					end_word(&mut word, &mut tokens);
+					tokens.push(Token::Operator(op));
+					op.chars().skip(1).for_each(|ch| {
+						quotes.is_unquoted(ch);
+					});
+					i += op.len();
+					continue;
+				}
+				if let Some(heredoc) = heredoc_delimiter(&chars, i) {
+					pending.push(heredoc);
+				}
+				word.push(ch);
+			}
+		}
+		i += 1;
+	}
+	end_word(&mut word, &mut tokens);
+	lines.resize(tokens.len(), line);
+
+	if quotes.is_open() && at_eof {
+		return Err(ParseError::Syntax("syntax error: unexpected end of file".to_string()));
+	}
+	if quotes.is_open() {
+		return Err(ParseError::Incomplete);
+	}
+	read_heredocs(&chars, chars.len(), &mut pending, &mut heredocs, at_eof)?;
+
+	Ok(Tokens {
+		tokens,
+		lines,
+		heredocs,
+	})
+}
+
+// Find the `))` closing the `((` at `chars[start]`, returning the index of its
+// last `)`.
+fn arithmetic_end(chars: &[char], start: usize) -> Option<usize> {
+	let mut depth = 0;
+	for (i, &ch) in chars.iter().enumerate().skip(start) {
+		match ch {
+			'(' => depth += 1,
+			')' if depth == 2 && chars.get(i + 1) == Some(&')') => return Some(i + 1),
+			')' => depth -= 1,
+			_ => (),
+		}
+	}
+	None
+}
+
+// Find the `]]` word closing the `[[` at `chars[start]`, returning the index
+// of its last `]`.
+fn conditional_end(chars: &[char], start: usize) -> Option<usize> {
+	let mut quotes = QuoteTracker::new();
+	for i in start + 2..chars.len() {
+		if !quotes.is_unquoted(chars[i]) || chars[i] != ']' || chars.get(i + 1) != Some(&']') {
+			continue;
+		}
+		let ends_word = chars.get(i + 2).map_or(true, |ch| ch.is_whitespace() || ";&|)".contains(*ch));
+		if chars[i - 1].is_whitespace() && ends_word {
+			return Some(i + 1);
+		}
+	}
+	None
+}
+
+// Whether the next word is in the position of a command name.
+fn at_command_start(tokens: &[Token]) -> bool {
+	match tokens.last() {
+		None | Some(Token::Newline | Token::Operator(_)) => true,
+		Some(Token::Word(word)) => COMMAND_STARTS.contains(&word.as_str()),
+		Some(_) => false,
+	}
+}
+
+// Find the `)` closing the `(` of an array assignment at `chars[start]`.
+fn array_end(chars: &[char], start: usize) -> Option<usize> {
+	let mut quotes = QuoteTracker::new();
+	let mut depth = 0;
+	for (i, &ch) in chars.iter().enumerate().skip(start) {
+		if !quotes.is_unquoted(ch) {
+			continue;
+		}
+		match ch {
+			'(' => depth += 1,
+			')' if depth == 1 => return Some(i),
+			')' => depth -= 1,
+			_ => (),
+		}
+	}
+	None
+}
+
+fn end_word(word: &mut String, tokens: &mut Vec<Token>) {
+	if !word.is_empty() {
+		tokens.push(Token::Word(std::mem::take(word)));
+	}
+}
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+	prefix.chars().enumerate().all(|(i, ch)| chars.get(i) == Some(&ch))
+}
+
+// When `chars[start]` begins a `<<` or `<<-` operator, read the delimiter
+// after it along with whether leading tabs are stripped from the body.
+fn heredoc_delimiter(chars: &[char], start: usize) -> Option<(String, bool)> {
+	let is_heredoc = chars[start] == '<'
+		&& chars.get(start + 1) == Some(&'<')
+		&& chars.get(start + 2) != Some(&'<')
+		&& (start == 0 || chars[start - 1] != '<');
+	if !is_heredoc {
+		return None;
+	}
+	let strip_tabs = chars.get(start + 2) == Some(&'-');
+
+	let mut i = start + if strip_tabs { 3 } else { 2 };
+	while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+		i += 1;
+	}
+	let mut quotes = QuoteTracker::new();
+	let mut delimiter = String::new();
+	while i < chars.len() {
+		let ch = chars[i];
+		if quotes.is_unquoted(ch) && (ch.is_whitespace() || ";|&<>()".contains(ch)) {
+			break;
+		}
+		delimiter.push(ch);
+		i += 1;
+	}
+	Some((utils::remove_quotes(&delimiter), strip_tabs))
+}
+
+// Read the bodies of the pending heredocs from the lines starting at
+// `chars[start]`, returning the index just past the last delimiter line.
+fn read_heredocs(
+	chars: &[char],
+	start: usize,
+	pending: &mut Vec<(String, bool)>,
+	heredocs: &mut VecDeque<String>,
+	at_eof: bool,
+) -> Result<usize, ParseError> {
+	let mut i = start;
+	for (delimiter, strip_tabs) in pending.drain(..) {
+		let mut body = String::new();
+		loop {
+			if i >= chars.len() {
+				if !at_eof {
+					return Err(ParseError::Incomplete);
+				}
+				shell_error!("warning: here-document delimited by end-of-file (wanted `{}')", delimiter);
+				break;
+			}
+			let len = chars[i..].iter().position(|&ch| ch == '\n').unwrap_or(chars.len() - i);
+			let mut line: String = chars[i..i + len].iter().collect();
+			i += len + 1;
+			if strip_tabs {
+				line = line.trim_start_matches('\t').to_string();
+			}
+			if line == delimiter {
+				break;
+			}
+			body.push_str(&line);
+			body.push('\n');
+		}
+		heredocs.push_back(body);
+	}
+	Ok(i.min(chars.len()))
+}
@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+use std::io::{self, IsTerminal, Write};
+use std::rc::Rc;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, EditMode, Editor, Helper};
+
+use crate::completion::{self, Request};
+use crate::prompt::Prompt;
+use crate::{signals, Shell};
+
+// Reads the lines typed at the prompt. On a terminal they are read with line
+// editing and the shell history; otherwise a byte at a time, so commands the
+// shell starts still see the rest of the input.
+pub struct LineEditor {
+	// None when stdin is not a terminal
+	editor: Option<Editor<ShellHelper, DefaultHistory>>,
+	shell: Rc<RefCell<Shell>>,
+	// the history revision and the number of its entries the editor has
+	synced: (usize, usize),
+}
+
+impl LineEditor {
+	pub fn new(shell: Rc<RefCell<Shell>>) -> Self {
+		let mut editor = if io::stdin().is_terminal() { new_editor() } else { None };
+		if let Some(editor) = &mut editor {
+			editor.set_helper(Some(ShellHelper {
+				shell: Rc::clone(&shell),
+			}));
+		}
+		LineEditor {
+			editor,
+			shell,
+			synced: (0, 0),
+		}
+	}
+
+	// Read one line, newline included, after showing `prompt`. Returns 0 at
+	// end of input and fails with `Interrupted` on Ctrl+C, leaving the cursor
+	// on a fresh line. The shell must not be borrowed meanwhile, as
+	// completing a word can run shell functions.
+	pub fn read_line(&mut self, prompt: &Prompt, line: &mut String) -> io::Result<usize> {
+		self.sync_history();
+		let Some(editor) = &mut self.editor else {
+			print!("{}", prompt.text);
+			io::stdout().flush()?;
+			let read = signals::read_line(line);
+			if read.is_err() {
+				println!();
+			}
+			return read;
+		};
+		// the editor works out where the cursor goes from the visible part
+		match editor.readline(&(&prompt.visible, &prompt.text)) {
+			Ok(read) => {
+				line.push_str(&read);
+				line.push('\n');
+				Ok(read.len() + 1)
+			}
+			Err(ReadlineError::Eof) => Ok(0),
+			Err(ReadlineError::Interrupted) => Err(io::ErrorKind::Interrupted.into()),
+			Err(ReadlineError::Io(err)) => Err(err),
+			Err(err) => Err(io::Error::other(err)),
+		}
+	}
+
+	// Give the editor the entries added to the shell history since the last
+	// line, starting over when entries were taken away.
+	fn sync_history(&mut self) {
+		let Some(editor) = &mut self.editor else {
+			return;
+		};
+		let shell = self.shell.borrow();
+		let entries = shell.history.entries();
+		let (revision, mut synced) = self.synced;
+		if revision != shell.history.revision() || synced > entries.len() {
+			editor.history_mut().clear().ok();
+			synced = 0;
+		}
+		for entry in &entries[synced..] {
+			editor.add_history_entry(entry.as_str()).ok();
+		}
+		self.synced = (shell.history.revision(), entries.len());
+	}
+}
+
+fn new_editor() -> Option<Editor<ShellHelper, DefaultHistory>> {
+	// the shell decides what goes into the history. The emacs key bindings
+	// include the incremental history search: Ctrl+R finds the latest entry
+	// containing what is typed, Ctrl+R again an older one, and Ctrl+G or
+	// Escape gives up and puts back the line as it was.
+	let config = Config::builder()
+		.edit_mode(EditMode::Emacs)
+		// Tab completes as far as the matches agree, a second Tab lists them
+		.completion_type(CompletionType::List)
+		.auto_add_history(false)
+		.history_ignore_dups(false)
+		.ok()?
+		.history_ignore_space(false)
+		.max_history_size(usize::MAX)
+		.ok()?
+		.build();
+	Editor::with_config(config).ok()
+}
+
+// Gives the editor what it needs from the shell.
+pub struct ShellHelper {
+	shell: Rc<RefCell<Shell>>,
+}
+
+impl Completer for ShellHelper {
+	type Candidate = Pair;
+
+	// A unique match is followed by a space, or a file name of a directory by
+	// a `/`.
+	fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+		let request = Request::new(line, pos);
+		let matches = completion::complete(&mut self.shell.borrow_mut(), &request);
+		let space = if matches.nospace { "" } else { " " };
+		let candidates = matches.words.into_iter().map(|word| {
+			if !matches.filenames {
+				return Pair {
+					display: word.clone(),
+					replacement: word + space,
+				};
+			}
+			let name = word.rsplit('/').next().unwrap_or_default();
+			if completion::is_dir(&word) {
+				Pair {
+					display: format!("{}/", name),
+					replacement: escape(&word) + "/",
+				}
+			} else {
+				Pair {
+					display: name.to_string(),
+					replacement: escape(&word) + space,
+				}
+			}
+		});
+		Ok((request.start, candidates.collect()))
+	}
+}
+
+// Put a backslash before every character the shell would treat specially.
+fn escape(path: &str) -> String {
+	let mut escaped = String::new();
+	for ch in path.chars() {
+		if " \t\n\\'\"$`;|&()<>*?[]{}!#".contains(ch) {
+			escaped.push('\\');
+		}
+		escaped.push(ch);
+	}
+	escaped
+}
+
+impl Hinter for ShellHelper {
+	type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
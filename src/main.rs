@@ -1,57 +1,773 @@
-#[allow(unused_imports)]
-use std::io::{self, Write};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::rc::Rc;
 
+// Print an error message after the script and line it comes from while a
+// script runs, as every command reports its errors.
+macro_rules! shell_error {
+    ($($arg:tt)*) => {
+        eprintln!("{}{}", $crate::error_prefix(), format_args!($($arg)*))
+    };
+}
+
+mod alias;
+mod alias_cmd;
+mod arith;
+mod bg_cmd;
+mod brace_expand;
 mod cd_cmd;
+mod command_cmd;
+mod completion;
+mod complete_cmd;
+mod compound;
+mod cond_expr;
+mod declare_cmd;
+mod dirs_cmd;
+mod dirstack;
+mod disown_cmd;
+mod env_cmd;
 mod executable_cmd;
+mod expand;
+mod export_cmd;
+mod fg_cmd;
+mod functions;
+mod glob;
+mod hash;
+mod hash_cmd;
+mod history;
+mod history_cmd;
+mod jobs;
+mod jobs_cmd;
+mod kill_cmd;
+mod let_cmd;
+mod lexer;
+mod line_editor;
+mod options;
+mod param_expand;
+mod pipeline;
+mod printf_cmd;
+mod proc_subst;
+mod prompt;
 mod pwd_cmd;
+mod read_cmd;
+mod redirect;
+mod set_cmd;
+mod signals;
+mod source_cmd;
+mod startup;
+mod test_cmd;
+mod trap_cmd;
+mod traps;
 mod type_cmd;
+mod unset_cmd;
 mod utils;
+mod variables;
+mod wait_cmd;
 
 // cat '/tmp/bar/f   55' '/tmp/bar/f   1' '/tmp/bar/f   34'
 
+// State that lives for the whole session and is shared by every command.
+pub struct Shell {
+    pub vars: variables::Variables,
+    pub functions: functions::Functions,
+    // exit status of the most recent pipeline
+    pub last_status: i32,
+    // exit status of the last command substitution of the command being
+    // expanded, which a command of assignments alone ends with
+    pub substitution_status: Option<i32>,
+    // how many loops the running command is in, not counting any outside the
+    // current function
+    pub loop_depth: usize,
+    pub jobs: jobs::Jobs,
+    pub traps: traps::Traps,
+    pub aliases: alias::Aliases,
+    pub history: history::History,
+    pub completions: completion::Completions,
+    pub options: options::ShellOptions,
+    // how many conditions the running command is in, where a failure does
+    // not make `set -e` leave the shell
+    pub condition_depth: usize,
+    // `$$`: the shell's own process, which subshells keep reporting
+    pub pid: u32,
+    // `$!`: the process of the job started in the background most recently
+    pub last_bg_pid: Option<u32>,
+    // the directories `pushd` saved
+    pub dirs: dirstack::DirStack,
+    // where the commands run so far were found in `PATH`
+    pub hash: hash::HashTable,
+    // reading commands from a terminal, where an error that would end a
+    // script only fails its command
+    pub interactive: bool,
+}
+
+// What the shell was started with.
+pub struct Options {
+    // `--norc`: leave out `~/.shellrc`
+    pub norc: bool,
+    // `--noprofile`: leave out `/etc/profile` and `~/.profile`
+    pub noprofile: bool,
+    // `-l`, `--login`, or a name starting with `-`
+    pub login: bool,
+    // `-c`: the command to run instead of reading any
+    pub command: Option<String>,
+    // `-s`: read the commands from stdin even with arguments left
+    pub read_stdin: bool,
+    // what is left after the options: the script and its arguments, the
+    // `$0` and arguments of `-c`, or the arguments of `-s`
+    pub args: Vec<String>,
+}
+
+impl Options {
+    // Whether the commands come from stdin rather than from `-c` or a script.
+    pub fn reads_stdin(&self) -> bool {
+        self.command.is_none() && (self.read_stdin || self.args.is_empty())
+    }
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args();
+    let name = args.next().unwrap_or_default();
+    let mut options = Options {
+        norc: false,
+        noprofile: false,
+        login: name.starts_with('-'),
+        command: None,
+        read_stdin: false,
+        args: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--norc" => options.norc = true,
+            "--noprofile" => options.noprofile = true,
+            "-l" | "--login" => options.login = true,
+            "-s" => options.read_stdin = true,
+            "-c" => match args.next() {
+                Some(command) => options.command = Some(command),
+                None => return Err("-c: option requires an argument".to_string()),
+            },
+            "--" => break,
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("{}: invalid option", arg)),
+            _ => {
+                options.args.push(arg);
+                break;
+            }
+        }
+    }
+    options.args.extend(args);
+    Ok(options)
+}
+
 fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("shell: {}", err);
+            eprintln!("Usage: shell [--norc] [--noprofile] [-l] [-s] [-c command [name [args]]] [script [args]]");
+            std::process::exit(2);
+        }
+    };
+    let mut shell = Shell {
+        vars: variables::Variables::new(),
+        functions: functions::Functions::new(),
+        last_status: 0,
+        substitution_status: None,
+        loop_depth: 0,
+        jobs: jobs::Jobs::new(),
+        traps: traps::Traps::new(),
+        aliases: alias::Aliases::new(),
+        history: history::History::new(),
+        completions: completion::Completions::new(),
+        options: options::ShellOptions::new(),
+        condition_depth: 0,
+        pid: std::process::id(),
+        last_bg_pid: None,
+        dirs: dirstack::DirStack::new(),
+        hash: hash::HashTable::new(),
+        interactive: false,
+    };
+    pwd_cmd::init(&mut shell.vars);
+    signals::install();
+    // known before the startup files run, so an error in them does not end
+    // the shell before its first prompt
+    shell.interactive = options.reads_stdin() && io::stdin().is_terminal();
+    if shell.interactive {
+        jobs::enable_job_control();
+    }
+    startup::run(&mut shell, &options);
+
+    // a command or a script runs instead of the prompt
+    if let Some(command) = &options.command {
+        if let Some((arg0, args)) = options.args.split_first() {
+            shell.vars.set_arg0(arg0);
+            shell.vars.replace_positional(args.to_vec());
+        }
+        let status = match source_cmd::run_script(&mut shell, "-c", command) {
+            Ok(status) | Err(compound::ControlFlow::Return(status)) => status,
+            Err(compound::ControlFlow::Interrupt) => 130,
+            Err(_) => 0,
+        };
+        exit(&mut shell, status);
+    }
+    if options.read_stdin {
+        shell.vars.replace_positional(options.args.clone());
+    } else if let Some((script, script_args)) = options.args.split_first() {
+        let status = run_script_file(&mut shell, script, script_args);
+        exit(&mut shell, status);
+    }
+
+    shell.history.load(&shell.vars);
+    // the editor lives as long as the prompt and reaches the shell to complete
+    // words
+    let shell = Rc::new(RefCell::new(shell));
+    let mut editor = line_editor::LineEditor::new(Rc::clone(&shell));
     // moving this outside to avoid re-allocating every iteration
     let mut input: String = String::new();
 
     loop {
-        print!("$ ");
-        io::stdout().flush().unwrap();
+        {
+            let mut shell = shell.borrow_mut();
+            traps::run_pending(&mut shell);
+            // Ctrl+C at the prompt has nothing left to stop
+            signals::take_interrupt();
+            shell.jobs.report_finished();
+            run_prompt_command(&mut shell);
+        }
+
+        // Wait for user input, stopping at end of file
+        input.clear();
+        let command = read_command(&shell, &mut editor, &mut input);
+        let mut shell = shell.borrow_mut();
+        match command {
+            Ok(Some(Ok(list))) => {
+                compound::run_top_level(&mut shell, &list);
+            }
+            Ok(Some(Err(err))) => shell_error!("{}", err),
+            Ok(None) => {
+                let status = shell.last_status;
+                exit(&mut shell, status);
+            }
+            // Ctrl+C drops what was typed so far and starts over at a fresh
+            // prompt
+            Err(_) => (),
+        }
+    }
+}
+
+// Leave the shell, running the EXIT trap, saving the history and hanging up
+// the jobs still running first.
+pub fn exit(shell: &mut Shell, status: i32) -> ! {
+    traps::run(shell, traps::SignalOrEvent::Exit);
+    shell.history.save(&shell.vars);
+    io::stdout().flush().ok();
+    shell.jobs.hang_up();
+    std::process::exit(status);
+}
+
+// Run a script with its arguments as the positional parameters, returning the
+// status of its last command.
+fn run_script_file(shell: &mut Shell, script: &str, args: &[String]) -> i32 {
+    let text = match std::fs::read_to_string(script) {
+        Ok(text) => text,
+        Err(err) => {
+            let errno = nix::errno::Errno::from_raw(err.raw_os_error().unwrap_or(0));
+            eprintln!("shell: {}: {}", script, errno.desc());
+            return 127;
+        }
+    };
+    shell.vars.set_arg0(script);
+    shell.vars.replace_positional(args.to_vec());
+    match source_cmd::run_script(shell, script, &text) {
+        Ok(status) | Err(compound::ControlFlow::Return(status)) => status,
+        Err(compound::ControlFlow::Interrupt) => 130,
+        Err(_) => 0,
+    }
+}
+
+thread_local! {
+    // the script running and the line its current command starts on, kept
+    // apart from the shell so commands that only see part of it can report
+    // where they failed
+    static LOCATION: RefCell<Option<(String, usize)>> = const { RefCell::new(None) };
+}
+
+// Set the script and line commands run from, returning the ones before.
+pub fn set_location(location: Option<(String, usize)>) -> Option<(String, usize)> {
+    LOCATION.with(|current| current.replace(location))
+}
+
+// Move on to another line of the script running, if one is.
+pub fn set_line(line: usize) {
+    LOCATION.with(|location| {
+        if let Some((_, current)) = &mut *location.borrow_mut() {
+            *current = line;
+        }
+    });
+}
+
+// What goes in front of an error message: the script and line it comes from
+// while a script runs.
+pub fn error_prefix() -> String {
+    LOCATION.with(|location| match &*location.borrow() {
+        Some((script, line)) => format!("shell: {}: line {}: ", script, line),
+        None => String::new(),
+    })
+}
+
+// Run `PROMPT_COMMAND` in the shell itself before the prompt, keeping the
+// status of the last command the user ran.
+fn run_prompt_command(shell: &mut Shell) {
+    let Some(command) = shell.vars.get("PROMPT_COMMAND") else {
+        return;
+    };
+    let status = shell.last_status;
+    run_line(shell, &command);
+    shell.last_status = status;
+}
+
+// Read lines into `input` until they make up complete commands, prompting
+// with `PS1` for the first line and `PS2` for every line after it. Each line has its history
+// references replaced as it comes in, and the whole command goes into the
+// history. None once input has run out.
+fn read_command(
+    shell: &RefCell<Shell>,
+    editor: &mut line_editor::LineEditor,
+    input: &mut String,
+) -> io::Result<Option<Result<compound::CommandList, String>>> {
+    let mut prompt = {
+        let shell = shell.borrow();
+        prompt::expand(&shell.vars.get("PS1").unwrap_or("$ ".to_string()), &shell.vars)
+    };
+    loop {
+        let mut line = String::new();
+        let at_eof = editor.read_line(&prompt, &mut line)? == 0;
+        let mut shell = shell.borrow_mut();
+        let shell = &mut *shell;
+        if at_eof && input.is_empty() {
+            return Ok(None);
+        }
+        match history::expand(&line, &shell.history) {
+            Ok(Some(expanded)) => {
+                print!("{}", expanded);
+                line = expanded;
+            }
+            Ok(None) => (),
+            Err(err) => return Ok(Some(Err(err))),
+        }
+        input.push_str(&line);
+
+        let parsed = compound::parse(input, at_eof, &shell.aliases);
+        if !matches!(parsed, Err(lexer::ParseError::Incomplete)) {
+            shell.history.add(input.trim_end_matches('\n'), &shell.vars);
+        }
+        match parsed {
+            Ok(list) => return Ok(Some(Ok(list))),
+            Err(lexer::ParseError::Syntax(err)) => return Ok(Some(Err(err))),
+            Err(lexer::ParseError::Incomplete) => {
+                prompt = prompt::expand(&shell.vars.get("PS2").unwrap_or("> ".to_string()), &shell.vars);
+            }
+        }
+    }
+}
+
+// Parse and run a whole command line that needs no further input.
+pub fn run_line(shell: &mut Shell, line: &str) -> i32 {
+    match compound::parse(line, true, &shell.aliases) {
+        Ok(list) => compound::run_top_level(shell, &list),
+        Err(lexer::ParseError::Syntax(err)) => {
+            shell_error!("{}", err);
+            2
+        }
+        Err(lexer::ParseError::Incomplete) => 2,
+    }
+}
+
+// Run a single command: start its process substitutions, apply its redirects
+// for the duration of the command and dispatch what is left.
+pub fn run_simple_command(shell: &mut Shell, stage: &pipeline::Stage) -> Result<i32, compound::ControlFlow> {
+    if let Some(line) = stage.line {
+        set_line(line);
+    }
+    let (assignments, command) = variables::take_assignments(&stage.command);
+    let (command, mut substitutions) = match proc_subst::substitute(shell, command) {
+        Ok(substituted) => substituted,
+        Err(err) => {
+            shell_error!("{}", err);
+            return Ok(1);
+        }
+    };
+    // the words are expanded first so `set -x` shows them before the
+    // redirects take effect
+    shell.substitution_status = None;
+    let expanded = expand_command(shell, assignments, command.trim());
+    let substitution_status = shell.substitution_status.take();
+    let guard = match expand_redirects(shell, &stage.redirects, &mut substitutions)
+        .and_then(|redirects| redirect::apply(&redirects))
+    {
+        Ok(guard) => guard,
+        Err(err) => {
+            shell_error!("{}", err);
+            proc_subst::reap(substitutions);
+            return Ok(1);
+        }
+    };
+
+    let status = match expanded {
+        // with no words left, the last command substitution decides
+        Ok((parts, _)) if parts.is_empty() => Ok(substitution_status.unwrap_or(0)),
+        Ok((parts, env)) => run_command(shell, &parts, &env),
+        Err(err) => {
+            shell_error!("{}", err);
+            Ok(1)
+        }
+    };
 
-        // Wait for user input
-        io::stdin().read_line(&mut input).unwrap();
+    drop(guard);
+    proc_subst::reap(substitutions);
+    status
+}
 
-        // let mut parts = input.trim().split_whitespace();
-        let parts = utils::parse_args(input.trim());
-        let cmd = parts.get(0).unwrap().as_str();
-        let args = &parts[1..];
+// Expand the words of a command along with the environment it runs with. A
+// command made of assignments alone sets shell variables instead and leaves
+// no words to run; otherwise the assignments only go into the environment of
+// an executable.
+fn expand_command(
+    shell: &mut Shell,
+    assignments: Vec<(String, String)>,
+    command: &str,
+) -> Result<(Vec<String>, HashMap<String, String>), String> {
+    let parts = match command.split_whitespace().next() {
+        Some("declare" | "typeset" | "readonly" | "local") => expand_declaration(shell, command)?,
+        _ => utils::parse_args(command, shell)?,
+    };
+    if parts.is_empty() {
+        // later assignments can already see the earlier ones
+        for (name, value) in assignments {
+            assign(shell, &name, &value)?;
+        }
+        return Ok((parts, HashMap::new()));
+    }
 
-        match cmd {
-            "exit" => {
-                return;
+    let env: HashMap<String, String> = assignments
+        .into_iter()
+        .map(|(name, value)| {
+            let value = utils::expand_word(&value, shell)?;
+            match name.strip_suffix('+') {
+                Some(name) => Ok((name.to_string(), shell.vars.get(name).unwrap_or_default() + &value)),
+                None => Ok((name, value)),
             }
-            "echo" => {
-                let echo_text = args.join(" ");
-                println!("{}", echo_text.trim());
+        })
+        .collect::<Result<_, String>>()?;
+    let mut words: Vec<String> = env
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, quote_for_trace(value)))
+        .collect();
+    words.sort();
+    words.extend(parts.iter().map(|part| quote_for_trace(part)));
+    trace(shell, &words);
+    Ok((parts, env))
+}
+
+// Expand the words of a declaration such as `declare -a name=(words)`. A
+// `name=(words)` is left as written, for the builtin to assign the words the
+// way an array assignment does.
+fn expand_declaration(shell: &mut Shell, command: &str) -> Result<Vec<String>, String> {
+    let mut parts: Vec<String> = Vec::new();
+    for word in utils::split_words(command) {
+        match word.split_once('=') {
+            Some((name, value))
+                if variables::is_valid_name(name.strip_suffix('+').unwrap_or(name))
+                    && value.starts_with('(')
+                    && value.ends_with(')') =>
+            {
+                parts.push(word.to_string());
             }
-            "type" => {
-                type_cmd::check_type(input.trim());
+            _ => parts.extend(utils::parse_args(word, shell)?),
+        }
+    }
+    Ok(parts)
+}
+
+// Carry out a `name=value` assignment as written: `name=(words)` makes the
+// variable an array of the words, `name[subscript]=value` sets one element
+// and anything else sets the variable. Written `name+=value`, the value is
+// added on to what is already there.
+pub fn assign(shell: &mut Shell, name: &str, value: &str) -> Result<(), String> {
+    let (name, append) = match name.strip_suffix('+') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if let Some(words) = value.strip_prefix('(').and_then(|value| value.strip_suffix(')')) {
+        if variables::is_valid_name(name) {
+            return assign_array(shell, name, words, append);
+        }
+    }
+    let mut value = utils::expand_word(value, shell)?;
+    let operator = if append { "+=" } else { "=" };
+    trace(shell, &[format!("{}{}{}", name, operator, quote_for_trace(&value))]);
+    if append {
+        value = appended(shell, name, &value)?;
+    }
+    match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+        Some((name, subscript)) => param_expand::set_element(shell, name, subscript, &value),
+        None => shell.vars.set(name, &value),
+    }
+}
+
+// What `name+=value` leaves in a variable or element: its value with `value`
+// added on the end, or for an integer variable the sum of the two.
+fn appended(shell: &mut Shell, name: &str, value: &str) -> Result<String, String> {
+    let (base, current) = match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+        Some((base, subscript)) => (base, param_expand::get_element(shell, base, subscript)?),
+        None => (name, shell.vars.get(name)),
+    };
+    let current = current.unwrap_or_default();
+    if shell.vars.is_integer(base) {
+        let sum = current.parse::<i64>().unwrap_or(0) + arith::evaluate(value, &mut shell.vars)?;
+        return Ok(sum.to_string());
+    }
+    Ok(current + value)
+}
+
+// Give an array the words between the parentheses of `name=(words)` as its
+// elements, in turn. A word written `[subscript]=value` puts its value under
+// that index, with the words after it following on from there, or under that
+// key of an associative array, which needs every word written that way.
+// With `append`, as for `name+=(words)`, the elements already there are kept
+// and the new ones follow on after the last of them.
+pub fn assign_array(shell: &mut Shell, name: &str, words: &str, append: bool) -> Result<(), String> {
+    let words = utils::parse_args(words, shell)?;
+    let quoted: Vec<String> = words.iter().map(|word| quote_for_trace(word)).collect();
+    let operator = if append { "+=" } else { "=" };
+    trace(shell, &[format!("{}{}({})", name, operator, quoted.join(" "))]);
+    let elements: Vec<(Option<&str>, &str)> = words
+        .iter()
+        .map(|word| match word.strip_prefix('[').and_then(|word| word.split_once("]=")) {
+            Some((subscript, value)) => (Some(subscript), value),
+            None => (None, word.as_str()),
+        })
+        .collect();
+
+    if shell.vars.is_assoc(name) {
+        let mut keyed: Vec<(&str, &str)> = Vec::new();
+        for (key, value) in elements {
+            match key {
+                Some(key) => keyed.push((key, value)),
+                None => return Err(format!("{}: {}: must use subscript when assigning associative array", name, value)),
             }
-            "pwd" => {
-                let cwd = pwd_cmd::get_pwd();
-                println!("{}", cwd.into_os_string().into_string().unwrap());
+        }
+        if !append {
+            return shell.vars.set_assoc(name, &keyed);
+        }
+        for (key, value) in keyed {
+            shell.vars.set_key(name, key, value)?;
+        }
+        return Ok(());
+    }
+    let mut index = 0;
+    if append {
+        let last = shell.vars.indexes(name).last().and_then(|index| index.parse::<i64>().ok());
+        index = last.map_or(0, |last| last + 1);
+    } else {
+        shell.vars.set_array(name, &[])?;
+    }
+    for (subscript, value) in elements {
+        if let Some(subscript) = subscript {
+            index = arith::evaluate(subscript, &mut shell.vars)?;
+        }
+        shell.vars.set_element(name, index, value)?;
+        index += 1;
+    }
+    Ok(())
+}
+
+// Under `set -x`, show a command on stderr after `PS4` once its words are
+// expanded. The line goes out in a single write, so the traces of the
+// commands of a pipeline never run into each other.
+pub fn trace(shell: &Shell, words: &[String]) {
+    if !shell.options.xtrace {
+        return;
+    }
+    let prefix = shell.vars.get("PS4").unwrap_or("+ ".to_string());
+    let line = format!("{}{}\n", prefix, words.join(" "));
+    io::stderr().write_all(line.as_bytes()).ok();
+}
+
+// Quote a word of a traced command when it would not read back as one word.
+pub fn quote_for_trace(word: &str) -> String {
+    let plain = |ch: char| ch.is_ascii_alphanumeric() || "_./=:,+@%-".contains(ch);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// Expand redirect targets right before the command runs, starting any process
+// substitution used as a target.
+pub fn expand_redirects(
+    shell: &mut Shell,
+    redirects: &[redirect::Redirect],
+    substitutions: &mut Vec<proc_subst::ProcessSubstitution>,
+) -> Result<Vec<redirect::Redirect>, String> {
+    let mut expanded = redirects.to_vec();
+    for redirect in expanded.iter_mut() {
+        match &mut redirect.kind {
+            redirect::RedirectKind::Duplicate(_) => (),
+            redirect::RedirectKind::Heredoc { expand, body, .. } => {
+                if *expand {
+                    *body = redirect::expand_heredoc(body, shell)?;
+                }
+            }
+            // the word is the text itself, so it is never split or globbed
+            redirect::RedirectKind::HereString => {
+                redirect.target = utils::expand_word(&redirect.target, shell)?;
             }
-            "cd" => {
-                cd_cmd::change_directory(&args.join(" "));
+            _ if redirect.target.starts_with("<(") => {
+                let (target, started) = proc_subst::substitute(shell, &redirect.target)?;
+                redirect.target = target;
+                substitutions.extend(started);
             }
             _ => {
-                if type_cmd::get_executable(cmd).is_some() {
-                    executable_cmd::run_executable(cmd, args);
-                } else {
-                    println!("{}: command not found", input.trim());
+                let words = utils::parse_args(&redirect.target, shell)?;
+                match words.as_slice() {
+                    [target] => redirect.target = target.clone(),
+                    _ => return Err(format!("{}: ambiguous redirect", redirect.target)),
                 }
             }
         }
+    }
 
-        input.clear();
+    Ok(expanded)
+}
+
+// Dispatch a single parsed command to a builtin or an external executable and
+// return its exit status.
+pub fn run_command(
+    shell: &mut Shell,
+    parts: &[String],
+    env: &HashMap<String, String>,
+) -> Result<i32, compound::ControlFlow> {
+    let Some(cmd) = parts.first() else {
+        return Ok(0);
+    };
+    let cmd = cmd.as_str();
+    let args = &parts[1..];
+
+    if let Some(body) = shell.functions.get(cmd) {
+        return Ok(functions::call(shell, &body, args));
     }
+    run_builtin(shell, parts, env)
+}
+
+// Run a command as a builtin or an external executable, whether or not a
+// function has its name.
+pub fn run_builtin(
+    shell: &mut Shell,
+    parts: &[String],
+    env: &HashMap<String, String>,
+) -> Result<i32, compound::ControlFlow> {
+    let Some(cmd) = parts.first() else {
+        return Ok(0);
+    };
+    let cmd = cmd.as_str();
+    let args = &parts[1..];
+
+    let status = match cmd {
+        "exit" => {
+            let code = args.first().and_then(|arg| arg.parse().ok()).unwrap_or(shell.last_status);
+            exit(shell, code);
+        }
+        // without an argument the function returns the status of the command
+        // run before `return`
+        "return" => {
+            let status = match args.first() {
+                Some(arg) => match arg.parse::<i32>() {
+                    Ok(status) => status & 0xff,
+                    Err(_) => {
+                        shell_error!("return: {}: numeric argument required", arg);
+                        2
+                    }
+                },
+                None => shell.last_status,
+            };
+            return Err(compound::ControlFlow::Return(status));
+        }
+        "break" | "continue" => {
+            if shell.loop_depth == 0 {
+                shell_error!("{}: only meaningful in a `for', `while', or `until' loop", cmd);
+                return Ok(1);
+            }
+            let levels = match args.first().map(|arg| arg.parse::<usize>()) {
+                None => 1,
+                Some(Ok(levels @ 1..)) => levels.min(shell.loop_depth),
+                Some(_) => {
+                    shell_error!("{}: {}: loop count out of range", cmd, args[0]);
+                    return Ok(1);
+                }
+            };
+            return Err(match cmd {
+                "break" => compound::ControlFlow::Break(levels),
+                _ => compound::ControlFlow::Continue(levels),
+            });
+        }
+        "source" | "." => return source_cmd::source(shell, cmd, args),
+        "command" => return command_cmd::command(shell, args, env),
+        // only the builtins themselves, never a function or a file
+        "builtin" => match args.first() {
+            None => 0,
+            Some(name) if type_cmd::BUILTIN_COMMANDS.contains(&name.as_str()) => {
+                return run_builtin(shell, args, env);
+            }
+            Some(name) => {
+                shell_error!("builtin: {}: not a shell builtin", name);
+                1
+            }
+        },
+        "echo" => {
+            let echo_text = args.join(" ");
+            println!("{}", echo_text.trim());
+            0
+        }
+        "type" => type_cmd::type_builtin(&shell.aliases, &shell.functions, args),
+        "pwd" => pwd_cmd::pwd(&shell.vars, args),
+        "cd" => cd_cmd::cd(&mut shell.vars, args),
+        "pushd" => dirs_cmd::pushd(&mut shell.dirs, &mut shell.vars, args),
+        "popd" => dirs_cmd::popd(&mut shell.dirs, &mut shell.vars, args),
+        "dirs" => dirs_cmd::dirs(&mut shell.dirs, &shell.vars, args),
+        "hash" => hash_cmd::hash(&mut shell.hash, args),
+        "declare" | "typeset" => declare_cmd::declare(shell, args),
+        "readonly" => declare_cmd::readonly(shell, args),
+        "let" => let_cmd::let_builtin(&mut shell.vars, args),
+        "test" => test_cmd::test(shell, args),
+        "[" => test_cmd::bracket(shell, args),
+        "printf" => printf_cmd::printf(&mut shell.vars, args),
+        "read" => read_cmd::read(&mut shell.vars, args, env),
+        "mapfile" | "readarray" => read_cmd::mapfile(&mut shell.vars, args),
+        "export" => export_cmd::export(&mut shell.vars, args),
+        "local" => declare_cmd::local(shell, args),
+        "unset" => unset_cmd::unset(&mut shell.vars, &mut shell.functions, args),
+        "env" => env_cmd::env(&mut shell.jobs, args, env),
+        "jobs" => jobs_cmd::jobs(&mut shell.jobs, args),
+        "fg" => fg_cmd::fg(&mut shell.jobs, args),
+        "bg" => bg_cmd::bg(&mut shell.jobs, args),
+        "wait" => wait_cmd::wait(&mut shell.jobs, args),
+        "disown" => disown_cmd::disown(&mut shell.jobs, args),
+        "trap" => trap_cmd::trap(&mut shell.traps, args),
+        "kill" => kill_cmd::kill(&mut shell.jobs, args),
+        "alias" => alias_cmd::alias(&mut shell.aliases, args),
+        "unalias" => alias_cmd::unalias(&mut shell.aliases, args),
+        "history" => history_cmd::history(&mut shell.history, &shell.vars, args),
+        "complete" => complete_cmd::complete(&mut shell.completions, args),
+        "set" => set_cmd::set(&mut shell.options, &mut shell.vars, args),
+        "shopt" => set_cmd::shopt(&mut shell.options, args),
+        _ => {
+            if let Some(path) = type_cmd::get_executable(&mut shell.hash, cmd) {
+                executable_cmd::run_executable(&mut shell.jobs, &path, cmd, args, env, true)
+            } else {
+                shell_error!("{}: command not found", cmd);
+                127
+            }
+        }
+    };
+    Ok(status)
 }
@@ -0,0 +1,74 @@
+// The options `set` turns on and off.
+pub struct ShellOptions {
+	// `-e`: leave the shell as soon as a command fails
+	pub errexit: bool,
+	// `-x`: show every command as it runs
+	pub xtrace: bool,
+	// `-u`: expanding a variable that is not set is an error
+	pub nounset: bool,
+	// `-o pipefail`: a pipeline fails when any of its commands does
+	pub pipefail: bool,
+	// `shopt -s nullglob`: a pattern that matches no file leaves no word
+	// behind instead of itself
+	pub nullglob: bool,
+	// `shopt -s dotglob`: patterns match names that start with `.` too
+	pub dotglob: bool,
+}
+
+impl ShellOptions {
+	pub fn new() -> Self {
+		ShellOptions {
+			errexit: false,
+			xtrace: false,
+			nounset: false,
+			pipefail: false,
+			nullglob: false,
+			dotglob: false,
+		}
+	}
+
+	// The name of the option a single letter stands for.
+	pub fn name_of(flag: char) -> Option<&'static str> {
+		match flag {
+			'e' => Some("errexit"),
+			'x' => Some("xtrace"),
+			'u' => Some("nounset"),
+			_ => None,
+		}
+	}
+
+	// Every option by name, with whether it is on.
+	pub fn list(&self) -> [(&'static str, bool); 4] {
+		[
+			("errexit", self.errexit),
+			("nounset", self.nounset),
+			("pipefail", self.pipefail),
+			("xtrace", self.xtrace),
+		]
+	}
+
+	pub fn set(&mut self, name: &str, on: bool) -> Result<(), String> {
+		match name {
+			"errexit" => self.errexit = on,
+			"xtrace" => self.xtrace = on,
+			"nounset" => self.nounset = on,
+			"pipefail" => self.pipefail = on,
+			_ => return Err(format!("{}: invalid option name", name)),
+		}
+		Ok(())
+	}
+
+	// Every option `shopt` sets by name, with whether it is on.
+	pub fn shopt_list(&self) -> [(&'static str, bool); 2] {
+		[("dotglob", self.dotglob), ("nullglob", self.nullglob)]
+	}
+
+	pub fn set_shopt(&mut self, name: &str, on: bool) -> Result<(), String> {
+		match name {
+			"dotglob" => self.dotglob = on,
+			"nullglob" => self.nullglob = on,
+			_ => return Err(format!("{}: invalid shell option name", name)),
+		}
+		Ok(())
+	}
+}
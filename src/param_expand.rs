@@ -0,0 +1,489 @@
+use crate::arith;
+use crate::glob;
+use crate::utils::{self, QuoteTracker};
+use crate::variables;
+use crate::Shell;
+
+// Expand a parameter reference: the name of a `$name`, or everything between
+// the braces of a `${...}`, where a subscript after the name picks elements
+// of an array and an operator after that can work on the value. The words
+// after an operator are only expanded when they are used.
+pub fn expand(shell: &mut Shell, reference: &str) -> Result<String, String> {
+	if let Some(name) = reference.strip_prefix('#').filter(|name| !name.is_empty()) {
+		return length(shell, reference, name);
+	}
+	if reference.len() > 1 && reference.starts_with('!') {
+		return match expand_list(shell, reference) {
+			Some(list) => Ok(list?.0.join(" ")),
+			None => Err(format!("${{{}}}: bad substitution", reference)),
+		};
+	}
+	// an operator after a list works on each of its words
+	if let Some(list) = expand_list(shell, reference) {
+		return Ok(list?.0.join(" "));
+	}
+	let (name, rest) = split_name(reference);
+	if name.is_empty() {
+		return Err(format!("${{{}}}: bad substitution", reference));
+	}
+	let (subscript, rest) = split_subscript(rest);
+	// the parameter as written, for messages
+	let param = &reference[..reference.len() - rest.len()];
+	let value = match subscript {
+		None => lookup(shell, name),
+		Some("@" | "*") => shell.vars.elements(name).map(|elements| elements.join(" ")),
+		Some(subscript) => get_element(shell, name, subscript)?,
+	};
+	let is_list = matches!((name, subscript), ("@" | "*", None) | (_, Some("@" | "*")));
+	// the operators that work on the value itself take unset as empty
+	let operand = rest.strip_prefix(':').unwrap_or(rest);
+	if !operand.starts_with(['-', '+', '=', '?']) {
+		let value = match value {
+			Some(value) => value,
+			None if shell.options.nounset && !is_list => return fatal(shell, format!("{}: unbound variable", param)),
+			None => String::new(),
+		};
+		if let Some(transform) = Transform::parse(shell, rest) {
+			return Ok(transform?.apply(&value));
+		}
+		return match rest.chars().next() {
+			None => Ok(value),
+			Some(':') if !operand.is_empty() => match sliced_list(shell, name, subscript) {
+				Some(items) => Ok(slice(shell, items, operand)?.join(" ")),
+				None => Ok(slice(shell, value.chars().map(String::from).collect(), operand)?.concat()),
+			},
+			_ => Err(format!("${{{}}}: bad substitution", reference)),
+		};
+	}
+
+	// with a `:` in front an empty value counts as unset for the operator
+	let (colon, rest) = match rest.strip_prefix(':') {
+		Some(rest) => (true, rest),
+		None => (false, rest),
+	};
+	let is_set = value.as_ref().is_some_and(|value| !colon || !value.is_empty());
+	let mut chars = rest.chars();
+	match chars.next() {
+		Some('-') if is_set => Ok(value.unwrap_or_default()),
+		Some('-') => utils::expand_word(chars.as_str(), shell),
+		Some('+') if is_set => utils::expand_word(chars.as_str(), shell),
+		Some('+') => Ok(String::new()),
+		Some('=') if is_set => Ok(value.unwrap_or_default()),
+		Some('=') if !variables::is_valid_name(name) || is_list => Err(format!("${}: cannot assign in this way", param)),
+		Some('=') => {
+			let word = utils::expand_word(chars.as_str(), shell)?;
+			match subscript {
+				Some(subscript) => {
+					set_element(shell, name, subscript, &word)?;
+					// an integer variable holds what the word evaluated to
+					Ok(get_element(shell, name, subscript)?.unwrap_or(word))
+				}
+				None => {
+					shell.vars.set(name, &word)?;
+					Ok(shell.vars.get(name).unwrap_or(word))
+				}
+			}
+		}
+		// the command does not run, with the message or a default as the error
+		Some('?') if is_set => Ok(value.unwrap_or_default()),
+		Some('?') => match utils::expand_word(chars.as_str(), shell)? {
+			message if message.is_empty() => fatal(shell, format!("{}: parameter null or not set", param)),
+			message => fatal(shell, format!("{}: {}", param, message)),
+		},
+		_ => Err(format!("${{{}}}: bad substitution", reference)),
+	}
+}
+
+// Fail with an error that also leaves the shell when it is not reading
+// commands from a terminal, so a script never goes on without a value it
+// needs, as after an unset variable under `set -u` or `${name?}`.
+fn fatal(shell: &mut Shell, message: String) -> Result<String, String> {
+	if !shell.interactive {
+		shell_error!("{}", message);
+		crate::exit(shell, 1);
+	}
+	Err(message)
+}
+
+// An operator that works on a value as text, with its words already
+// expanded, so it can be applied to every element of a list in turn.
+enum Transform {
+	// `#`, `##`, `%` and `%%`
+	Strip {
+		suffix: bool,
+		longest: bool,
+		pattern: String,
+	},
+	// `/`, `//`, `/#` and `/%`
+	Replace {
+		pattern: String,
+		replacement: String,
+		anchor: Option<char>,
+		all: bool,
+	},
+	// `^`, `^^`, `,` and `,,`
+	Case {
+		pattern: String,
+		upper: bool,
+		all: bool,
+	},
+}
+
+impl Transform {
+	// The operator at the start of `rest` with its words expanded, or None
+	// when it is not one of these.
+	fn parse(shell: &mut Shell, rest: &str) -> Option<Result<Transform, String>> {
+		let op = rest.chars().next().filter(|&op| "#%/^,".contains(op))?;
+		let (doubled, rest) = doubled(rest);
+		let transform = match op {
+			'#' | '%' => utils::expand_pattern(rest, shell).map(|pattern| Transform::Strip {
+				suffix: op == '%',
+				longest: doubled,
+				pattern,
+			}),
+			'/' => {
+				let (pattern, replacement) = split_replacement(rest);
+				let (anchor, pattern) = match pattern.chars().next() {
+					Some(anchor @ ('#' | '%')) if !doubled => (Some(anchor), &pattern[1..]),
+					_ => (None, pattern),
+				};
+				utils::expand_pattern(pattern, shell).and_then(|pattern| {
+					Ok(Transform::Replace {
+						pattern,
+						replacement: utils::expand_word(replacement, shell)?,
+						anchor,
+						all: doubled,
+					})
+				})
+			}
+			_ => utils::expand_pattern(rest, shell).map(|pattern| Transform::Case {
+				pattern,
+				upper: op == '^',
+				all: doubled,
+			}),
+		};
+		Some(transform)
+	}
+
+	fn apply(&self, value: &str) -> String {
+		match self {
+			Transform::Strip { suffix: false, longest, pattern } => strip_prefix(value, pattern, *longest).to_string(),
+			Transform::Strip { suffix: true, longest, pattern } => strip_suffix(value, pattern, *longest).to_string(),
+			Transform::Replace {
+				pattern,
+				replacement,
+				anchor,
+				all,
+			} => replace(value, pattern, replacement, *anchor, *all),
+			Transform::Case { pattern, upper, all } => convert_case(value, pattern, *upper, *all),
+		}
+	}
+}
+
+// Whether the operator at the start of `rest` is written twice, as in `##`,
+// along with what follows it.
+fn doubled(rest: &str) -> (bool, &str) {
+	let mut chars = rest.chars();
+	let op = chars.next();
+	match chars.next() {
+		Some(ch) if Some(ch) == op => (true, chars.as_str()),
+		_ => (false, &rest[op.map_or(0, char::len_utf8)..]),
+	}
+}
+
+// `${name#pattern}`: the value without the shortest start the pattern
+// matches, trying longer and longer ones. `${name##pattern}` takes off the
+// longest one instead, trying shorter and shorter ones.
+fn strip_prefix<'a>(value: &'a str, pattern: &str, longest: bool) -> &'a str {
+	let mut ends: Vec<usize> = value.char_indices().map(|(idx, _)| idx).chain([value.len()]).collect();
+	if longest {
+		ends.reverse();
+	}
+	for end in ends {
+		if glob::is_match(pattern, &value[..end]) {
+			return &value[end..];
+		}
+	}
+	value
+}
+
+// `${name%pattern}`: the value without the shortest end the pattern
+// matches, trying longer and longer ones. `${name%%pattern}` takes off the
+// longest one instead, trying shorter and shorter ones.
+fn strip_suffix<'a>(value: &'a str, pattern: &str, longest: bool) -> &'a str {
+	let mut starts: Vec<usize> = value.char_indices().map(|(idx, _)| idx).chain([value.len()]).collect();
+	if !longest {
+		starts.reverse();
+	}
+	for start in starts {
+		if glob::is_match(pattern, &value[start..]) {
+			return &value[..start];
+		}
+	}
+	value
+}
+
+// Split the `pattern/replacement` of a `${name/pattern/replacement}` at the
+// first `/` that is not quoted. Without one the replacement is empty.
+fn split_replacement(text: &str) -> (&str, &str) {
+	let mut quotes = QuoteTracker::new();
+	match text.char_indices().find(|&(_, ch)| quotes.is_unquoted(ch) && ch == '/') {
+		Some((idx, _)) => (&text[..idx], &text[idx + 1..]),
+		None => (text, ""),
+	}
+}
+
+// `${name/pattern/replacement}`: the value with the first and longest text
+// the pattern matches replaced, or with `${name//pattern/replacement}` every
+// such text, looking on after each replacement. A `#` anchor makes the match
+// start the value and a `%` anchor makes it end it. An empty pattern only
+// matches with an anchor, which puts the replacement before or after the
+// value, and a `&` in the replacement stands for itself rather than the
+// matched text.
+fn replace(value: &str, pattern: &str, replacement: &str, anchor: Option<char>, all: bool) -> String {
+	if pattern.is_empty() {
+		return match anchor {
+			Some('#') => format!("{}{}", replacement, value),
+			Some('%') => format!("{}{}", value, replacement),
+			_ => value.to_string(),
+		};
+	}
+	let bounds: Vec<usize> = value.char_indices().map(|(idx, _)| idx).chain([value.len()]).collect();
+	let mut replaced = String::new();
+	// where the text not yet copied into `replaced` starts
+	let mut copied = 0;
+	let mut n = 0;
+	// nothing is left to replace at the very end, except in an empty value
+	let starts = (bounds.len() - 1).max(1);
+	while n < starts {
+		let start = bounds[n];
+		if anchor == Some('#') && start > 0 {
+			break;
+		}
+		let mut ends = bounds[n..].iter().rev().filter(|&&end| anchor != Some('%') || end == value.len());
+		let Some(&end) = ends.find(|&&end| glob::is_match(pattern, &value[start..end])) else {
+			n += 1;
+			continue;
+		};
+		replaced.push_str(&value[copied..start]);
+		replaced.push_str(replacement);
+		copied = end;
+		if !all {
+			break;
+		}
+		// an empty match moves on a character so the search gets anywhere
+		n = bounds.iter().position(|&bound| bound == end).unwrap_or(bounds.len()).max(n + 1);
+	}
+	replaced.push_str(&value[copied..]);
+	replaced
+}
+
+// The words a reference to a list expands to: `$@`, `$*`, `${name[@]}` or
+// `${name[*]}`, maybe sliced with `:offset:length` or with an operator such
+// as `#pattern` applied to each of them, along with whether they are joined
+// into one word in double quotes as for `"$*"`. `${!name[@]}` and
+// `${!name[*]}` list the indexes or keys of an array. None for any other
+// reference.
+pub fn expand_list(shell: &mut Shell, reference: &str) -> Option<Result<(Vec<String>, bool), String>> {
+	if let Some(reference) = reference.strip_prefix('!').filter(|reference| reference.len() > 1) {
+		let (name, rest) = split_name(reference);
+		return match split_subscript(rest) {
+			(Some(subscript @ ("@" | "*")), "") => Some(Ok((shell.vars.indexes(name), subscript == "*"))),
+			_ => None,
+		};
+	}
+	let (name, rest) = split_name(reference);
+	let (subscript, rest) = split_subscript(rest);
+	let star = match (name, subscript) {
+		("@", None) | (_, Some("@")) => false,
+		("*", None) | (_, Some("*")) => true,
+		_ => return None,
+	};
+	let items = match subscript {
+		Some(_) => shell.vars.elements(name).unwrap_or_default(),
+		None => shell.vars.positional().to_vec(),
+	};
+	if rest.is_empty() {
+		return Some(Ok((items, star)));
+	}
+	if let Some(transform) = Transform::parse(shell, rest) {
+		return Some(transform.map(|transform| (items.iter().map(|item| transform.apply(item)).collect(), star)));
+	}
+	let range = rest.strip_prefix(':').filter(|range| !range.is_empty() && !range.starts_with(['-', '+', '=', '?']))?;
+	let items = sliced_list(shell, name, subscript)?;
+	Some(slice(shell, items, range).map(|items| (items, star)))
+}
+
+// The words `${@:offset:length}` and `${name[@]:offset:length}` count: the
+// positional parameters with `$0` in front, or the elements of an array.
+fn sliced_list(shell: &Shell, name: &str, subscript: Option<&str>) -> Option<Vec<String>> {
+	match (name, subscript) {
+		(_, Some("@" | "*")) => Some(shell.vars.elements(name).unwrap_or_default()),
+		("@" | "*", None) => Some(
+			[shell.vars.get("0").unwrap_or_default()]
+				.into_iter()
+				.chain(shell.vars.positional().iter().cloned())
+				.collect(),
+		),
+		_ => None,
+	}
+}
+
+// `${name:offset}` and `${name:offset:length}`: the items from `offset` on,
+// at most `length` of them, where the items are the characters of a value or
+// the words of a list. Both are arithmetic, and a negative one counts back
+// from the end, which needs a space after the `:` so it is not read as `:-`.
+// A range reaching past the end is cut off there.
+fn slice(shell: &mut Shell, items: Vec<String>, range: &str) -> Result<Vec<String>, String> {
+	let (offset, length) = match range.split_once(':') {
+		Some((offset, length)) => (offset, Some(length)),
+		None => (range, None),
+	};
+	let len = items.len() as i64;
+	let offset = arith::evaluate(&utils::expand_word(offset, shell)?, &mut shell.vars)?;
+	let start = if offset < 0 { len + offset } else { offset };
+	// counting back past the start leaves nothing
+	if start < 0 {
+		return Ok(Vec::new());
+	}
+	let end = match length {
+		Some(length) => match arith::evaluate(&utils::expand_word(length, shell)?, &mut shell.vars)? {
+			length if length < 0 => len + length,
+			length => start.saturating_add(length),
+		},
+		None => len,
+	};
+	let (start, end) = (start.min(len) as usize, end.clamp(0, len) as usize);
+	Ok(items.get(start..end.max(start)).unwrap_or_default().to_vec())
+}
+
+// `${name^}` and `${name,}`: the value with its first character made upper
+// or lower case, or with `${name^^}` and `${name,,}` every character. A
+// pattern after the operator limits it to the characters it matches.
+fn convert_case(value: &str, pattern: &str, upper: bool, all: bool) -> String {
+	let pattern = if pattern.is_empty() { "?" } else { pattern };
+	let mut converted = String::new();
+	for (n, ch) in value.chars().enumerate() {
+		if (n > 0 && !all) || !glob::is_match(pattern, &ch.to_string()) {
+			converted.push(ch);
+		} else if upper {
+			converted.extend(ch.to_uppercase());
+		} else {
+			converted.extend(ch.to_lowercase());
+		}
+	}
+	converted
+}
+
+// `${#name}`: the number of characters in the value, or of positional
+// parameters for `${#@}` and `${#*}` and of elements for `${#name[@]}`.
+fn length(shell: &mut Shell, reference: &str, name: &str) -> Result<String, String> {
+	let (found, rest) = split_name(name);
+	let (subscript, rest) = split_subscript(rest);
+	if found.is_empty() || !rest.is_empty() {
+		return Err(format!("${{{}}}: bad substitution", reference));
+	}
+	let value = match (found, subscript) {
+		(_, Some("@" | "*")) => return Ok(shell.vars.elements(found).map_or(0, |elements| elements.len()).to_string()),
+		("@" | "*", None) => return Ok(shell.vars.positional().len().to_string()),
+		(_, Some(subscript)) => get_element(shell, found, subscript)?,
+		(_, None) => lookup(shell, found),
+	};
+	match value {
+		Some(value) => Ok(value.chars().count().to_string()),
+		None if shell.options.nounset => fatal(shell, format!("{}: unbound variable", name)),
+		None => Ok("0".to_string()),
+	}
+}
+
+// The element `name[subscript]` stands for. The subscript of an associative
+// array is a word giving the key, and that of any other variable is
+// arithmetic giving the index.
+pub fn get_element(shell: &mut Shell, name: &str, subscript: &str) -> Result<Option<String>, String> {
+	let subscript = utils::expand_word(subscript, shell)?;
+	if shell.vars.is_assoc(name) {
+		return Ok(shell.vars.get_key(name, &subscript));
+	}
+	let index = arith::evaluate(&subscript, &mut shell.vars)?;
+	Ok(shell.vars.get_element(name, index))
+}
+
+// Set the element `name[subscript]` stands for.
+pub fn set_element(shell: &mut Shell, name: &str, subscript: &str, value: &str) -> Result<(), String> {
+	let subscript = utils::expand_word(subscript, shell)?;
+	if shell.vars.is_assoc(name) {
+		return shell.vars.set_key(name, &subscript, value);
+	}
+	let index = arith::evaluate(&subscript, &mut shell.vars)?;
+	shell.vars.set_element(name, index, value)
+}
+
+// Split the `[subscript]` off the front of what follows a name, when there
+// is one.
+fn split_subscript(rest: &str) -> (Option<&str>, &str) {
+	if !rest.starts_with('[') {
+		return (None, rest);
+	}
+	let mut depth = 0;
+	for (idx, ch) in rest.char_indices() {
+		match ch {
+			'[' => depth += 1,
+			']' if depth == 1 => return (Some(&rest[1..idx]), &rest[idx + 1..]),
+			']' => depth -= 1,
+			_ => (),
+		}
+	}
+	(None, rest)
+}
+
+// Split a reference into the parameter it names and whatever follows it.
+fn split_name(reference: &str) -> (&str, &str) {
+	let len = match reference.chars().next() {
+		Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => reference
+			.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+			.unwrap_or(reference.len()),
+		Some(ch) if ch.is_ascii_digit() => reference.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(reference.len()),
+		Some(ch) if "?$!#@*".contains(ch) => 1,
+		_ => 0,
+	};
+	reference.split_at(len)
+}
+
+// The value of a parameter: one of the special ones the shell keeps itself,
+// or else a variable. None when it is not set.
+fn lookup(shell: &Shell, name: &str) -> Option<String> {
+	match name {
+		"?" => Some(shell.last_status.to_string()),
+		"$" => Some(shell.pid.to_string()),
+		"#" => Some(shell.vars.positional().len().to_string()),
+		"!" => shell.last_bg_pid.map(|pid| pid.to_string()),
+		"@" | "*" => {
+			let args = shell.vars.positional();
+			(!args.is_empty()).then(|| args.join(" "))
+		}
+		// the process running the expansion, which differs in a subshell
+		"BASHPID" => Some(std::process::id().to_string()),
+		_ => shell.vars.get(name),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn longest_prefix() {
+		assert_eq!(strip_prefix("abc", "a*", true), "");
+		assert_eq!(strip_prefix("abc", "b*", true), "abc");
+		assert_eq!(strip_prefix("/usr/lib/libc.so", "*/", true), "libc.so");
+		assert_eq!(strip_prefix("/usr/lib/libc.so", "*/", false), "usr/lib/libc.so");
+	}
+
+	#[test]
+	fn shortest_suffix() {
+		assert_eq!(strip_suffix("abc", "b*", false), "a");
+		assert_eq!(strip_suffix("abcbc", "b*", false), "abc");
+		assert_eq!(strip_suffix("abc", "x*", false), "abc");
+		assert_eq!(strip_suffix("notes.txt", ".txt", false), "notes");
+	}
+}
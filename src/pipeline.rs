@@ -0,0 +1,87 @@
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::unistd::{dup2, fork, pipe, setpgid, ForkResult, Pid};
+
+use crate::compound::{self, Command, ControlFlow};
+use crate::jobs;
+use crate::redirect::Redirect;
+use crate::signals;
+use crate::Shell;
+
+// One command of a pipeline with its redirects already taken out.
+pub struct Stage {
+	pub command: String,
+	pub redirects: Vec<Redirect>,
+	// the script line it starts on, for its error messages
+	pub line: Option<usize>,
+}
+
+// Run every stage in its own child with stdout of one stage wired to stdin of
+// the next. The shell waits for all of them and returns the status of each.
+// Under job control the stages share a process group led by the first one.
+pub fn run_pipeline(shell: &mut Shell, stages: &[Command], text: &str) -> Vec<i32> {
+	let job_control = jobs::job_control();
+	let mut pgid: Option<Pid> = None;
+	let mut children: Vec<Pid> = Vec::new();
+	let mut prev_read: Option<OwnedFd> = None;
+
+	for (i, stage) in stages.iter().enumerate() {
+		let next_pipe = if i + 1 < stages.len() {
+			match pipe() {
+				Ok(fds) => Some(fds),
+				Err(err) => {
+					shell_error!("pipe: {}", err);
+					break;
+				}
+			}
+		} else {
+			None
+		};
+
+		// anything still buffered would otherwise be written by the child too
+		io::stdout().flush().ok();
+
+		match unsafe { fork() } {
+			Ok(ForkResult::Child) => {
+				if job_control {
+					jobs::start_job(pgid, true);
+				}
+				// a stage whose reader went away should end quietly, the way an
+				// external command does
+				unsafe { signal(Signal::SIGPIPE, SigHandler::SigDfl) }.ok();
+				signals::reset();
+				if let Some(read_end) = prev_read.take() {
+					dup2(read_end.as_raw_fd(), 0).ok();
+				}
+				if let Some((read_end, write_end)) = next_pipe {
+					dup2(write_end.as_raw_fd(), 1).ok();
+					drop(read_end);
+					drop(write_end);
+				}
+				let status = match compound::run_command(shell, stage) {
+					Ok(status) | Err(ControlFlow::Return(status)) => status,
+					Err(ControlFlow::Break(_) | ControlFlow::Continue(_)) => 0,
+					Err(ControlFlow::Interrupt) => signals::exit_interrupted(),
+				};
+				io::stdout().flush().ok();
+				std::process::exit(status);
+			}
+			Ok(ForkResult::Parent { child }) => {
+				if job_control {
+					setpgid(child, *pgid.get_or_insert(child)).ok();
+				}
+				children.push(child);
+				prev_read = next_pipe.map(|(read_end, _write_end)| read_end);
+			}
+			Err(err) => {
+				shell_error!("fork: {}", err);
+				break;
+			}
+		}
+	}
+	drop(prev_read);
+
+	shell.jobs.wait_foreground(children, text)
+}
@@ -0,0 +1,430 @@
+use std::io::{self, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::variables::{self, Variables};
+
+// `printf [-v var] format [arguments]`: write the arguments as the format
+// lays out. The format is used again for as long as arguments are left, and
+// a conversion without an argument gets an empty string or zero. With `-v`
+// the result goes into the variable instead.
+pub fn printf(vars: &mut Variables, args: &[String]) -> i32 {
+	let (var, args) = match args.first().map(String::as_str) {
+		Some("-v") if args.len() > 1 => (Some(args[1].as_str()), &args[2..]),
+		Some("--") => (None, &args[1..]),
+		_ => (None, args),
+	};
+	let Some((format, args)) = args.split_first() else {
+		eprintln!("printf: usage: printf [-v var] format [arguments]");
+		return 2;
+	};
+	if let Some(var) = var.filter(|var| !variables::is_valid_name(var)) {
+		shell_error!("printf: `{}': not a valid identifier", var);
+		return 2;
+	}
+
+	let mut printer = Printer {
+		args,
+		next: 0,
+		output: Vec::new(),
+		status: 0,
+	};
+	loop {
+		let first = printer.next;
+		// a format that takes no arguments is only used once
+		if !printer.print(format) || printer.next == first || printer.next >= args.len() {
+			break;
+		}
+	}
+
+	match var {
+		Some(var) => {
+			if let Err(err) = vars.set(var, &String::from_utf8_lossy(&printer.output)) {
+				shell_error!("printf: {}", err);
+				return 1;
+			}
+		}
+		None => {
+			let mut stdout = io::stdout();
+			stdout.write_all(&printer.output).ok();
+			stdout.flush().ok();
+		}
+	}
+	printer.status
+}
+
+// What a `%` conversion asks for besides its letter.
+#[derive(Default)]
+struct Spec {
+	flags: String,
+	width: usize,
+	precision: Option<usize>,
+}
+
+impl Spec {
+	// Pad a converted value out to the width, on the right with `-` and
+	// otherwise on the left. With `0` a number is padded with zeros between
+	// its sign and its digits.
+	fn pad(&self, sign: &str, digits: &str, zeros: bool) -> String {
+		let len = sign.chars().count() + digits.chars().count();
+		let fill = self.width.saturating_sub(len);
+		if self.flags.contains('-') {
+			format!("{}{}{}", sign, digits, " ".repeat(fill))
+		} else if zeros && self.flags.contains('0') {
+			format!("{}{}{}", sign, "0".repeat(fill), digits)
+		} else {
+			format!("{}{}{}", " ".repeat(fill), sign, digits)
+		}
+	}
+
+	// The sign in front of a number: `-` when it is negative, and `+` or a
+	// blank when the flags ask for one.
+	fn sign(&self, negative: bool) -> &'static str {
+		if negative {
+			"-"
+		} else if self.flags.contains('+') {
+			"+"
+		} else if self.flags.contains(' ') {
+			" "
+		} else {
+			""
+		}
+	}
+}
+
+struct Printer<'a> {
+	args: &'a [String],
+	// the argument the next conversion takes
+	next: usize,
+	output: Vec<u8>,
+	status: i32,
+}
+
+impl Printer<'_> {
+	// Write the format once. Returns false when a `\c` ends all output.
+	fn print(&mut self, format: &str) -> bool {
+		let mut chars = format.chars().peekable();
+		while let Some(ch) = chars.next() {
+			let go_on = match ch {
+				'\\' => unescape(&mut chars, &mut self.output),
+				'%' => self.convert(&mut chars),
+				_ => {
+					push_char(&mut self.output, ch);
+					true
+				}
+			};
+			if !go_on {
+				return false;
+			}
+		}
+		true
+	}
+
+	fn next_arg(&mut self) -> Option<&str> {
+		let arg = self.args.get(self.next)?;
+		self.next += 1;
+		Some(arg)
+	}
+
+	fn integer_arg(&mut self) -> i64 {
+		let arg = self.next_arg().unwrap_or_default().to_string();
+		parse_integer(&arg).unwrap_or_else(|err| {
+			shell_error!("printf: {}", err);
+			self.status = 1;
+			0
+		})
+	}
+
+	fn float_arg(&mut self) -> f64 {
+		let arg = self.next_arg().unwrap_or_default().to_string();
+		if let Some(ch) = arg.strip_prefix(['\'', '"']).and_then(|rest| rest.chars().next()) {
+			return ch as u32 as f64;
+		}
+		match arg.trim() {
+			"" => 0.0,
+			arg => arg.parse().unwrap_or_else(|_| {
+				shell_error!("printf: {}: invalid number", arg);
+				self.status = 1;
+				0.0
+			}),
+		}
+	}
+
+	// Read a width or precision, either written out or given by the next
+	// argument for a `*`.
+	fn read_number(&mut self, chars: &mut Peekable<Chars>, spec: &mut Spec) -> Option<usize> {
+		if chars.next_if_eq(&'*').is_some() {
+			let number = self.integer_arg();
+			// a negative width from an argument left-justifies
+			if number < 0 {
+				spec.flags.push('-');
+			}
+			return Some(number.unsigned_abs() as usize);
+		}
+		let digits = take_digits(chars, 10, usize::MAX);
+		digits.parse().ok()
+	}
+
+	// Carry out the conversion after a `%`. Returns false when the rest of the
+	// output is to be left out.
+	fn convert(&mut self, chars: &mut Peekable<Chars>) -> bool {
+		let mut spec = Spec::default();
+		while let Some(flag) = chars.next_if(|ch| "-+ #0".contains(*ch)) {
+			spec.flags.push(flag);
+		}
+		spec.width = self.read_number(chars, &mut spec).unwrap_or(0);
+		if chars.next_if_eq(&'.').is_some() {
+			spec.precision = Some(self.read_number(chars, &mut spec).unwrap_or(0));
+		}
+
+		let converted = match chars.next() {
+			None => "%".to_string(),
+			Some('%') => "%".to_string(),
+			Some(conversion @ ('d' | 'i' | 'o' | 'u' | 'x' | 'X')) => {
+				let value = self.integer_arg();
+				format_integer(&spec, conversion, value)
+			}
+			Some(conversion @ ('f' | 'F' | 'e' | 'E' | 'g' | 'G')) => {
+				let value = self.float_arg();
+				format_float(&spec, conversion, value)
+			}
+			Some('s') => {
+				let arg = self.next_arg().unwrap_or_default();
+				let text: String = match spec.precision {
+					Some(precision) => arg.chars().take(precision).collect(),
+					None => arg.to_string(),
+				};
+				spec.pad("", &text, false)
+			}
+			Some('q') => {
+				let arg = self.next_arg().unwrap_or_default();
+				spec.pad("", &quote(arg), false)
+			}
+			Some('c') => {
+				let arg = self.next_arg().unwrap_or_default();
+				let text: String = arg.chars().take(1).collect();
+				spec.pad("", &text, false)
+			}
+			// the argument with its escapes carried out
+			Some('b') => {
+				let arg = self.next_arg().unwrap_or_default().to_string();
+				let mut text = Vec::new();
+				let mut arg_chars = arg.chars().peekable();
+				let mut go_on = true;
+				while let Some(ch) = arg_chars.next() {
+					if ch != '\\' {
+						push_char(&mut text, ch);
+					} else if !unescape(&mut arg_chars, &mut text) {
+						go_on = false;
+						break;
+					}
+				}
+				let text = String::from_utf8_lossy(&text).into_owned();
+				self.output.extend_from_slice(spec.pad("", &text, false).as_bytes());
+				return go_on;
+			}
+			Some(conversion) => {
+				shell_error!("printf: %{}: invalid format character", conversion);
+				self.status = 1;
+				return false;
+			}
+		};
+		self.output.extend_from_slice(converted.as_bytes());
+		true
+	}
+}
+
+// Quote a word for `%q` so the shell reads it back as it is: special
+// characters get a backslash, and a word with control characters in it goes
+// in single quotes, which keep those as they are.
+fn quote(word: &str) -> String {
+	let plain = |ch: char| ch.is_alphanumeric() || "_-./:,+@%=".contains(ch);
+	if word.is_empty() {
+		"''".to_string()
+	} else if word.chars().any(char::is_control) {
+		format!("'{}'", word.replace('\'', "'\\''"))
+	} else {
+		word.chars().fold(String::new(), |mut quoted, ch| {
+			if !plain(ch) {
+				quoted.push('\\');
+			}
+			quoted.push(ch);
+			quoted
+		})
+	}
+}
+
+// Carry out the escape after a backslash, taking what it needs from `chars`.
+// Returns false for `\c`, which ends all output.
+fn unescape(chars: &mut Peekable<Chars>, output: &mut Vec<u8>) -> bool {
+	let Some(ch) = chars.next() else {
+		output.push(b'\\');
+		return true;
+	};
+	let byte = match ch {
+		'n' => b'\n',
+		't' => b'\t',
+		'r' => b'\r',
+		'a' => 0x07,
+		'b' => 0x08,
+		'v' => 0x0b,
+		'f' => 0x0c,
+		'e' | 'E' => 0x1b,
+		'\\' | '"' | '\'' | '?' => ch as u8,
+		'c' => return false,
+		// `\0NNN`, or `\NNN` with up to three digits in all
+		'0'..='7' => {
+			let max = if ch == '0' { 3 } else { 2 };
+			let digits = format!("{}{}", ch, take_digits(chars, 8, max));
+			u32::from_str_radix(&digits, 8).unwrap_or(0) as u8
+		}
+		'x' => match take_digits(chars, 16, 2) {
+			digits if digits.is_empty() => {
+				output.extend_from_slice(b"\\x");
+				return true;
+			}
+			digits => u8::from_str_radix(&digits, 16).unwrap_or(0),
+		},
+		'u' | 'U' => {
+			let digits = take_digits(chars, 16, if ch == 'u' { 4 } else { 8 });
+			match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+				Some(decoded) => push_char(output, decoded),
+				None => {
+					push_char(output, '\\');
+					push_char(output, ch);
+					output.extend_from_slice(digits.as_bytes());
+				}
+			}
+			return true;
+		}
+		_ => {
+			push_char(output, '\\');
+			push_char(output, ch);
+			return true;
+		}
+	};
+	output.push(byte);
+	true
+}
+
+fn push_char(output: &mut Vec<u8>, ch: char) {
+	output.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+// Take up to `max` digits of the radix off the front of `chars`.
+fn take_digits(chars: &mut Peekable<Chars>, radix: u32, max: usize) -> String {
+	let mut digits = String::new();
+	while digits.len() < max {
+		match chars.next_if(|ch| ch.is_digit(radix)) {
+			Some(digit) => digits.push(digit),
+			None => break,
+		}
+	}
+	digits
+}
+
+// Read an integer argument the way C does, in hex after `0x` and in octal
+// after a leading `0`. A leading quote gives the code of the character after
+// it, and an empty argument is zero.
+fn parse_integer(arg: &str) -> Result<i64, String> {
+	if let Some(ch) = arg.strip_prefix(['\'', '"']).and_then(|rest| rest.chars().next()) {
+		return Ok(ch as i64);
+	}
+	let trimmed = arg.trim();
+	if trimmed.is_empty() {
+		return Ok(0);
+	}
+	let (negative, digits) = match trimmed.strip_prefix('-') {
+		Some(digits) => (true, digits),
+		None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+	};
+	let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+		i64::from_str_radix(hex, 16)
+	} else if digits.len() > 1 && digits.starts_with('0') {
+		i64::from_str_radix(&digits[1..], 8)
+	} else {
+		digits.parse()
+	};
+	match value {
+		Ok(value) if negative => Ok(-value),
+		Ok(value) => Ok(value),
+		Err(_) => Err(format!("{}: invalid number", arg)),
+	}
+}
+
+// Convert an integer for `%d`, `%i`, `%o`, `%u`, `%x` or `%X`. The unsigned
+// conversions show a negative number as its two's complement.
+fn format_integer(spec: &Spec, conversion: char, value: i64) -> String {
+	let (negative, mut digits) = match conversion {
+		'o' => (false, format!("{:o}", value as u64)),
+		'u' => (false, (value as u64).to_string()),
+		'x' => (false, format!("{:x}", value as u64)),
+		'X' => (false, format!("{:X}", value as u64)),
+		_ => (value < 0, value.unsigned_abs().to_string()),
+	};
+	// the precision is the least number of digits
+	if let Some(precision) = spec.precision {
+		if precision == 0 && value == 0 {
+			digits.clear();
+		} else if digits.len() < precision {
+			digits = format!("{}{}", "0".repeat(precision - digits.len()), digits);
+		}
+	}
+	let prefix = match conversion {
+		'o' if spec.flags.contains('#') && !digits.starts_with('0') => "0",
+		'x' if spec.flags.contains('#') && value != 0 => "0x",
+		'X' if spec.flags.contains('#') && value != 0 => "0X",
+		'd' | 'i' => spec.sign(negative),
+		_ => "",
+	};
+	spec.pad(prefix, &digits, spec.precision.is_none())
+}
+
+// Convert a number for `%f`, `%e` or `%g`, or their upper case forms.
+fn format_float(spec: &Spec, conversion: char, value: f64) -> String {
+	let precision = spec.precision.unwrap_or(6);
+	let magnitude = value.abs();
+	let digits = if !magnitude.is_finite() {
+		if magnitude.is_nan() { "nan" } else { "inf" }.to_string()
+	} else {
+		match conversion.to_ascii_lowercase() {
+			'f' => format!("{:.*}", precision, magnitude),
+			'e' => exponential(magnitude, precision),
+			_ => general(magnitude, precision, spec.flags.contains('#')),
+		}
+	};
+	let digits = if conversion.is_ascii_uppercase() { digits.to_uppercase() } else { digits };
+	spec.pad(spec.sign(value.is_sign_negative() && !value.is_nan()), &digits, value.is_finite())
+}
+
+// `%e`: one digit before the point and an exponent of at least two digits.
+fn exponential(value: f64, precision: usize) -> String {
+	let formatted = format!("{:.*e}", precision, value);
+	let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+	let exponent: i32 = exponent.parse().unwrap_or(0);
+	let sign = if exponent < 0 { '-' } else { '+' };
+	format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+// `%g`: `%e` for very small or large numbers and `%f` otherwise, with
+// `precision` significant digits and no trailing zeros unless `alt` keeps
+// them.
+fn general(value: f64, precision: usize, alt: bool) -> String {
+	let precision = precision.max(1);
+	let rounded = format!("{:.*e}", precision - 1, value);
+	let exponent: i32 = rounded.split_once('e').and_then(|(_, exponent)| exponent.parse().ok()).unwrap_or(0);
+	let formatted = if exponent < -4 || exponent >= precision as i32 {
+		exponential(value, precision - 1)
+	} else {
+		format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+	};
+	if alt {
+		return formatted;
+	}
+	let (mantissa, exponent) = match formatted.split_once('e') {
+		Some((mantissa, exponent)) => (mantissa, format!("e{}", exponent)),
+		None => (formatted.as_str(), String::new()),
+	};
+	let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+	format!("{}{}", mantissa, exponent)
+}
@@ -0,0 +1,134 @@
+use std::env;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::{close, dup2, fork, mkfifo, pipe, ForkResult, Pid};
+
+use crate::signals::{self, waitpid};
+use crate::utils::{self, QuoteTracker};
+use crate::Shell;
+
+// A running `<(...)` command. The outer command reads its output through
+// `fd`, or through the named pipe at `fifo` where `/dev/fd` is missing.
+pub struct ProcessSubstitution {
+	pid: Pid,
+	fd: Option<OwnedFd>,
+	fifo: Option<PathBuf>,
+}
+
+// Start every `<(...)` in the command and replace each of them with a path
+// the outer command can open to read that command's output. All of them run
+// at the same time.
+pub fn substitute(shell: &mut Shell, command: &str) -> Result<(String, Vec<ProcessSubstitution>), String> {
+	let chars: Vec<char> = command.chars().collect();
+	let mut quotes = QuoteTracker::new();
+	let mut substituted = String::new();
+	let mut substitutions: Vec<ProcessSubstitution> = Vec::new();
+
+	let mut i = 0;
+	while i < chars.len() {
+		let ch = chars[i];
+		if quotes.is_unquoted(ch) && ch == '<' && chars.get(i + 1) == Some(&'(') {
+			let Some(end) = utils::find_closing_paren(&chars, i + 1) else {
+				return Err("syntax error: unexpected end of file".to_string());
+			};
+			let inner: String = chars[i + 2..end].iter().collect();
+			let substitution = spawn(shell, &inner, substitutions.len())?;
+			substituted.push_str(&substitution.path());
+			substitutions.push(substitution);
+			i = end + 1;
+			continue;
+		}
+		substituted.push(ch);
+		i += 1;
+	}
+
+	Ok((substituted, substitutions))
+}
+
+// Close our side of every substitution and wait for the commands to finish.
+pub fn reap(substitutions: Vec<ProcessSubstitution>) {
+	for substitution in substitutions {
+		drop(substitution.fd);
+		if let Some(fifo) = &substitution.fifo {
+			// release a writer still blocked on a fifo nobody opened
+			if let Ok(fd) = open(fifo.as_path(), OFlag::O_RDONLY | OFlag::O_NONBLOCK, Mode::empty()) {
+				close(fd).ok();
+			}
+			std::fs::remove_file(fifo).ok();
+		}
+		waitpid(substitution.pid, None).ok();
+	}
+}
+
+impl ProcessSubstitution {
+	fn path(&self) -> String {
+		match (&self.fd, &self.fifo) {
+			(Some(fd), _) => format!("/dev/fd/{}", fd.as_raw_fd()),
+			(None, Some(fifo)) => fifo.display().to_string(),
+			(None, None) => String::new(),
+		}
+	}
+}
+
+fn spawn(shell: &mut Shell, command: &str, index: usize) -> Result<ProcessSubstitution, String> {
+	if Path::new("/dev/fd").is_dir() {
+		spawn_with_pipe(shell, command)
+	} else {
+		spawn_with_fifo(shell, command, index)
+	}
+}
+
+fn spawn_with_pipe(shell: &mut Shell, command: &str) -> Result<ProcessSubstitution, String> {
+	let (read_end, write_end) = pipe().map_err(|err| format!("pipe: {}", err.desc()))?;
+	io::stdout().flush().ok();
+
+	match unsafe { fork() } {
+		Ok(ForkResult::Child) => {
+			signals::reset();
+			dup2(write_end.as_raw_fd(), 1).ok();
+			drop(read_end);
+			drop(write_end);
+			run_and_exit(shell, command);
+		}
+		Ok(ForkResult::Parent { child }) => Ok(ProcessSubstitution {
+			pid: child,
+			fd: Some(read_end),
+			fifo: None,
+		}),
+		Err(err) => Err(format!("fork: {}", err.desc())),
+	}
+}
+
+fn spawn_with_fifo(shell: &mut Shell, command: &str, index: usize) -> Result<ProcessSubstitution, String> {
+	let fifo = env::temp_dir().join(format!("shell-subst-{}-{}", std::process::id(), index));
+	mkfifo(&fifo, Mode::S_IRUSR | Mode::S_IWUSR)
+		.map_err(|err| format!("{}: {}", fifo.display(), err.desc()))?;
+	io::stdout().flush().ok();
+
+	match unsafe { fork() } {
+		Ok(ForkResult::Child) => {
+			signals::reset();
+			// opening blocks until the outer command opens the other end
+			if let Ok(fd) = open(&fifo, OFlag::O_WRONLY, Mode::empty()) {
+				dup2(fd, 1).ok();
+			}
+			run_and_exit(shell, command);
+		}
+		Ok(ForkResult::Parent { child }) => Ok(ProcessSubstitution {
+			pid: child,
+			fd: None,
+			fifo: Some(fifo),
+		}),
+		Err(err) => Err(format!("fork: {}", err.desc())),
+	}
+}
+
+fn run_and_exit(shell: &mut Shell, command: &str) -> ! {
+	let status = crate::run_line(shell, command);
+	io::stdout().flush().ok();
+	std::process::exit(status);
+}
@@ -0,0 +1,148 @@
+use std::env;
+
+use nix::libc;
+use nix::unistd::{gethostname, geteuid, User};
+
+use crate::pwd_cmd;
+use crate::variables::Variables;
+
+// Expand the backslash escapes of a prompt string like `PS1`: `\u` is the
+// user name, `\h` the host name up to the first dot and `\H` all of it, `\w`
+// the current directory as `$PWD` has it, with the home directory shown as
+// `~`, and `\W` its last part, `\$` a `#` for root and a `$` for anyone else, `\n` a newline
+// and `\t` the time as HH:MM:SS. `\e`, `\a` and `\NNN` in octal stand for
+// those characters. Whatever is between `\[` and `\]`, like the escape
+// sequences that color the prompt, takes up no room on the screen.
+pub fn expand(prompt: &str, vars: &Variables) -> Prompt {
+	let chars: Vec<char> = prompt.chars().collect();
+	let mut expanded = Prompt {
+		text: String::new(),
+		visible: String::new(),
+		hidden: false,
+	};
+
+	let mut i = 0;
+	while i < chars.len() {
+		if chars[i] != '\\' || i + 1 == chars.len() {
+			expanded.push(chars[i]);
+			i += 1;
+			continue;
+		}
+		i += 2;
+		match chars[i - 1] {
+			'u' => expanded.push_str(&user_name()),
+			'h' => expanded.push_str(host_name().split('.').next().unwrap_or_default()),
+			'H' => expanded.push_str(&host_name()),
+			'w' => expanded.push_str(&working_directory(vars)),
+			'W' => {
+				let dir = working_directory(vars);
+				match dir.rsplit_once('/') {
+					Some((_, name)) if !name.is_empty() => expanded.push_str(name),
+					_ => expanded.push_str(&dir),
+				}
+			}
+			'$' => expanded.push(if geteuid().is_root() { '#' } else { '$' }),
+			'n' => expanded.push('\n'),
+			't' => expanded.push_str(&time()),
+			'e' => expanded.push('\x1b'),
+			'a' => expanded.push('\x07'),
+			'\\' => expanded.push('\\'),
+			'[' => expanded.hidden = true,
+			']' => expanded.hidden = false,
+			digit @ '0'..='7' => {
+				let mut code = digit.to_digit(8).unwrap();
+				for _ in 0..2 {
+					match chars.get(i).and_then(|ch| ch.to_digit(8)) {
+						Some(value) => {
+							code = code * 8 + value;
+							i += 1;
+						}
+						None => break,
+					}
+				}
+				expanded.extend(char::from_u32(code));
+			}
+			other => {
+				expanded.push('\\');
+				expanded.push(other);
+			}
+		}
+	}
+	expanded
+}
+
+// An expanded prompt: all of it, and only the part that shows on the screen.
+pub struct Prompt {
+	pub text: String,
+	pub visible: String,
+	// inside `\[` and `\]`
+	hidden: bool,
+}
+
+impl Prompt {
+	fn push(&mut self, ch: char) {
+		self.text.push(ch);
+		if !self.hidden {
+			self.visible.push(ch);
+		}
+	}
+
+	fn push_str(&mut self, text: &str) {
+		text.chars().for_each(|ch| self.push(ch));
+	}
+
+	fn extend(&mut self, ch: Option<char>) {
+		ch.into_iter().for_each(|ch| self.push(ch));
+	}
+}
+
+fn user_name() -> String {
+	match User::from_uid(geteuid()) {
+		Ok(Some(user)) => user.name,
+		_ => env::var("USER").unwrap_or_default(),
+	}
+}
+
+fn host_name() -> String {
+	gethostname().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn working_directory(vars: &Variables) -> String {
+	let dir = pwd_cmd::logical_pwd(vars).display().to_string();
+	match vars.get("HOME") {
+		Some(home) if !home.is_empty() && (dir == home || dir.starts_with(&format!("{}/", home))) => {
+			format!("~{}", &dir[home.len()..])
+		}
+		_ => dir,
+	}
+}
+
+// The local time as HH:MM:SS.
+fn time() -> String {
+	let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+	unsafe {
+		let now = libc::time(std::ptr::null_mut());
+		libc::localtime_r(&now, &mut tm);
+	}
+	format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hidden_sequences_take_no_room() {
+		let cwd = env::current_dir().unwrap();
+		let mut vars = Variables::new();
+		vars.set("PWD", &cwd.to_string_lossy()).unwrap();
+		vars.set("HOME", &cwd.parent().unwrap().to_string_lossy()).unwrap();
+		let name = cwd.file_name().unwrap().to_string_lossy();
+
+		let prompt = expand("\\[\\033[32m\\]\\w\\[\\033[0m\\] $ ", &vars);
+		assert_eq!(prompt.text, format!("\x1b[32m~/{}\x1b[0m $ ", name));
+		assert_eq!(prompt.visible, format!("~/{} $ ", name));
+		// the cursor ends up just past the visible part
+		assert_eq!(prompt.visible.chars().count(), name.chars().count() + 5);
+	}
+}
@@ -1,6 +1,79 @@
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::variables::Variables;
+
+// Print the current directory: by default, or with `-L`, as it was reached
+// with any symbolic links on the way kept, and with `-P` with them resolved.
+// The last of the two flags wins.
+pub fn pwd(vars: &Variables, args: &[String]) -> i32 {
+	let mut physical = false;
+	for arg in args {
+		if !arg.starts_with('-') || arg == "-" {
+			break;
+		}
+		for flag in arg.chars().skip(1) {
+			match flag {
+				'L' => physical = false,
+				'P' => physical = true,
+				_ => {
+					shell_error!("pwd: -{}: invalid option", flag);
+					eprintln!("pwd: usage: pwd [-LP]");
+					return 2;
+				}
+			}
+		}
+	}
+	let cwd = if physical { get_pwd() } else { logical_pwd(vars) };
+	println!("{}", cwd.display());
+	0
+}
 
 pub fn get_pwd() -> PathBuf {
-	env::current_dir().unwrap()
-}
\ No newline at end of file
+	env::current_dir().unwrap_or_default()
+}
+
+// `$PWD` when it is an absolute path without `.` or `..` that names the
+// current directory, and the physical path otherwise.
+pub fn logical_pwd(vars: &Variables) -> PathBuf {
+	match vars.get("PWD") {
+		Some(pwd) if is_logical(Path::new(&pwd)) => PathBuf::from(pwd),
+		_ => get_pwd(),
+	}
+}
+
+// Make `$PWD` name the current directory, keeping the one the shell was
+// started with when it does.
+pub fn init(vars: &mut Variables) {
+	let pwd = logical_pwd(vars);
+	vars.set("PWD", &pwd.to_string_lossy()).ok();
+	vars.export("PWD");
+}
+
+// Where `path` leads from the logical directory `from`, worked out from the
+// names alone: `.` is dropped and `..` takes off the name before it.
+pub fn join_logical(from: &Path, path: &Path) -> PathBuf {
+	let mut joined = PathBuf::from("/");
+	for component in from.join(path).components() {
+		match component {
+			Component::ParentDir => {
+				joined.pop();
+			}
+			Component::Normal(name) => joined.push(name),
+			_ => (),
+		}
+	}
+	joined
+}
+
+fn is_logical(path: &Path) -> bool {
+	if !path.is_absolute() || path.components().any(|c| matches!(c, Component::CurDir | Component::ParentDir)) {
+		return false;
+	}
+	match (fs::metadata(path), fs::metadata(".")) {
+		(Ok(dir), Ok(cwd)) => dir.dev() == cwd.dev() && dir.ino() == cwd.ino(),
+		_ => false,
+	}
+}
@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::os::fd::{AsFd, RawFd};
+use std::time::{Duration, Instant};
+
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices};
+use nix::unistd;
+
+use crate::variables::{self, Variables};
+
+const USAGE: &str = "read: usage: read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [name ...]";
+
+const MAPFILE_USAGE: &str = "mapfile: usage: mapfile [-t] [-d delim] [-n count] [-O origin] [-s count] [-u fd] [array]";
+
+// Status of a `read` that timed out, as if ended by SIGALRM.
+const TIMED_OUT: i32 = 128 + 14;
+
+// How a line is to be read.
+struct Options {
+	// `-r`: backslashes are kept as they are
+	raw: bool,
+	// `-s`: what is typed is not echoed
+	silent: bool,
+	prompt: Option<String>,
+	timeout: Option<Duration>,
+	// `-n`: stop after this many characters
+	count: Option<usize>,
+	// `-a`: put the fields in this array
+	array: Option<String>,
+	delimiter: u8,
+}
+
+// `read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout]
+// [name...]`: read a line from stdin and split it at the characters of `IFS`
+// into the names in turn, the last of them getting the rest of the line.
+// Without names the whole line goes into `REPLY`. Unless `-r` is given a
+// backslash keeps the character after it from splitting the line, and at the
+// end of a line joins it to the next. Fails at the end of input and when a
+// timeout runs out. An `IFS` in `env`, as in `IFS=: read`, is used in place of
+// the variable.
+pub fn read(vars: &mut Variables, args: &[String], env: &HashMap<String, String>) -> i32 {
+	let Some((options, names)) = parse_options(args) else {
+		return 2;
+	};
+	if let Some(name) = names.iter().chain(&options.array).find(|name| !variables::is_valid_name(name)) {
+		shell_error!("read: `{}': not a valid identifier", name);
+		return 1;
+	}
+
+	// `-t 0` only asks whether there is input to read
+	if options.timeout == Some(Duration::ZERO) {
+		return if wait_for_input(Duration::ZERO) { 0 } else { 1 };
+	}
+
+	let stdin = io::stdin();
+	let on_terminal = stdin.is_terminal();
+	if let Some(prompt) = options.prompt.as_ref().filter(|_| on_terminal) {
+		io::stdout().flush().ok();
+		eprint!("{}", prompt);
+	}
+	// characters are taken as typed for `-n` and hidden for `-s`
+	let saved = tcgetattr(stdin.as_fd()).ok().filter(|_| on_terminal);
+	if let Some(saved) = &saved {
+		let mut termios = saved.clone();
+		if options.silent {
+			termios.local_flags.remove(LocalFlags::ECHO);
+		}
+		if options.count.is_some() {
+			termios.local_flags.remove(LocalFlags::ICANON);
+			termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+			termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+		}
+		tcsetattr(stdin.as_fd(), SetArg::TCSANOW, &termios).ok();
+	}
+	let (line, mut status) = read_line(&options);
+	if let Some(saved) = &saved {
+		tcsetattr(stdin.as_fd(), SetArg::TCSANOW, saved).ok();
+	}
+
+	let chars = unescape(&line, options.raw);
+	let ifs = env.get("IFS").cloned().or_else(|| vars.get("IFS")).unwrap_or(" \t\n".to_string());
+	let assigned = match (&options.array, names) {
+		(Some(array), _) => vars.set_array(array, &split_line(&chars, &ifs, usize::MAX)),
+		(None, []) => vars.set("REPLY", &chars.iter().map(|&(ch, _)| ch).collect::<String>()),
+		(None, names) => {
+			let mut fields = split_line(&chars, &ifs, names.len()).into_iter();
+			names.iter().try_for_each(|name| vars.set(name, &fields.next().unwrap_or_default()))
+		}
+	};
+	if let Err(err) = assigned {
+		shell_error!("read: {}", err);
+		status = 1;
+	}
+	status
+}
+
+// `mapfile [-t] [-d delim] [-n count] [-O origin] [-s count] [-u fd]
+// [array]`, also run as `readarray`: read lines from stdin, or the file
+// descriptor `-u` names, into the elements of an array, `MAPFILE` by default.
+// Each line keeps its newline unless `-t` is given. `-s` skips lines first and
+// `-n` stops after that many, with 0 for all of them. The array is emptied
+// first, unless `-O` gives the index to start from, in which case the
+// elements before it are kept.
+pub fn mapfile(vars: &mut Variables, args: &[String]) -> i32 {
+	let (mut trim, mut delimiter, mut count, mut origin, mut skip, mut fd) = (false, b'\n', 0, None, 0, 0);
+	let mut names = args;
+	while let Some(arg) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		names = &names[1..];
+		if arg == "--" {
+			break;
+		}
+		let mut flags = arg.chars().skip(1);
+		while let Some(flag) = flags.next() {
+			if flag == 't' {
+				trim = true;
+				continue;
+			}
+			if !"dnOsu".contains(flag) {
+				shell_error!("mapfile: -{}: invalid option", flag);
+				eprintln!("{}", MAPFILE_USAGE);
+				return 2;
+			}
+			let rest: String = flags.by_ref().collect();
+			let value = if !rest.is_empty() {
+				rest
+			} else if let Some((value, rest)) = names.split_first() {
+				names = rest;
+				value.clone()
+			} else {
+				shell_error!("mapfile: -{}: option requires an argument", flag);
+				eprintln!("{}", MAPFILE_USAGE);
+				return 2;
+			};
+			if flag == 'd' {
+				delimiter = value.bytes().next().unwrap_or(0);
+				continue;
+			}
+			let Ok(number) = value.parse::<usize>() else {
+				match flag {
+					'u' => shell_error!("mapfile: {}: invalid file descriptor specification", value),
+					'O' => shell_error!("mapfile: {}: invalid array origin", value),
+					_ => shell_error!("mapfile: {}: invalid line count", value),
+				}
+				return 1;
+			};
+			match flag {
+				'n' => count = number,
+				'O' => origin = Some(number),
+				's' => skip = number,
+				_ => fd = number as RawFd,
+			}
+		}
+	}
+	let name = names.first().map_or("MAPFILE", String::as_str);
+	if !variables::is_valid_name(name) {
+		shell_error!("mapfile: `{}': not a valid identifier", name);
+		return 1;
+	}
+
+	if origin.is_none() {
+		if let Err(err) = vars.set_array(name, &[]) {
+			shell_error!("mapfile: {}", err);
+			return 1;
+		}
+	}
+	let mut index = origin.unwrap_or(0) as i64;
+	let mut read = 0;
+	while count == 0 || read < count {
+		let mut line = match read_record(fd, delimiter) {
+			Ok(Some(line)) => line,
+			Ok(None) => break,
+			Err(err) => {
+				shell_error!("mapfile: {}: invalid file descriptor: {}", fd, err.desc());
+				return 1;
+			}
+		};
+		if skip > 0 {
+			skip -= 1;
+			continue;
+		}
+		if trim && line.last() == Some(&delimiter) {
+			line.pop();
+		}
+		if let Err(err) = vars.set_element(name, index, &String::from_utf8_lossy(&line)) {
+			shell_error!("mapfile: {}", err);
+			return 1;
+		}
+		index += 1;
+		read += 1;
+	}
+	0
+}
+
+// Read from `fd` up to and including the next `delimiter`, a byte at a time so
+// nothing after it is taken. None at the end of input.
+fn read_record(fd: RawFd, delimiter: u8) -> nix::Result<Option<Vec<u8>>> {
+	let mut record: Vec<u8> = Vec::new();
+	loop {
+		let mut byte = [0];
+		match unistd::read(fd, &mut byte) {
+			Ok(1) => (),
+			Ok(_) => return Ok(Some(record).filter(|record| !record.is_empty())),
+			Err(nix::errno::Errno::EINTR) => continue,
+			Err(err) => return Err(err),
+		}
+		record.push(byte[0]);
+		if byte[0] == delimiter {
+			return Ok(Some(record));
+		}
+	}
+}
+
+fn parse_options(args: &[String]) -> Option<(Options, &[String])> {
+	let mut options = Options {
+		raw: false,
+		silent: false,
+		prompt: None,
+		timeout: None,
+		count: None,
+		array: None,
+		delimiter: b'\n',
+	};
+	let mut names = args;
+	while let Some(arg) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		names = &names[1..];
+		if arg == "--" {
+			break;
+		}
+		let mut flags = arg.chars().skip(1);
+		while let Some(flag) = flags.next() {
+			match flag {
+				'r' => options.raw = true,
+				's' => options.silent = true,
+				'p' | 't' | 'n' | 'a' | 'd' => {
+					// the value follows the flag or is the next argument
+					let rest: String = flags.by_ref().collect();
+					let value = if !rest.is_empty() {
+						rest
+					} else if let Some((value, rest)) = names.split_first() {
+						names = rest;
+						value.clone()
+					} else {
+						shell_error!("read: -{}: option requires an argument", flag);
+						eprintln!("{}", USAGE);
+						return None;
+					};
+					match flag {
+						'p' => options.prompt = Some(value),
+						't' => match value.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()) {
+							Some(timeout) => options.timeout = Some(timeout),
+							None => {
+								shell_error!("read: {}: invalid timeout specification", value);
+								return None;
+							}
+						},
+						'n' => match value.parse() {
+							Ok(count) => options.count = Some(count),
+							Err(_) => {
+								shell_error!("read: {}: invalid number", value);
+								return None;
+							}
+						},
+						'a' => options.array = Some(value),
+						// an empty delimiter reads up to a NUL
+						_ => options.delimiter = value.bytes().next().unwrap_or(0),
+					}
+				}
+				_ => {
+					shell_error!("read: -{}: invalid option", flag);
+					eprintln!("{}", USAGE);
+					return None;
+				}
+			}
+		}
+	}
+	Some((options, names))
+}
+
+// Read up to the delimiter, or as many characters as `-n` asks for, one byte
+// at a time so nothing after it is taken from stdin. Returns the text without
+// the delimiter and the status of the read.
+fn read_line(options: &Options) -> (String, i32) {
+	let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+	let mut bytes: Vec<u8> = Vec::new();
+	let mut chars = 0;
+	let status = loop {
+		if options.count.is_some_and(|count| chars >= count) {
+			break 0;
+		}
+		if let Some(deadline) = deadline {
+			// input that never runs out still has to stop at the deadline
+			let now = Instant::now();
+			if now >= deadline || !wait_for_input(deadline - now) {
+				break TIMED_OUT;
+			}
+		}
+		let mut byte = [0];
+		match unistd::read(0, &mut byte) {
+			Ok(1) => (),
+			Err(nix::errno::Errno::EINTR) => continue,
+			_ => break 1,
+		}
+		if byte[0] == options.delimiter {
+			// a backslash at the end of a line joins the next one on
+			let backslashes = bytes.iter().rev().take_while(|&&byte| byte == b'\\').count();
+			if options.raw || options.delimiter != b'\n' || backslashes % 2 == 0 {
+				break 0;
+			}
+			bytes.pop();
+			continue;
+		}
+		// only the first byte of a character counts towards `-n`
+		if byte[0] & 0xc0 != 0x80 {
+			chars += 1;
+		}
+		bytes.push(byte[0]);
+	};
+	(String::from_utf8_lossy(&bytes).into_owned(), status)
+}
+
+// Wait until stdin has input to read, for no longer than `timeout`.
+fn wait_for_input(timeout: Duration) -> bool {
+	let stdin = io::stdin();
+	let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+	let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+	matches!(poll(&mut fds, timeout), Ok(ready) if ready > 0)
+}
+
+// Take the backslashes out of a line unless it is read raw, marking each
+// character that one escaped so it does not split the line.
+fn unescape(line: &str, raw: bool) -> Vec<(char, bool)> {
+	if raw {
+		return line.chars().map(|ch| (ch, false)).collect();
+	}
+	let mut chars: Vec<(char, bool)> = Vec::new();
+	let mut line = line.chars();
+	while let Some(ch) = line.next() {
+		match ch {
+			'\\' => chars.extend(line.next().map(|ch| (ch, true))),
+			_ => chars.push((ch, false)),
+		}
+	}
+	chars
+}
+
+// Split a line into at most `count` fields at the characters of `ifs`. Blanks
+// in `ifs` around a field are dropped and any other of its characters ends a
+// field on its own. The last field takes the rest of the line, less the
+// blanks at its end.
+fn split_line(chars: &[(char, bool)], ifs: &str, count: usize) -> Vec<String> {
+	let is_separator = |&(ch, escaped): &(char, bool)| !escaped && ifs.contains(ch);
+	let is_blank = |item: &(char, bool)| is_separator(item) && matches!(item.0, ' ' | '\t' | '\n');
+	let mut fields: Vec<String> = Vec::new();
+	let mut rest = chars;
+	let skip_blanks = |rest: &mut &[(char, bool)]| {
+		let blanks = rest.iter().take_while(|item| is_blank(item)).count();
+		*rest = &rest[blanks..];
+	};
+	skip_blanks(&mut rest);
+	while !rest.is_empty() {
+		if fields.len() + 1 == count {
+			let blanks = rest.iter().rev().take_while(|item| is_blank(item)).count();
+			fields.push(rest[..rest.len() - blanks].iter().map(|&(ch, _)| ch).collect());
+			break;
+		}
+		let len = rest.iter().take_while(|item| !is_separator(item)).count();
+		fields.push(rest[..len].iter().map(|&(ch, _)| ch).collect());
+		rest = &rest[len..];
+		// the separator after the field, with the blanks around it
+		skip_blanks(&mut rest);
+		if rest.first().is_some_and(|item| is_separator(item) && !is_blank(item)) {
+			rest = &rest[1..];
+			skip_blanks(&mut rest);
+		}
+	}
+	fields
+}
@@ -0,0 +1,404 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{IntoRawFd, RawFd};
+use std::thread;
+
+use nix::fcntl::{fcntl, open, FcntlArg, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::{close, dup2, pipe2, sysconf, SysconfVar};
+
+use crate::arith;
+use crate::expand;
+use crate::param_expand;
+use crate::utils::{self, QuoteTracker};
+use crate::variables;
+use crate::Shell;
+
+#[derive(Clone)]
+pub enum RedirectKind {
+	Truncate,
+	Append,
+	Input,
+	// `&>` and `&>>`: one file shared by stdout and stderr
+	TruncateBoth,
+	AppendBoth,
+	// `N>&M` and `N<&M`: make N a copy of the already open descriptor M
+	Duplicate(RawFd),
+	// `<<DELIM` and `<<-DELIM`: the body is filled in by the REPL from the lines
+	// that follow the command and expanded when the command runs
+	Heredoc {
+		strip_tabs: bool,
+		expand: bool,
+		body: String,
+	},
+	// `<<<word`: the word plus a newline becomes stdin
+	HereString,
+}
+
+#[derive(Clone)]
+pub struct Redirect {
+	pub fd: RawFd,
+	pub kind: RedirectKind,
+	pub target: String,
+}
+
+// Holds copies of the descriptors a redirect replaced and puts them back when
+// dropped, so a builtin only sees the redirect for the duration of its run.
+pub struct RedirectGuard {
+	saved: Vec<(RawFd, Option<RawFd>)>,
+}
+
+impl RedirectGuard {
+	// Point `fd` at `source`, keeping a copy of the original out of reach of
+	// child processes so it can be put back later.
+	fn replace(&mut self, fd: RawFd, source: RawFd) -> nix::Result<()> {
+		let saved = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(10)).ok();
+		self.saved.push((fd, saved));
+		dup2(source, fd).map(|_| ())
+	}
+}
+
+impl Drop for RedirectGuard {
+	fn drop(&mut self) {
+		io::stdout().flush().ok();
+		io::stderr().flush().ok();
+		for (fd, saved) in self.saved.drain(..).rev() {
+			match saved {
+				Some(saved) => {
+					dup2(saved, fd).ok();
+					close(saved).ok();
+				}
+				None => {
+					close(fd).ok();
+				}
+			}
+		}
+	}
+}
+
+// Strip every unquoted redirect operator and its target out of a command,
+// returning the remaining command text alongside the redirects in order.
+pub fn extract(command: &str) -> Result<(String, Vec<Redirect>), String> {
+	let chars: Vec<char> = command.chars().collect();
+	let mut quotes = QuoteTracker::new();
+	let mut remaining = String::new();
+	let mut redirects: Vec<Redirect> = Vec::new();
+
+	// start of the current word in `remaining` and whether it is a bare number,
+	// which makes it the descriptor of a redirect that follows directly
+	let mut word_start = 0;
+	let mut word_is_number = true;
+
+	let mut i = 0;
+	while i < chars.len() {
+		let ch = chars[i];
+		let is_unquoted = quotes.is_unquoted(ch);
+		if is_unquoted && ch == '&' && chars.get(i + 1) == Some(&'>') {
+			let (kind, op_len) = match chars.get(i + 2) {
+				Some('>') => (RedirectKind::AppendBoth, 3),
+				_ => (RedirectKind::TruncateBoth, 2),
+			};
+			let (target, next) = read_target(&chars, i + op_len)?;
+			redirects.push(Redirect { fd: 1, kind, target });
+			remaining.push(' ');
+			word_start = remaining.len();
+			word_is_number = true;
+			i = next;
+			continue;
+		}
+		// `<(` starts a process substitution, which stays part of the command
+		let is_substitution = ch == '<' && chars.get(i + 1) == Some(&'(');
+		if is_unquoted && (ch == '>' || ch == '<') && !is_substitution {
+			let next_ch = chars.get(i + 1).copied();
+			let third_ch = chars.get(i + 2).copied();
+			let op_len = match (ch, next_ch, third_ch) {
+				('<', Some('<'), Some('<' | '-')) => 3,
+				('>', Some('>'), _) | ('<', Some('<'), _) | (_, Some('&'), _) => 2,
+				_ => 1,
+			};
+			let mut fd = if ch == '>' { 1 } else { 0 };
+			if word_is_number && remaining.len() > word_start {
+				fd = parse_fd(&remaining[word_start..])?;
+				remaining.truncate(word_start);
+			}
+			let (mut target, next) = read_target(&chars, i + op_len)?;
+			let kind = match (ch, next_ch, third_ch) {
+				(_, Some('&'), _) => {
+					RedirectKind::Duplicate(parse_fd(&utils::remove_quotes(&target))?)
+				}
+				('>', Some('>'), _) => RedirectKind::Append,
+				('>', _, _) => RedirectKind::Truncate,
+				('<', Some('<'), Some('<')) => RedirectKind::HereString,
+				('<', Some('<'), _) => {
+					// any quoting in the delimiter keeps the body literal
+					let expand = !target.contains(['\'', '"', '\\']);
+					target = utils::remove_quotes(&target);
+					RedirectKind::Heredoc {
+						strip_tabs: op_len == 3,
+						expand,
+						body: String::new(),
+					}
+				}
+				_ => RedirectKind::Input,
+			};
+			redirects.push(Redirect { fd, kind, target });
+			remaining.push(' ');
+			word_start = remaining.len();
+			word_is_number = true;
+			i = next;
+			continue;
+		}
+
+		remaining.push(ch);
+		if is_unquoted && ch.is_whitespace() {
+			word_start = remaining.len();
+			word_is_number = true;
+		} else if !(is_unquoted && ch.is_ascii_digit()) {
+			word_is_number = false;
+		}
+		i += 1;
+	}
+
+	Ok((remaining, redirects))
+}
+
+// Parse a descriptor number, rejecting anything the process could never
+// have open.
+fn parse_fd(word: &str) -> Result<RawFd, String> {
+	let open_max = sysconf(SysconfVar::OPEN_MAX)
+		.ok()
+		.flatten()
+		.unwrap_or(RawFd::MAX as i64);
+	match word.parse::<i64>() {
+		Ok(fd) if fd >= 0 && fd < open_max => Ok(fd as RawFd),
+		_ => Err(format!("{}: bad file descriptor", word)),
+	}
+}
+
+// Read the raw word following a redirect operator, skipping leading blanks.
+// The word ends at the first unquoted blank or redirect operator.
+fn read_target(chars: &[char], start: usize) -> Result<(String, usize), String> {
+	let mut i = start;
+	while i < chars.len() && chars[i].is_whitespace() {
+		i += 1;
+	}
+
+	let mut quotes = QuoteTracker::new();
+	let mut word = String::new();
+	while i < chars.len() {
+		let ch = chars[i];
+		let is_operator = ch == '>' || (ch == '<' && chars.get(i + 1) != Some(&'('));
+		if quotes.is_unquoted(ch) && (ch.is_whitespace() || is_operator) {
+			break;
+		}
+		word.push(ch);
+		i += 1;
+	}
+
+	if word.is_empty() {
+		return match chars.get(i) {
+			Some(ch) => Err(format!("syntax error near unexpected token `{}'", ch)),
+			None => Err("syntax error near unexpected token `newline'".to_string()),
+		};
+	}
+	Ok((word, i))
+}
+
+// Expand variables, arithmetic and command substitutions in a heredoc body. A
+// backslash only escapes `$`, `` ` ``, `\` and a newline, as it does inside
+// double quotes.
+pub fn expand_heredoc(body: &str, shell: &mut Shell) -> Result<String, String> {
+	let chars: Vec<char> = body.chars().collect();
+	let mut expanded = String::new();
+
+	let mut i = 0;
+	while i < chars.len() {
+		match (chars[i], chars.get(i + 1)) {
+			('\\', Some('$' | '`' | '\\')) => {
+				expanded.push(chars[i + 1]);
+				i += 2;
+			}
+			('\\', Some('\n')) => i += 2,
+			('$', Some('(')) if utils::find_arith_end(&chars, i + 1).is_some() => {
+				let end = utils::find_arith_end(&chars, i + 1).unwrap_or_default();
+				let expr: String = chars[i + 3..end - 1].iter().collect();
+				let expr = utils::expand_word(&expr, shell)?;
+				expanded.push_str(&arith::evaluate(&expr, &mut shell.vars)?.to_string());
+				i = end + 1;
+			}
+			('$', Some('(')) => match utils::find_closing_paren(&chars, i + 1) {
+				Some(end) => {
+					let command: String = chars[i + 2..end].iter().collect();
+					expanded.push_str(&expand::substitute(shell, &command));
+					i = end + 1;
+				}
+				None => {
+					expanded.push('$');
+					i += 1;
+				}
+			},
+			('`', _) => match utils::find_closing_backtick(&chars, i) {
+				Some(end) => {
+					let command: String = chars[i + 1..end].iter().collect();
+					expanded.push_str(&expand::backtick_substitution(shell, &command));
+					i = end + 1;
+				}
+				None => {
+					expanded.push('`');
+					i += 1;
+				}
+			},
+			('$', _) => match variables::read_reference(&chars, i) {
+				Some((name, end)) => {
+					let value = param_expand::expand(shell, &name)?;
+					expanded.push_str(&value);
+					i = end;
+				}
+				None => {
+					expanded.push('$');
+					i += 1;
+				}
+			},
+			(ch, _) => {
+				expanded.push(ch);
+				i += 1;
+			}
+		}
+	}
+
+	Ok(expanded)
+}
+
+// Point each redirected descriptor at its target. The returned guard restores
+// the original descriptors once it goes out of scope.
+pub fn apply(redirects: &[Redirect]) -> Result<RedirectGuard, String> {
+	io::stdout().flush().ok();
+	io::stderr().flush().ok();
+
+	let mut guard = RedirectGuard { saved: Vec::new() };
+	for redirect in redirects {
+		if let RedirectKind::Duplicate(source) = redirect.kind {
+			guard
+				.replace(redirect.fd, source)
+				.map_err(|err| format!("{}: {}", source, err.desc()))?;
+			continue;
+		}
+		if let RedirectKind::Heredoc { body, .. } = &redirect.kind {
+			feed_text(&mut guard, redirect.fd, body.clone())?;
+			continue;
+		}
+		if let RedirectKind::HereString = redirect.kind {
+			feed_text(&mut guard, redirect.fd, format!("{}\n", redirect.target))?;
+			continue;
+		}
+
+		let flags = match redirect.kind {
+			RedirectKind::Truncate | RedirectKind::TruncateBoth => {
+				OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC
+			}
+			RedirectKind::Append | RedirectKind::AppendBoth => {
+				OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_APPEND
+			}
+			_ => OFlag::O_RDONLY,
+		};
+		let file = open(redirect.target.as_str(), flags | OFlag::O_CLOEXEC, Mode::from_bits_truncate(0o644))
+			.and_then(|file| move_above(file, redirect.fd))
+			.map_err(|err| format!("{}: {}", redirect.target, err.desc()))?;
+
+		let mut result = guard.replace(redirect.fd, file);
+		if let RedirectKind::TruncateBoth | RedirectKind::AppendBoth = redirect.kind {
+			result = result.and_then(|_| guard.replace(2, file));
+		}
+		close(file).ok();
+		result.map_err(|err| format!("{}: {}", redirect.target, err.desc()))?;
+	}
+
+	Ok(guard)
+}
+
+// Make `fd` the read end of a pipe that delivers `text`.
+fn feed_text(guard: &mut RedirectGuard, fd: RawFd, text: String) -> Result<(), String> {
+	let (read_end, write_end) =
+		pipe2(OFlag::O_CLOEXEC).map_err(|err| format!("pipe: {}", err.desc()))?;
+	let read_end = move_above(read_end.into_raw_fd(), fd).map_err(|err| format!("pipe: {}", err.desc()))?;
+	// a text larger than the pipe buffer would block until the command reads
+	// it, so it is written from a separate thread
+	thread::spawn(move || {
+		File::from(write_end).write_all(text.as_bytes()).ok();
+	});
+	let result = guard.replace(fd, read_end);
+	close(read_end).ok();
+	result.map_err(|err| format!("{}: {}", fd, err.desc()))
+}
+
+// Move a newly opened descriptor above both the ones scripts use and `target`,
+// closing the original. Opened as the lowest free descriptor, it could
+// otherwise be the very one it is about to be copied onto, as for `3<file`.
+fn move_above(fd: RawFd, target: RawFd) -> nix::Result<RawFd> {
+	let moved = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(target.max(9) + 1));
+	close(fd).ok();
+	moved
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use std::fs;
+	use std::process::{self, Command};
+
+	use nix::sys::wait::{waitpid, WaitStatus};
+	use nix::unistd::{fork, ForkResult};
+
+	use super::*;
+
+	// A file name of its own for every test, so they can run side by side.
+	fn temp_path(name: &str) -> String {
+		format!("{}/shell-redirect-{}-{}", env::temp_dir().display(), process::id(), name)
+	}
+
+	// Run `script` with `sh` in a child that has the redirects of `command`
+	// applied, the way the shell starts an external command, so the test
+	// process keeps its own descriptors.
+	fn run_redirected(command: &str, script: &str) {
+		let (_, redirects) = extract(command).unwrap();
+		match unsafe { fork() }.unwrap() {
+			ForkResult::Child => {
+				let status = apply(&redirects)
+					.ok()
+					.and_then(|_guard| Command::new("sh").args(["-c", script]).status().ok());
+				let code = if status.is_some_and(|status| status.success()) { 0 } else { 1 };
+				unsafe { nix::libc::_exit(code) };
+			}
+			ForkResult::Parent { child } => {
+				assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+			}
+		}
+	}
+
+	#[test]
+	fn append_keeps_what_is_there() {
+		let path = temp_path("append");
+		fs::remove_file(&path).ok();
+		run_redirected(&format!("echo a >> {}", path), "echo a");
+		run_redirected(&format!("echo b >> {}", path), "echo b");
+		assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+		fs::remove_file(&path).ok();
+	}
+
+	#[test]
+	fn append_creates_a_missing_file() {
+		let path = temp_path("create");
+		fs::remove_file(&path).ok();
+		run_redirected(&format!("echo a >> {}", path), "echo a");
+		assert_eq!(fs::read_to_string(&path).unwrap(), "a\n");
+		fs::remove_file(&path).ok();
+	}
+
+	#[test]
+	fn both_streams_go_to_one_file() {
+		let path = temp_path("both");
+		run_redirected(&format!("cmd &> {}", path), "echo out; echo err >&2");
+		assert_eq!(fs::read_to_string(&path).unwrap(), "out\nerr\n");
+		fs::remove_file(&path).ok();
+	}
+}
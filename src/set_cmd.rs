@@ -0,0 +1,130 @@
+use crate::declare_cmd;
+use crate::options::ShellOptions;
+use crate::variables::{ShellValue, Variables};
+
+// `-x` turns a single-letter option on and `+x` off, as `-o name` and
+// `+o name` do with the option's name. The arguments after `--`, or from the
+// first one that is not an option, become the positional parameters. With no
+// arguments every variable is listed, arrays with their elements, and a lone
+// `-o` or `+o` lists the options.
+pub fn set(options: &mut ShellOptions, vars: &mut Variables, args: &[String]) -> i32 {
+	if args.is_empty() {
+		for name in vars.names() {
+			match vars.value(&name) {
+				Some(array @ (ShellValue::IndexedArray(_) | ShellValue::AssocArray(_))) => {
+					println!("{}={}", name, declare_cmd::format_value(array));
+				}
+				_ => {
+					let value = vars.get(&name).unwrap_or_default();
+					println!("{}='{}'", name, value.replace('\'', "'\\''"));
+				}
+			}
+		}
+		return 0;
+	}
+
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		let on = match arg.chars().next() {
+			_ if arg == "--" => {
+				vars.replace_positional(args.cloned().collect());
+				return 0;
+			}
+			Some('-') => true,
+			Some('+') => false,
+			_ => {
+				vars.replace_positional(std::iter::once(arg).chain(args).cloned().collect());
+				return 0;
+			}
+		};
+		let flags = &arg[1..];
+		let result = if flags == "o" {
+			match args.next() {
+				Some(name) => options.set(name, on),
+				None => {
+					list_options(options, on);
+					Ok(())
+				}
+			}
+		} else {
+			flags.chars().try_for_each(|flag| match ShellOptions::name_of(flag) {
+				Some(name) => options.set(name, on),
+				None => Err(format!("{}{}: invalid option", &arg[..1], flag)),
+			})
+		};
+		if let Err(err) = result {
+			shell_error!("set: {}", err);
+			return 2;
+		}
+	}
+	0
+}
+
+// `shopt [-qsu] [name...]`: `-s` turns the named options on and `-u` off.
+// Without either each one is shown as on or off, and the status is 1 when any
+// of them is off; `-q` only gives the status. Without names every option is
+// shown, or with `-s` or `-u` only those that are on or off.
+pub fn shopt(options: &mut ShellOptions, args: &[String]) -> i32 {
+	let (mut on, mut quiet) = (None, false);
+	let mut names = args;
+	while let Some(arg) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		names = &names[1..];
+		if arg == "--" {
+			break;
+		}
+		for flag in arg.chars().skip(1) {
+			match flag {
+				's' => on = Some(true),
+				'u' => on = Some(false),
+				'q' => quiet = true,
+				_ => {
+					shell_error!("shopt: -{}: invalid option", flag);
+					eprintln!("shopt: usage: shopt [-qsu] [optname ...]");
+					return 2;
+				}
+			}
+		}
+	}
+
+	if names.is_empty() {
+		for (name, value) in options.shopt_list() {
+			if on != Some(!value) && !quiet {
+				println!("{:<15}\t{}", name, if value { "on" } else { "off" });
+			}
+		}
+		return 0;
+	}
+	let mut status = 0;
+	for name in names {
+		let result = match on {
+			Some(on) => options.set_shopt(name, on),
+			None => match options.shopt_list().into_iter().find(|&(option, _)| option == name) {
+				Some((_, value)) => {
+					if !quiet {
+						println!("{:<15}\t{}", name, if value { "on" } else { "off" });
+					}
+					status |= !value as i32;
+					Ok(())
+				}
+				None => Err(format!("{}: invalid shell option name", name)),
+			},
+		};
+		if let Err(err) = result {
+			shell_error!("shopt: {}", err);
+			status = 1;
+		}
+	}
+	status
+}
+
+// `set -o` shows whether each option is on, `set +o` the commands that turn
+// them back to how they are now.
+fn list_options(options: &ShellOptions, readable: bool) {
+	for (name, on) in options.list() {
+		if readable {
+			println!("{:<15}\t{}", name, if on { "on" } else { "off" });
+		} else {
+			println!("set {}o {}", if on { '-' } else { '+' }, name);
+		}
+	}
+}
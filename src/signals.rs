@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use nix::errno::Errno;
+use nix::sys::signal::{sigaction, signal, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::wait::{self, WaitPidFlag, WaitStatus};
+use nix::unistd::{self, Pid};
+
+// One bit per signal number: the signals the shell catches, and the ones that
+// arrived and have not been dealt with yet.
+static CAUGHT: AtomicU64 = AtomicU64::new(0);
+static PENDING: AtomicU64 = AtomicU64::new(0);
+// Set when Ctrl+C stops what the shell is running, until the command line it
+// belongs to has been left.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(signal: i32) {
+	PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+// Keep SIGINT from killing the shell. Ctrl+C from the terminal reaches the
+// command in the foreground directly, and the shell stops what it runs once
+// it notices.
+pub fn install() {
+	catch(Signal::SIGINT);
+}
+
+// Note a signal as pending instead of letting it take its default action.
+// The handler is installed without SA_RESTART so a read waiting for input
+// returns, which drops the line being typed.
+pub fn catch(signal: Signal) {
+	let action = SigAction::new(SigHandler::Handler(on_signal), SaFlags::empty(), SigSet::empty());
+	if unsafe { sigaction(signal, &action) }.is_ok() {
+		CAUGHT.fetch_or(1 << signal as i32, Ordering::SeqCst);
+	}
+}
+
+pub fn ignore(signal: Signal) {
+	set_handler(signal, SigHandler::SigIgn);
+}
+
+// Go back to how the shell treats a signal when nothing is set for it.
+pub fn restore(signal: Signal) {
+	match signal {
+		Signal::SIGINT => catch(signal),
+		Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU if crate::jobs::job_control() => ignore(signal),
+		_ => set_handler(signal, SigHandler::SigDfl),
+	}
+}
+
+fn set_handler(signal: Signal, handler: SigHandler) {
+	unsafe { self::signal(signal, handler) }.ok();
+	CAUGHT.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
+}
+
+// Put back the default behaviour of every signal the shell catches in a
+// child that is about to run a command, so Ctrl+C stops it. Ignored signals
+// stay ignored.
+pub fn reset() {
+	let caught = CAUGHT.load(Ordering::SeqCst);
+	for caught_signal in Signal::iterator().filter(|&signal| caught & (1 << signal as i32) != 0) {
+		unsafe { signal(caught_signal, SigHandler::SigDfl) }.ok();
+	}
+}
+
+// The signals that arrived since the last call, in order of their numbers.
+pub fn take_pending() -> Vec<Signal> {
+	let pending = PENDING.swap(0, Ordering::SeqCst);
+	Signal::iterator()
+		.filter(|&signal| pending & (1 << signal as i32) != 0)
+		.collect()
+}
+
+// Note a signal as if it had arrived, for one meant for the shell that went
+// to the job in the foreground instead.
+pub fn mark_pending(signal: Signal) {
+	PENDING.fetch_or(1 << signal as i32, Ordering::SeqCst);
+}
+
+pub fn interrupt() {
+	INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+pub fn interrupted() -> bool {
+	INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub fn take_interrupt() -> bool {
+	INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+// Leave a child interrupted by Ctrl+C by dying of SIGINT, so the shell
+// waiting for it stops too.
+pub fn exit_interrupted() -> ! {
+	io::stdout().flush().ok();
+	unsafe { signal(Signal::SIGINT, SigHandler::SigDfl) }.ok();
+	nix::sys::signal::raise(Signal::SIGINT).ok();
+	std::process::exit(130);
+}
+
+// A signal given by number, by name or by name without the `SIG` prefix, in
+// any case.
+pub fn parse(name: &str) -> Option<Signal> {
+	if let Ok(number) = name.parse::<i32>() {
+		return Signal::try_from(number).ok();
+	}
+	let name = name.to_ascii_uppercase();
+	let name = name.strip_prefix("SIG").unwrap_or(&name);
+	Signal::from_str(&format!("SIG{}", name)).ok()
+}
+
+// `waitpid` that carries on when a signal interrupts it.
+pub fn waitpid(pid: Pid, flags: Option<WaitPidFlag>) -> nix::Result<WaitStatus> {
+	loop {
+		match wait::waitpid(pid, flags) {
+			Err(Errno::EINTR) => continue,
+			result => return result,
+		}
+	}
+}
+
+// Read one line from stdin into `line`, returning how many bytes were read
+// with 0 at end of input. Reading a byte at a time leaves the rest of the
+// input to the commands the shell starts. Fails with `Interrupted` when
+// Ctrl+C arrives first.
+pub fn read_line(line: &mut String) -> io::Result<usize> {
+	let mut bytes: Vec<u8> = Vec::new();
+	let mut byte = [0u8; 1];
+	loop {
+		match unistd::read(0, &mut byte) {
+			Ok(0) => break,
+			Ok(_) => {
+				bytes.push(byte[0]);
+				if byte[0] == b'\n' {
+					break;
+				}
+			}
+			Err(Errno::EINTR) => return Err(io::ErrorKind::Interrupted.into()),
+			Err(_) => break,
+		}
+	}
+	line.push_str(&String::from_utf8_lossy(&bytes));
+	Ok(bytes.len())
+}
@@ -0,0 +1,89 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use nix::errno::Errno;
+
+use crate::compound::{self, ControlFlow};
+use crate::lexer::ParseError;
+use crate::Shell;
+
+// `source file [args]` and `. file [args]`: run a file in the shell itself,
+// with the arguments as the positional parameters while it runs. `return`
+// leaves the file early.
+pub fn source(shell: &mut Shell, cmd: &str, args: &[String]) -> Result<i32, ControlFlow> {
+	let Some(name) = args.first() else {
+		shell_error!("{}: filename argument required", cmd);
+		eprintln!("{}: usage: {} filename [arguments]", cmd, cmd);
+		return Ok(2);
+	};
+	let text = match fs::read_to_string(find_file(name)) {
+		Ok(text) => text,
+		Err(err) => {
+			shell_error!("{}: {}: {}", cmd, name, Errno::from_raw(err.raw_os_error().unwrap_or(0)).desc());
+			return Ok(1);
+		}
+	};
+
+	let saved = (args.len() > 1).then(|| shell.vars.replace_positional(args[1..].to_vec()));
+	let result = run_script(shell, name, &text);
+	if let Some(saved) = saved {
+		shell.vars.replace_positional(saved);
+	}
+	match result {
+		Err(ControlFlow::Return(status)) => Ok(status),
+		result => result,
+	}
+}
+
+// A name without a slash is looked for in `PATH` first, then taken as it is.
+fn find_file(name: &str) -> String {
+	if !name.contains('/') {
+		let path = env::var("PATH").unwrap_or_default();
+		for dir in path.split(':') {
+			let candidate = format!("{}/{}", dir, name);
+			if Path::new(&candidate).is_file() {
+				return candidate;
+			}
+		}
+	}
+	name.to_string()
+}
+
+// Run the commands of a script one at a time, each as soon as the lines read
+// so far complete it, so the aliases it defines apply to the commands after
+// them. Errors name the script and the line of the command. A syntax error
+// stops the script.
+pub fn run_script(shell: &mut Shell, name: &str, text: &str) -> Result<i32, ControlFlow> {
+	let saved = crate::set_location(None);
+	let result = run_lines(shell, name, text);
+	crate::set_location(saved);
+	result
+}
+
+fn run_lines(shell: &mut Shell, name: &str, text: &str) -> Result<i32, ControlFlow> {
+	let mut status = 0;
+	let mut command = String::new();
+	// the line the command being read starts on
+	let mut start = 1;
+	let mut lines = text.split_inclusive('\n').enumerate().peekable();
+	while let Some((i, line)) = lines.next() {
+		if command.is_empty() {
+			start = i + 1;
+		}
+		command.push_str(line);
+		crate::set_location(Some((name.to_string(), start)));
+		match compound::parse_lines(&command, start, lines.peek().is_none(), &shell.aliases) {
+			Ok(list) => {
+				command.clear();
+				status = compound::run_list(shell, &list)?;
+			}
+			Err(ParseError::Incomplete) => (),
+			Err(ParseError::Syntax(err)) => {
+				shell_error!("{}", err);
+				return Ok(2);
+			}
+		}
+	}
+	Ok(status)
+}
@@ -0,0 +1,36 @@
+use std::env;
+use std::fs;
+
+use crate::source_cmd;
+use crate::{Options, Shell};
+
+// Run the startup files: `/etc/profile` and `~/.profile` for a login shell,
+// then `~/.shellrc` when the shell reads its commands from a terminal. A
+// shell running a script or a `-c` command runs the file `SHELL_ENV` names
+// instead. Errors in the files are reported and the shell starts anyway.
+pub fn run(shell: &mut Shell, options: &Options) {
+	let home = env::var("HOME").ok();
+	if options.login && !options.noprofile {
+		run_file(shell, "/etc/profile");
+		if let Some(home) = &home {
+			run_file(shell, &format!("{}/.profile", home));
+		}
+	}
+
+	if shell.interactive {
+		if let (false, Some(home)) = (options.norc, &home) {
+			run_file(shell, &format!("{}/.shellrc", home));
+		}
+	} else if !options.reads_stdin() {
+		if let Some(path) = shell.vars.get("SHELL_ENV").filter(|path| !path.is_empty()) {
+			run_file(shell, &path);
+		}
+	}
+}
+
+// Source a file if there is one.
+fn run_file(shell: &mut Shell, path: &str) {
+	if let Ok(text) = fs::read_to_string(path) {
+		source_cmd::run_script(shell, path, &text).ok();
+	}
+}
@@ -0,0 +1,149 @@
+use crate::cond_expr;
+use crate::Shell;
+
+// `test expr`: succeed when the expression is true and fail when it is
+// false, with status 2 when it cannot be evaluated. Up to four arguments are
+// read the way POSIX lays out by their number, so an operand that looks like
+// an operator is still taken as a string; longer expressions are parsed with
+// `-o` binding looser than `-a`, which binds looser than `!`.
+pub fn test(shell: &mut Shell, args: &[String]) -> i32 {
+	run(shell, "test", args)
+}
+
+// `[ expr ]`: `test` with a closing `]` after the expression.
+pub fn bracket(shell: &mut Shell, args: &[String]) -> i32 {
+	match args.split_last() {
+		Some((last, args)) if last == "]" => run(shell, "[", args),
+		_ => {
+			shell_error!("[: missing `]'");
+			2
+		}
+	}
+}
+
+fn run(shell: &mut Shell, name: &str, args: &[String]) -> i32 {
+	match evaluate(shell, args) {
+		Ok(true) => 0,
+		Ok(false) => 1,
+		Err(err) => {
+			shell_error!("{}: {}", name, err);
+			2
+		}
+	}
+}
+
+fn evaluate(shell: &mut Shell, args: &[String]) -> Result<bool, String> {
+	let word = |i: usize| args[i].as_str();
+	match args.len() {
+		0 => Ok(false),
+		1 => Ok(!args[0].is_empty()),
+		2 if word(0) == "!" => Ok(args[1].is_empty()),
+		2 if cond_expr::is_unary(word(0)) => Ok(cond_expr::test_unary(shell, word(0), word(1))),
+		2 => Err(format!("{}: unary operator expected", word(0))),
+		3 if is_binary(word(1)) => binary(word(0), word(1), word(2)),
+		3 if word(0) == "!" => Ok(!evaluate(shell, &args[1..])?),
+		3 if word(0) == "(" && word(2) == ")" => Ok(!args[1].is_empty()),
+		4 if word(0) == "!" => Ok(!evaluate(shell, &args[1..])?),
+		4 if word(0) == "(" && word(3) == ")" => evaluate(shell, &args[1..3]),
+		_ => {
+			let mut parser = Parser { args, pos: 0, shell };
+			let result = parser.or()?;
+			match args.get(parser.pos) {
+				Some(word) => Err(format!("{}: unexpected argument", word)),
+				None => Ok(result),
+			}
+		}
+	}
+}
+
+fn is_binary(op: &str) -> bool {
+	cond_expr::is_binary(op) || op == "-a" || op == "-o"
+}
+
+// Compare two strings, integers or files. `==` is the same as `=` and
+// compares the strings as they are.
+fn binary(left: &str, op: &str, right: &str) -> Result<bool, String> {
+	match op {
+		"=" | "==" => Ok(left == right),
+		"!=" => Ok(left != right),
+		"<" => Ok(left < right),
+		">" => Ok(left > right),
+		"-a" => Ok(!left.is_empty() && !right.is_empty()),
+		"-o" => Ok(!left.is_empty() || !right.is_empty()),
+		"-nt" | "-ot" | "-ef" => Ok(cond_expr::compare_files(op, left, right)),
+		_ => Ok(cond_expr::compare_integers(op, integer(left)?, integer(right)?)),
+	}
+}
+
+fn integer(word: &str) -> Result<i64, String> {
+	word.trim().parse().map_err(|_| format!("{}: integer expression expected", word))
+}
+
+struct Parser<'a> {
+	args: &'a [String],
+	pos: usize,
+	shell: &'a mut Shell,
+}
+
+impl Parser<'_> {
+	fn peek(&self, ahead: usize) -> Option<&str> {
+		self.args.get(self.pos + ahead).map(String::as_str)
+	}
+
+	fn next(&mut self) -> Result<&str, String> {
+		let word = self.args.get(self.pos).ok_or("argument expected")?;
+		self.pos += 1;
+		Ok(word)
+	}
+
+	fn or(&mut self) -> Result<bool, String> {
+		let mut result = self.and()?;
+		while self.peek(0) == Some("-o") {
+			self.pos += 1;
+			// both sides are evaluated, as every argument is already expanded
+			result |= self.and()?;
+		}
+		Ok(result)
+	}
+
+	fn and(&mut self) -> Result<bool, String> {
+		let mut result = self.not()?;
+		while self.peek(0) == Some("-a") {
+			self.pos += 1;
+			result &= self.not()?;
+		}
+		Ok(result)
+	}
+
+	fn not(&mut self) -> Result<bool, String> {
+		if self.peek(0) == Some("!") && self.peek(1).is_some() {
+			self.pos += 1;
+			return Ok(!self.not()?);
+		}
+		self.primary()
+	}
+
+	fn primary(&mut self) -> Result<bool, String> {
+		if self.peek(0) == Some("(") && self.peek(1).is_some() {
+			self.pos += 1;
+			let result = self.or()?;
+			return match self.next()? {
+				")" => Ok(result),
+				word => Err(format!("{}: `)' expected", word)),
+			};
+		}
+		let word = self.next()?.to_string();
+		match (self.peek(0), self.peek(1)) {
+			(Some(op), Some(_)) if cond_expr::is_binary(op) => {
+				let op = self.next()?.to_string();
+				let right = self.next()?.to_string();
+				binary(&word, &op, &right)
+			}
+			(Some(_), _) if cond_expr::is_unary(&word) => {
+				let operand = self.next()?.to_string();
+				Ok(cond_expr::test_unary(self.shell, &word, &operand))
+			}
+			_ => Ok(!word.is_empty()),
+		}
+	}
+}
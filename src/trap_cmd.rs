@@ -0,0 +1,36 @@
+use crate::traps::{SignalOrEvent, Traps};
+
+pub fn trap(traps: &mut Traps, args: &[String]) -> i32 {
+	let args = match args.first().map(String::as_str) {
+		Some("--") => &args[1..],
+		Some("-p") if args.len() == 1 => &args[1..],
+		_ => args,
+	};
+	if args.is_empty() {
+		for (trap, command) in traps.list() {
+			println!("trap -- '{}' {}", command.replace('\'', "'\\''"), trap.name());
+		}
+		return 0;
+	}
+
+	// a lone signal, or `-` before the signals, puts them back to default
+	let (command, names) = match args {
+		[name] => (None, std::slice::from_ref(name)),
+		[command, names @ ..] if command == "-" => (None, names),
+		[command, names @ ..] => (Some(command.as_str()), names),
+		[] => unreachable!(),
+	};
+	let mut status = 0;
+	for name in names {
+		let Some(trap) = SignalOrEvent::parse(name) else {
+			shell_error!("trap: {}: invalid signal specification", name);
+			status = 1;
+			continue;
+		};
+		match command {
+			Some(command) => traps.set(trap, command),
+			None => traps.reset(trap),
+		}
+	}
+	status
+}
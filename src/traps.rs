@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use nix::sys::signal::Signal;
+use nix::unistd::{getpid, Pid};
+
+use crate::signals;
+use crate::Shell;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SignalOrEvent {
+	// the shell exiting
+	Exit,
+	Signal(Signal),
+}
+
+impl SignalOrEvent {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name.to_ascii_uppercase().as_str() {
+			"EXIT" | "0" => Some(SignalOrEvent::Exit),
+			_ => signals::parse(name).map(SignalOrEvent::Signal),
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			SignalOrEvent::Exit => "EXIT",
+			SignalOrEvent::Signal(signal) => signal.as_str(),
+		}
+	}
+}
+
+// The command set for each trapped signal or event. An empty command means
+// the signal is ignored.
+pub struct Traps {
+	commands: HashMap<SignalOrEvent, String>,
+	// the shell itself, as its forked children get a copy of the traps
+	owner: Pid,
+}
+
+impl Traps {
+	pub fn new() -> Self {
+		Traps {
+			commands: HashMap::new(),
+			owner: getpid(),
+		}
+	}
+
+	pub fn set(&mut self, trap: SignalOrEvent, command: &str) {
+		if let SignalOrEvent::Signal(signal) = trap {
+			match command {
+				"" => signals::ignore(signal),
+				_ => signals::catch(signal),
+			}
+		}
+		self.commands.insert(trap, command.to_string());
+	}
+
+	pub fn reset(&mut self, trap: SignalOrEvent) {
+		if let SignalOrEvent::Signal(signal) = trap {
+			signals::restore(signal);
+		}
+		self.commands.remove(&trap);
+	}
+
+	// Every trap sorted with EXIT first and the signals by number.
+	pub fn list(&self) -> Vec<(SignalOrEvent, &str)> {
+		let mut traps: Vec<(SignalOrEvent, &str)> = self
+			.commands
+			.iter()
+			.map(|(trap, command)| (*trap, command.as_str()))
+			.collect();
+		traps.sort();
+		traps
+	}
+
+	fn command(&self, trap: SignalOrEvent) -> Option<String> {
+		// a subshell does not run the traps of its parent
+		if getpid() != self.owner {
+			return None;
+		}
+		self.commands.get(&trap).filter(|command| !command.is_empty()).cloned()
+	}
+}
+
+// Run the trap command of every signal caught since the last check. The
+// commands run in the shell itself and leave `$?` as it was. A SIGINT with no
+// trap set interrupts the shell instead.
+pub fn run_pending(shell: &mut Shell) {
+	for signal in signals::take_pending() {
+		let trap = SignalOrEvent::Signal(signal);
+		if signal == Signal::SIGINT && shell.traps.command(trap).is_none() {
+			signals::interrupt();
+		}
+		run(shell, trap);
+	}
+}
+
+pub fn run(shell: &mut Shell, trap: SignalOrEvent) {
+	let Some(command) = shell.traps.command(trap) else {
+		return;
+	};
+	// an `exit` inside the EXIT trap must not run it again
+	if trap == SignalOrEvent::Exit {
+		shell.traps.commands.remove(&trap);
+	}
+	let status = shell.last_status;
+	crate::run_line(shell, &command);
+	shell.last_status = status;
+}
@@ -3,37 +3,161 @@ use std::env;
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 
-const BUILTIN_COMMANDS: [&str; 4] = ["echo", "exit", "type", "pwd"];
+use crate::alias::Aliases;
+use crate::functions::Functions;
+use crate::hash::HashTable;
 
-pub fn check_type(command: &str) {
-	if let Some(cmd) = command.trim().strip_prefix("type") {
-		let cmd = cmd.trim();
-		if BUILTIN_COMMANDS.contains(&cmd) {
-			println!("{} is a shell builtin", cmd);
-			return;
+pub const BUILTIN_COMMANDS: [&str; 43] = [
+	"echo", "exit", "return", "break", "continue", "type", "pwd", "cd", "export", "local", "unset",
+	"env", "jobs", "fg", "bg", "wait", "disown", "trap", "kill", "alias", "unalias", "history",
+	"complete", "source", ".", "set", "shopt", "pushd", "popd", "dirs", "hash", "command",
+	"builtin", "declare", "typeset", "readonly", "let", "test", "[", "printf", "read",
+	"mapfile", "readarray",
+];
+
+// The reserved words that start or continue a compound command.
+pub const KEYWORDS: [&str; 16] = [
+	"if", "then", "elif", "else", "fi", "while", "until", "for", "in", "do", "done", "case", "esac",
+	"function", "{", "}",
+];
+
+// What `type` reports. `-a` shows every kind of command a name can be and
+// every file in `PATH` with it instead of only the one that runs, `-P` only
+// searches `PATH` and prints the files, and `-t` prints only the kind.
+#[derive(Clone, Copy, Default)]
+pub struct TypeFlags {
+	pub all: bool,
+	pub path: bool,
+	pub kind: bool,
+}
+
+// `type [-aPt] name...`
+pub fn type_builtin(aliases: &Aliases, functions: &Functions, args: &[String]) -> i32 {
+	let mut flags = TypeFlags::default();
+	let mut names = args;
+	while let Some(arg) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+		names = &names[1..];
+		if arg == "--" {
+			break;
 		}
-		if let Some(ext_path) = get_executable(cmd) {
-			println!("{} is {}", cmd, ext_path);
-			return;
+		for flag in arg.chars().skip(1) {
+			match flag {
+				'a' => flags.all = true,
+				'P' => flags.path = true,
+				't' => flags.kind = true,
+				_ => {
+					shell_error!("type: -{}: invalid option", flag);
+					eprintln!("type: usage: type [-aPt] name [name ...]");
+					return 2;
+				}
+			}
+		}
+	}
+
+	let mut status = 0;
+	for name in names {
+		if check_type(aliases, functions, flags, name) != 0 {
+			status = 1;
 		}
-		println!("{}: not found", cmd);
 	}
+	status
 }
 
-pub fn get_executable(cmd: &str) -> Option<String> {
-	let path = env::var("PATH").expect("PATH must be set");
+pub fn check_type(aliases: &Aliases, functions: &Functions, flags: TypeFlags, name: &str) -> i32 {
+	let mut found: Vec<(&str, String)> = Vec::new();
+	if !flags.path {
+		if let Some(value) = aliases.get(name) {
+			found.push(("alias", format!("{} is aliased to `{}'", name, value)));
+		}
+		if KEYWORDS.contains(&name) {
+			found.push(("keyword", format!("{} is a shell keyword", name)));
+		}
+		if functions.get(name).is_some() {
+			found.push(("function", format!("{} is a function", name)));
+		}
+		if BUILTIN_COMMANDS.contains(&name) {
+			found.push(("builtin", format!("{} is a shell builtin", name)));
+		}
+	}
+	if flags.all || found.is_empty() {
+		for path in path_matches(&env::var("PATH").unwrap_or_default(), name) {
+			let text = if flags.path { path } else { format!("{} is {}", name, path) };
+			found.push(("file", text));
+		}
+	}
+	if !flags.all {
+		found.truncate(1);
+	}
+
+	if found.is_empty() {
+		if !flags.kind && !flags.path {
+			println!("{}: not found", name);
+		}
+		return 1;
+	}
+	for (kind, text) in found {
+		println!("{}", if flags.kind { kind } else { &text });
+	}
+	0
+}
+
+// The path of the command `cmd`, from the hash table when it was run before.
+pub fn get_executable(hash: &mut HashTable, cmd: &str) -> Option<String> {
+	if cmd.contains('/') {
+		return search_path(cmd);
+	}
+	hash.lookup(cmd)
+}
+
+// The first executable named `cmd` in the directories of `PATH`.
+pub fn search_path(cmd: &str) -> Option<String> {
+	path_matches(&env::var("PATH").unwrap_or_default(), cmd).next()
+}
+
+// The first executable named `cmd` in the directories of `path`.
+pub fn search_dirs(path: &str, cmd: &str) -> Option<String> {
+	path_matches(path, cmd).next()
+}
+
+// Every executable named `cmd` in the directories of `path`, in order. A
+// name with a `/` in it is a path of its own and is not searched for.
+fn path_matches<'a>(path: &str, cmd: &'a str) -> impl Iterator<Item = String> + 'a {
+	let dirs: Vec<Option<String>> = match cmd.contains('/') {
+		true => vec![None],
+		false => path.split(':').map(|dir| Some(dir.to_string())).collect(),
+	};
+	dirs.into_iter()
+		.map(move |path_elem| match path_elem {
+			Some(path_elem) => format!("{}/{}", path_elem, cmd),
+			None => cmd.to_string(),
+		})
+		.filter(|file_path| is_executable(Path::new(file_path)))
+}
+
+// The names of the executables in `PATH` that start with `prefix`, sorted.
+pub fn executables_starting_with(prefix: &str) -> Vec<String> {
+	let path = env::var("PATH").unwrap_or_default();
+	let mut names: Vec<String> = Vec::new();
 	for path_elem in path.split(":") {
-		let file_path_str = &format!("{}/{}", path_elem, cmd);
-		let file_path = Path::new(file_path_str);
-		if file_path.exists() {
-			if let Ok(metadata) = fs::metadata(file_path) {
-				let permissions = metadata.permissions();
-				if permissions.mode() & 0o111 != 0 {
-					return Some(file_path_str.to_string());
-				}
+		let Ok(entries) = fs::read_dir(path_elem) else {
+			continue;
+		};
+		for entry in entries.flatten() {
+			let name = entry.file_name().to_string_lossy().to_string();
+			if name.starts_with(prefix) && is_executable(&entry.path()) {
+				names.push(name);
 			}
 		}
 	}
+	names.sort();
+	names.dedup();
+	names
+}
 
-	return None;
+// A regular file, or a link to one, with an execute bit set.
+fn is_executable(path: &Path) -> bool {
+	match fs::metadata(path) {
+		Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+		Err(_) => false,
+	}
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+use crate::arith;
+use crate::functions::Functions;
+use crate::variables::Variables;
+
+enum Target {
+	// a variable, or a function when there is no variable of that name
+	Any,
+	Variable,
+	Function,
+}
+
+pub fn unset(vars: &mut Variables, functions: &mut Functions, args: &[String]) -> i32 {
+	let mut target = Target::Any;
+	let mut names = args;
+	while let Some(flag) = names.first() {
+		match flag.as_str() {
+			"-v" => target = Target::Variable,
+			"-f" => target = Target::Function,
+			_ => break,
+		}
+		names = &names[1..];
+	}
+
+	let mut status = 0;
+	for name in names {
+		// `name[subscript]` unsets a single element of an array
+		if let Some((name, subscript)) = name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+			let unset = if vars.is_assoc(name) {
+				vars.unset_key(name, subscript)
+			} else {
+				arith::evaluate(subscript, vars).and_then(|index| vars.unset_element(name, index))
+			};
+			if let Err(err) = unset {
+				shell_error!("unset: {}", err);
+				status = 1;
+			}
+			continue;
+		}
+		match target {
+			Target::Any if vars.get(name).is_none() => {
+				functions.remove(name);
+			}
+			Target::Any | Target::Variable => {
+				if let Err(err) = vars.unset(name) {
+					shell_error!("unset: {}", err);
+					status = 1;
+				}
+			}
+			Target::Function => {
+				functions.remove(name);
+			}
+		}
+	}
+
+	status
+}
\ No newline at end of file
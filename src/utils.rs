@@ -1,86 +1,563 @@
+use crate::arith;
+use crate::brace_expand;
+use crate::expand;
+use crate::glob;
+use crate::param_expand;
+use crate::variables;
+use crate::Shell;
+
 enum QuoteState {
     None,
     Single,
     Double,
 }
 
-pub fn parse_args(s: &str) -> Vec<String> {
+// Follows quoting while raw command text is scanned one character at a time,
+// so callers can find operators like `|` or `>` that are not quoted. Text
+// inside a `$(...)`, `<(...)` or backtick substitution counts as quoted, and
+// so does that of a `${...}` reference.
+pub struct QuoteTracker {
+	quote_state: QuoteState,
+	is_escaped: bool,
+	in_backticks: bool,
+	// quote state outside of every substitution that is still open, with the
+	// character that closes it
+	outer_states: Vec<(QuoteState, char)>,
+	// the previous character, unless it was escaped or single quoted
+	prev: Option<char>,
+}
+
+impl QuoteTracker {
+	pub fn new() -> Self {
+		QuoteTracker {
+			quote_state: QuoteState::None,
+			is_escaped: false,
+			in_backticks: false,
+			outer_states: Vec::new(),
+			prev: None,
+		}
+	}
+
+	// Feed the next character and report whether it stands outside of any
+	// quotes, escapes and substitutions. Quote characters, backslashes and the
+	// parentheses of a substitution report false.
+	pub fn is_unquoted(&mut self, ch: char) -> bool {
+		let prev = self.prev.take();
+		if self.is_escaped {
+			self.is_escaped = false;
+			return false;
+		}
+		if self.in_backticks {
+			match ch {
+				'\\' => self.is_escaped = true,
+				'`' => self.in_backticks = false,
+				_ => (),
+			}
+			return false;
+		}
+		match (ch, &self.quote_state) {
+			('\\', QuoteState::None | QuoteState::Double) => self.is_escaped = true,
+			('`', QuoteState::None | QuoteState::Double) => self.in_backticks = true,
+			('\'', QuoteState::None) => self.quote_state = QuoteState::Single,
+			('\'', QuoteState::Single) => self.quote_state = QuoteState::None,
+			('"', QuoteState::None) => self.quote_state = QuoteState::Double,
+			('"', QuoteState::Double) => self.quote_state = QuoteState::None,
+			('(', QuoteState::None)
+				if matches!(prev, Some('<' | '$')) || self.in_substitution() =>
+			{
+				self.outer_states.push((QuoteState::None, ')'));
+			}
+			// braces nest inside a reference, as in `${name:-{}}`
+			('{', QuoteState::None) if prev == Some('$') || self.closes_with('}') => {
+				self.outer_states.push((QuoteState::None, '}'));
+			}
+			// quoting starts over inside a substitution
+			('(' | '{', QuoteState::Double) if prev == Some('$') => {
+				let close = if ch == '(' { ')' } else { '}' };
+				self.outer_states.push((QuoteState::Double, close));
+				self.quote_state = QuoteState::None;
+			}
+			(')' | '}', QuoteState::None) if self.closes_with(ch) => {
+				self.quote_state = self.outer_states.pop().map_or(QuoteState::None, |(state, _)| state);
+			}
+			(_, QuoteState::None) => {
+				self.prev = Some(ch);
+				return !self.in_substitution();
+			}
+			(_, QuoteState::Double) => self.prev = Some(ch),
+			_ => (),
+		}
+		false
+	}
+
+	pub fn in_substitution(&self) -> bool {
+		!self.outer_states.is_empty()
+	}
+
+	// Whether `ch` closes the innermost substitution or reference.
+	fn closes_with(&self, ch: char) -> bool {
+		self.outer_states.last().is_some_and(|&(_, close)| close == ch)
+	}
+
+	// Whether the last character was a backslash that escapes the next one.
+	pub fn is_escaping(&self) -> bool {
+		self.is_escaped
+	}
+
+	// Whether a quote, escape or substitution is still waiting to be closed.
+	pub fn is_open(&self) -> bool {
+		self.is_escaped
+			|| self.in_backticks
+			|| self.in_substitution()
+			|| !matches!(self.quote_state, QuoteState::None)
+	}
+}
+
+// Find the `)` that closes the substitution opened at `chars[open]`, which is
+// the `(` of a `$(` or `<(`.
+pub fn find_closing_paren(chars: &[char], open: usize) -> Option<usize> {
+	let mut quotes = QuoteTracker::new();
+	quotes.is_unquoted(chars[open - 1]);
+	quotes.is_unquoted(chars[open]);
+
+	let mut i = open;
+	while quotes.in_substitution() {
+		i += 1;
+		quotes.is_unquoted(*chars.get(i)?);
+	}
+	Some(i)
+}
+
+// Find the `))` that closes the `$((` whose outer `(` is at `chars[open]`,
+// returning the index of its last `)`. None when the parentheses close some
+// other way, as in `$((cmd) | cmd)`, which is a command substitution.
+pub fn find_arith_end(chars: &[char], open: usize) -> Option<usize> {
+	if chars.get(open) != Some(&'(') || chars.get(open + 1) != Some(&'(') {
+		return None;
+	}
+	let end = find_closing_paren(chars, open)?;
+	let mut depth = 0;
+	for (i, ch) in chars.iter().enumerate().take(end).skip(open + 1) {
+		match ch {
+			'(' => depth += 1,
+			')' => depth -= 1,
+			_ => continue,
+		}
+		if depth == 0 {
+			return (i + 1 == end).then_some(end);
+		}
+	}
+	None
+}
+
+// Find the backtick that closes the one at `chars[open]`.
+pub fn find_closing_backtick(chars: &[char], open: usize) -> Option<usize> {
+	let mut i = open + 1;
+	while i < chars.len() {
+		match chars[i] {
+			'\\' => i += 2,
+			'`' => return Some(i),
+			_ => i += 1,
+		}
+	}
+	None
+}
+
+// Strip quotes and escaping backslashes from a word without expanding
+// anything in it.
+pub fn remove_quotes(word: &str) -> String {
 	let mut quote_state = QuoteState::None;
-	let mut current_token: String = String::new();
+	let mut unquoted = String::new();
+
+	let mut chars = word.chars().peekable();
+	while let Some(ch) = chars.next() {
+		match (ch, &quote_state) {
+			('\\', QuoteState::None) => unquoted.extend(chars.next()),
+			('\\', QuoteState::Double) if matches!(chars.peek(), Some('\\' | '"' | '$' | '`')) => {
+				unquoted.extend(chars.next())
+			}
+			('\'', QuoteState::None) => quote_state = QuoteState::Single,
+			('\'', QuoteState::Single) => quote_state = QuoteState::None,
+			('"', QuoteState::None) => quote_state = QuoteState::Double,
+			('"', QuoteState::Double) => quote_state = QuoteState::None,
+			_ => unquoted.push(ch),
+		}
+	}
+
+	unquoted
+}
+
+// Split raw command text into its words at unquoted blanks, without
+// expanding anything. The parentheses of an array assignment `name=(...)` and
+// everything between them stay in one word.
+pub fn split_words(text: &str) -> Vec<&str> {
+	let mut quotes = QuoteTracker::new();
+	let mut depth = 0;
+	let mut words: Vec<&str> = Vec::new();
+	let mut start = None;
+	for (idx, ch) in text.char_indices() {
+		let unquoted = quotes.is_unquoted(ch);
+		if unquoted && ch.is_whitespace() && depth <= 0 {
+			words.extend(start.take().map(|start| &text[start..idx]));
+			continue;
+		}
+		start.get_or_insert(idx);
+		match ch {
+			'(' if unquoted => depth += 1,
+			')' if unquoted => depth -= 1,
+			_ => (),
+		}
+	}
+	words.extend(start.map(|start| &text[start..]));
+	words
+}
+
+// Expand and split a command into its words. Fails when an expansion in it
+// fails, in which case the command should not run.
+pub fn parse_args(s: &str, shell: &mut Shell) -> Result<Vec<String>, String> {
+	// braces expand first, into words that still get every other expansion
+	let mut quotes = QuoteTracker::new();
+	let mut expanded: Vec<String> = Vec::new();
+	let mut word = String::new();
+	for ch in s.chars() {
+		if quotes.is_unquoted(ch) && ch.is_whitespace() {
+			expanded.extend(brace_expand::expand(&word));
+			word.clear();
+		} else {
+			word.push(ch);
+		}
+	}
+	expanded.extend(brace_expand::expand(&word));
+
+	expand_tokens(&expanded.join(" "), shell, Expansion::Fields)
+}
+
+// Expand a single word the way an assignment value is expanded: results of
+// expansions are not split into more words, and neither are blanks.
+pub fn expand_word(word: &str, shell: &mut Shell) -> Result<String, String> {
+	Ok(expand_tokens(word, shell, Expansion::Word)?.concat())
+}
+
+// Expand a single word into a glob pattern, in which whatever was quoted
+// only matches itself.
+pub fn expand_pattern(word: &str, shell: &mut Shell) -> Result<String, String> {
+	Ok(expand_tokens(word, shell, Expansion::Pattern)?.concat())
+}
+
+// Expand a single word into a regular expression, in which whatever was
+// quoted only matches itself.
+pub fn expand_regex(word: &str, shell: &mut Shell) -> Result<String, String> {
+	Ok(expand_tokens(word, shell, Expansion::Regex)?.concat())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expansion {
+	// split into words at blanks, with globs replaced by their matches
+	Fields,
+	Word,
+	Pattern,
+	Regex,
+}
+
+fn expand_tokens(s: &str, shell: &mut Shell, mode: Expansion) -> Result<Vec<String>, String> {
+	let split_words = mode == Expansion::Fields;
+	let mut quote_state = QuoteState::None;
+	let mut current_token = Word {
+		nullglob: shell.options.nullglob,
+		dotglob: shell.options.dotglob,
+		..Word::default()
+	};
 	let mut tokens: Vec<String> = Vec::new();
 	let mut is_escaped = false;
 
 	let chars: Vec<char> = s.chars().collect();
 	// for ch in s.chars() {
-	for i in 0..chars.len() {
+	let mut i = 0;
+	while i < chars.len() {
 		let ch = chars[i];
+		i += 1;
+		// a backslash keeps whatever follows it literal
+		if is_escaped {
+			current_token.push(ch, true);
+			is_escaped = false;
+			continue;
+		}
+		let quoted = !matches!(quote_state, QuoteState::None);
 		match ch {
-			'\'' => {
-				if !is_escaped {
-					match quote_state {
-						QuoteState::None => quote_state = QuoteState::Single,
-						QuoteState::Single => quote_state = QuoteState::None,
-						QuoteState::Double => current_token.push(ch),
-					}
-				} else {
-					current_token.push(ch);
-					is_escaped = !is_escaped;
+			'\'' => match quote_state {
+				QuoteState::None => {
+					quote_state = QuoteState::Single;
+					current_token.has_quotes = true;
 				}
-			}
-			'"' => {
-				if !is_escaped {
-					match quote_state {
-						QuoteState::None => quote_state = QuoteState::Double,
-						QuoteState::Double => quote_state = QuoteState::None,
-						QuoteState::Single => current_token.push(ch),
+				QuoteState::Single => quote_state = QuoteState::None,
+				QuoteState::Double => current_token.push(ch, true),
+			},
+			'"' => match quote_state {
+				QuoteState::None => {
+					quote_state = QuoteState::Double;
+					current_token.has_quotes = true;
+				}
+				QuoteState::Double => quote_state = QuoteState::None,
+				QuoteState::Single => current_token.push(ch, true),
+			},
+			' ' => match quote_state {
+				QuoteState::None if split_words => {
+					if !current_token.is_empty() {
+						current_token.finish(&mut tokens);
 					}
-				} else {
-					current_token.push(ch);
-					is_escaped = !is_escaped;
 				}
+				_ => current_token.push(ch, quoted),
+			},
+			// arithmetic, command substitutions and variables expand outside of
+			// quotes and inside double quotes, where the value stays part of the
+			// word
+			'$' if !matches!(quote_state, QuoteState::Single) && find_arith_end(&chars, i).is_some() => {
+				let end = find_arith_end(&chars, i).unwrap_or_default();
+				let expr: String = chars[i + 2..end - 1].iter().collect();
+				let expr = expand_word(&expr, shell)?;
+				let value = arith::evaluate(&expr, &mut shell.vars)?;
+				current_token.push_str(&value.to_string(), quoted);
+				i = end + 1;
 			}
-			' ' => {
-				if !is_escaped {
-					match quote_state {
-						QuoteState::None => {
-							if !current_token.is_empty() {
-								tokens.push(current_token.clone());
-								current_token.clear();
-							}
+			'$' if chars.get(i) == Some(&'(') && !matches!(quote_state, QuoteState::Single) => {
+				let Some(end) = find_closing_paren(&chars, i) else {
+					current_token.push(ch, quoted);
+					continue;
+				};
+				let command: String = chars[i + 1..end].iter().collect();
+				let output = expand::substitute(shell, &command);
+				i = end + 1;
+				let ifs = field_separators(shell, split_words && !quoted);
+				push_expansion(&output, ifs.as_deref(), quoted, &mut current_token, &mut tokens);
+			}
+			'`' if !matches!(quote_state, QuoteState::Single) => {
+				let Some(end) = find_closing_backtick(&chars, i - 1) else {
+					current_token.push(ch, quoted);
+					continue;
+				};
+				let command: String = chars[i..end].iter().collect();
+				let output = expand::backtick_substitution(shell, &command);
+				i = end + 1;
+				let ifs = field_separators(shell, split_words && !quoted);
+				push_expansion(&output, ifs.as_deref(), quoted, &mut current_token, &mut tokens);
+			}
+			'$' if !matches!(quote_state, QuoteState::Single) => {
+				match variables::read_reference(&chars, i - 1) {
+					Some((name, end)) => {
+						i = end;
+						if let Some(list) = param_expand::expand_list(shell, &name) {
+							let (items, star) = list?;
+							push_list(shell, &items, star, quoted, split_words, &mut current_token, &mut tokens);
+							continue;
 						}
-						_ => current_token.push(ch),
+						let value = param_expand::expand(shell, &name)?;
+						let ifs = field_separators(shell, split_words && !quoted);
+						push_expansion(&value, ifs.as_deref(), quoted, &mut current_token, &mut tokens);
 					}
-				} else {
-					current_token.push(ch);
-					is_escaped = !is_escaped;
+					None => current_token.push(ch, quoted),
 				}
 			}
 			'\\' => match quote_state {
 				QuoteState::None => is_escaped = true,
-				QuoteState::Double => if !is_escaped {
-					if i + 1 < chars.len() {
-						let next_ch = chars[i + 1];
-						if next_ch == '\\' || next_ch == '"' {
-							is_escaped = true;
-						} else {
-							current_token.push(ch);
-						}
-					}
-				} else {
-					current_token.push(ch);
-					is_escaped = false;
+				// inside double quotes only a few characters can be escaped
+				QuoteState::Double => match chars.get(i) {
+					Some('\\' | '"' | '$' | '`') => is_escaped = true,
+					Some(_) => current_token.push(ch, true),
+					None => (),
 				},
-				_ => current_token.push(ch),
+				_ => current_token.push(ch, true),
 			},
-			_ => current_token.push(ch),
+			_ => current_token.push(ch, quoted),
 		}
 	}
 
 	if !current_token.is_empty() {
-		tokens.push(current_token)
+		match mode {
+			Expansion::Fields => current_token.finish(&mut tokens),
+			Expansion::Word => tokens.push(current_token.text),
+			Expansion::Pattern => tokens.push(current_token.pattern),
+			Expansion::Regex => tokens.push(current_token.regex),
+		}
 	}
 
-	return tokens;
+	Ok(tokens)
+}
+
+// A word being expanded. Next to its text it keeps the word as a glob pattern
+// and as a regular expression, in which the characters that were quoted are
+// escaped.
+#[derive(Default)]
+struct Word {
+	text: String,
+	pattern: String,
+	regex: String,
+	has_wildcard: bool,
+	// quotes keep a word even when nothing is left in it, as in `''`, and so
+	// does an empty field between two separators
+	has_quotes: bool,
+	// `nullglob` and `dotglob`, kept from one word to the next
+	nullglob: bool,
+	dotglob: bool,
+}
+
+impl Word {
+	fn push(&mut self, ch: char, quoted: bool) {
+		self.text.push(ch);
+		if quoted && glob::is_special(ch) {
+			self.pattern.push('\\');
+		}
+		self.pattern.push(ch);
+		if quoted && "\\.+*?()|[]{}^$".contains(ch) {
+			self.regex.push('\\');
+		}
+		self.regex.push(ch);
+		self.has_wildcard |= !quoted && glob::is_wildcard(ch);
+	}
+
+	fn push_str(&mut self, s: &str, quoted: bool) {
+		for ch in s.chars() {
+			self.push(ch, quoted);
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.text.is_empty() && !self.has_quotes
+	}
+
+	// Add the finished word to `tokens`, replaced by the paths it matches when
+	// it has an unquoted wildcard that matches any. With `nullglob` a wildcard
+	// that matches nothing leaves nothing.
+	fn finish(&mut self, tokens: &mut Vec<String>) {
+		let next = Word {
+			nullglob: self.nullglob,
+			dotglob: self.dotglob,
+			..Word::default()
+		};
+		let word = std::mem::replace(self, next);
+		let matches = if word.has_wildcard { glob::expand(&word.pattern, word.dotglob) } else { Vec::new() };
+		if matches.is_empty() && word.has_wildcard && word.nullglob {
+			return;
+		}
+		if matches.is_empty() {
+			tokens.push(word.text);
+		} else {
+			tokens.extend(matches);
+		}
+	}
+}
+
+// Add the words of a list such as `$@`, `$*` or `${name[@]}`. When words are
+// split each one makes at least one word of its own, except in `"$*"`, which
+// joins them with the first character of `IFS` as it does where nothing is
+// split. `"$@"` without any words leaves no word behind.
+fn push_list(
+	shell: &Shell,
+	args: &[String],
+	star: bool,
+	quoted: bool,
+	split_words: bool,
+	current_token: &mut Word,
+	tokens: &mut Vec<String>,
+) {
+	if !split_words || (quoted && star) {
+		let separator: String = match (star, shell.vars.get("IFS")) {
+			(true, Some(ifs)) => ifs.chars().take(1).collect(),
+			_ => " ".to_string(),
+		};
+		current_token.push_str(&args.join(&separator), quoted);
+		return;
+	}
+	if quoted {
+		if args.is_empty() && current_token.text.is_empty() {
+			current_token.has_quotes = false;
+		}
+		for (n, arg) in args.iter().enumerate() {
+			if n > 0 {
+				current_token.finish(tokens);
+				current_token.has_quotes = true;
+			}
+			current_token.push_str(arg, true);
+		}
+		return;
+	}
+	let ifs = field_separators(shell, true);
+	for (n, arg) in args.iter().enumerate() {
+		if n > 0 && !current_token.is_empty() {
+			current_token.finish(tokens);
+		}
+		push_expansion(arg, ifs.as_deref(), false, current_token, tokens);
+	}
+}
+
+// The characters the result of an expansion is split at, when it is split:
+// those of `IFS`, or blanks when it is not set. None when `IFS` is empty.
+fn field_separators(shell: &Shell, split: bool) -> Option<String> {
+	if !split {
+		return None;
+	}
+	let ifs = shell.vars.get("IFS").unwrap_or(" \t\n".to_string());
+	Some(ifs).filter(|ifs| !ifs.is_empty())
+}
+
+// Add the result of an expansion to the word being built. With `ifs` the
+// result is broken into words at those characters, the first and last of
+// which join the text around the expansion.
+fn push_expansion(
+	output: &str,
+	ifs: Option<&str>,
+	quoted: bool,
+	current_token: &mut Word,
+	tokens: &mut Vec<String>,
+) {
+	let Some(ifs) = ifs else {
+		current_token.push_str(output, quoted);
+		return;
+	};
+	for (n, (field, keep)) in split_fields(output, ifs).into_iter().enumerate() {
+		if n > 0 && !current_token.is_empty() {
+			current_token.finish(tokens);
+		}
+		current_token.push_str(&field, false);
+		current_token.has_quotes |= keep;
+	}
+}
+
+// Split text into fields at the characters of `ifs`. Blanks in it separate
+// fields however many there are and are dropped at either end, while any
+// other of its characters ends a field on its own, so two in a row leave an
+// empty one. Each field comes with whether it is kept when empty: a leading
+// or trailing separator gives an empty field that is only there to keep the
+// text around the expansion apart.
+fn split_fields(text: &str, ifs: &str) -> Vec<(String, bool)> {
+	let is_blank = |ch: char| ifs.contains(ch) && matches!(ch, ' ' | '\t' | '\n');
+	let mut fields: Vec<(String, bool)> = Vec::new();
+	let mut field = String::new();
+	let mut chars = text.chars().peekable();
+	if chars.next_if(|&ch| is_blank(ch)).is_some() {
+		while chars.next_if(|&ch| is_blank(ch)).is_some() {}
+		fields.push((String::new(), false));
+	}
+	let mut separated = false;
+	while let Some(ch) = chars.next() {
+		if !ifs.contains(ch) {
+			field.push(ch);
+			separated = false;
+			continue;
+		}
+		// blanks around a separator that is not a blank belong to it
+		while chars.next_if(|&ch| is_blank(ch)).is_some() {}
+		if is_blank(ch) && chars.next_if(|&ch| ifs.contains(ch)).is_some() {
+			while chars.next_if(|&ch| is_blank(ch)).is_some() {}
+		}
+		fields.push((std::mem::take(&mut field), true));
+		separated = true;
+	}
+	if !field.is_empty() {
+		fields.push((field, true));
+	} else if separated {
+		fields.push((String::new(), false));
+	}
+	fields
 }
 
 // 3.1.2.1 Escape Character
@@ -0,0 +1,601 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+use crate::arith;
+use crate::utils::QuoteTracker;
+
+// Shell variables. A variable that is in the environment is exported and is
+// kept there, so child processes see every update; anything else only lives
+// in the shell.
+pub struct Variables {
+	values: HashMap<String, ShellValue>,
+	// names exported before they were given a value
+	exported: HashSet<String>,
+	// `$0`: the shell or the script it runs
+	arg0: String,
+	// `$1`, `$2` and so on
+	positional: Vec<String>,
+	// names whose values are evaluated as arithmetic when assigned
+	integer: HashSet<String>,
+	// names whose values are converted to one case when assigned
+	case: HashMap<String, Case>,
+	readonly: HashSet<String>,
+	// one frame per running function, holding what each of its local
+	// variables hides so it can be put back when the function returns
+	scopes: Vec<HashMap<String, Saved>>,
+}
+
+// What a variable in the shell holds. Arrays are never exported.
+#[derive(Clone)]
+pub enum ShellValue {
+	Scalar(String),
+	// the elements by index, None for the indexes that were never set
+	IndexedArray(Vec<Option<String>>),
+	// the elements by key
+	AssocArray(HashMap<String, String>),
+}
+
+#[derive(Clone, Copy)]
+pub enum Case {
+	Lower,
+	Upper,
+}
+
+// A variable as it was before a function made it local.
+struct Saved {
+	value: Option<ShellValue>,
+	// exported, whether or not it has a value
+	in_env: bool,
+	integer: bool,
+	case: Option<Case>,
+}
+
+impl Variables {
+	pub fn new() -> Self {
+		Variables {
+			values: HashMap::new(),
+			exported: HashSet::new(),
+			arg0: env::args().next().unwrap_or_default(),
+			positional: Vec::new(),
+			integer: HashSet::new(),
+			case: HashMap::new(),
+			readonly: HashSet::new(),
+			scopes: Vec::new(),
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<String> {
+		if name == "0" {
+			return Some(self.arg0.clone());
+		}
+		if let Ok(n @ 1..) = name.parse::<usize>() {
+			return self.positional.get(n - 1).cloned();
+		}
+		match self.values.get(name) {
+			Some(ShellValue::Scalar(value)) => Some(value.clone()),
+			// an array stands for its first element
+			Some(ShellValue::IndexedArray(_)) => self.get_element(name, 0),
+			Some(ShellValue::AssocArray(_)) => self.get_key(name, "0"),
+			None => env::var(name).ok(),
+		}
+	}
+
+	// What a variable holds, whatever kind of value it is.
+	pub fn value(&self, name: &str) -> Option<ShellValue> {
+		match self.values.get(name) {
+			Some(value) => Some(value.clone()),
+			None => self.get(name).map(ShellValue::Scalar),
+		}
+	}
+
+	pub fn is_assoc(&self, name: &str) -> bool {
+		matches!(self.values.get(name), Some(ShellValue::AssocArray(_)))
+	}
+
+	// The element at `index` of an array, where a negative index counts back
+	// from the end. A variable that is not an array is an array of one.
+	pub fn get_element(&self, name: &str, index: i64) -> Option<String> {
+		match self.values.get(name) {
+			Some(ShellValue::IndexedArray(elements)) => {
+				let index = if index < 0 { elements.len() as i64 + index } else { index };
+				elements.get(usize::try_from(index).ok()?).cloned().flatten()
+			}
+			_ if index == 0 || index == -1 => self.get(name),
+			_ => None,
+		}
+	}
+
+	// The element of an associative array under `key`. Any other variable
+	// only has the key `0`.
+	pub fn get_key(&self, name: &str, key: &str) -> Option<String> {
+		match self.values.get(name) {
+			Some(ShellValue::AssocArray(elements)) => elements.get(key).cloned(),
+			_ if key == "0" => self.get(name),
+			_ => None,
+		}
+	}
+
+	// The elements of an array that are set, in order of index or key, or the
+	// value of any other variable on its own. None when nothing is set.
+	pub fn elements(&self, name: &str) -> Option<Vec<String>> {
+		match self.values.get(name) {
+			Some(ShellValue::IndexedArray(elements)) => Some(elements.iter().flatten().cloned().collect()),
+			Some(ShellValue::AssocArray(elements)) => {
+				let mut elements: Vec<(&String, &String)> = elements.iter().collect();
+				elements.sort();
+				Some(elements.into_iter().map(|(_, value)| value.clone()).collect())
+			}
+			_ => self.get(name).map(|value| vec![value]),
+		}
+	}
+
+	// The indexes of the elements of an array that are set, or the keys of an
+	// associative array, sorted.
+	pub fn indexes(&self, name: &str) -> Vec<String> {
+		match self.values.get(name) {
+			Some(ShellValue::AssocArray(elements)) => {
+				let mut keys: Vec<String> = elements.keys().cloned().collect();
+				keys.sort();
+				keys
+			}
+			Some(ShellValue::IndexedArray(elements)) => elements
+				.iter()
+				.enumerate()
+				.filter(|(_, element)| element.is_some())
+				.map(|(index, _)| index.to_string())
+				.collect(),
+			_ if self.get(name).is_some() => vec!["0".to_string()],
+			_ => Vec::new(),
+		}
+	}
+
+	pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+		match self.values.get(name) {
+			Some(ShellValue::IndexedArray(_)) => return self.set_element(name, 0, value),
+			Some(ShellValue::AssocArray(_)) => return self.set_key(name, "0", value),
+			_ => (),
+		}
+		let value = self.assignable(name, value)?;
+		if env::var_os(name).is_some() || self.exported.remove(name) {
+			env::set_var(name, value);
+		} else {
+			self.values.insert(name.to_string(), ShellValue::Scalar(value));
+		}
+		Ok(())
+	}
+
+	// Make a variable an associative array, keeping the elements it already
+	// has as one. An indexed array cannot become one.
+	pub fn declare_assoc(&mut self, name: &str) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: readonly variable", name));
+		}
+		match self.values.get(name) {
+			Some(ShellValue::AssocArray(_)) => return Ok(()),
+			Some(ShellValue::IndexedArray(_)) => {
+				return Err(format!("{}: cannot convert indexed to associative array", name));
+			}
+			_ => (),
+		}
+		let mut elements = HashMap::new();
+		if let Some(value) = self.get(name) {
+			elements.insert("0".to_string(), value);
+		}
+		env::remove_var(name);
+		self.exported.remove(name);
+		self.values.insert(name.to_string(), ShellValue::AssocArray(elements));
+		Ok(())
+	}
+
+	// Give an associative array the elements in `pairs` of key and value in
+	// place of the ones it has.
+	pub fn set_assoc(&mut self, name: &str, pairs: &[(&str, &str)]) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: readonly variable", name));
+		}
+		let mut elements = HashMap::new();
+		for (key, value) in pairs {
+			elements.insert(key.to_string(), self.assignable(name, value)?);
+		}
+		self.values.insert(name.to_string(), ShellValue::AssocArray(elements));
+		Ok(())
+	}
+
+	// Set the element under `key` of an associative array.
+	pub fn set_key(&mut self, name: &str, key: &str, value: &str) -> Result<(), String> {
+		let value = self.assignable(name, value)?;
+		match self.values.get_mut(name) {
+			Some(ShellValue::AssocArray(elements)) => {
+				elements.insert(key.to_string(), value);
+				Ok(())
+			}
+			_ => Err(format!("{}: not an associative array", name)),
+		}
+	}
+
+	// Unset the element under `key` of an associative array.
+	pub fn unset_key(&mut self, name: &str, key: &str) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: cannot unset: readonly variable", name));
+		}
+		if let Some(ShellValue::AssocArray(elements)) = self.values.get_mut(name) {
+			elements.remove(key);
+		}
+		Ok(())
+	}
+
+	// Make a variable an array of `elements`, taking it out of the
+	// environment.
+	pub fn set_array(&mut self, name: &str, elements: &[String]) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: readonly variable", name));
+		}
+		let elements = elements
+			.iter()
+			.map(|element| self.assignable(name, element).map(Some))
+			.collect::<Result<Vec<Option<String>>, String>>()?;
+		env::remove_var(name);
+		self.exported.remove(name);
+		self.values.insert(name.to_string(), ShellValue::IndexedArray(elements));
+		Ok(())
+	}
+
+	// Set the element at `index` of an array, where a negative index counts
+	// back from the end. Any other variable becomes an array with its value
+	// as the first element.
+	pub fn set_element(&mut self, name: &str, index: i64, value: &str) -> Result<(), String> {
+		if self.is_assoc(name) {
+			return self.set_key(name, &index.to_string(), value);
+		}
+		let value = self.assignable(name, value)?;
+		let len = match self.values.get(name) {
+			Some(ShellValue::IndexedArray(elements)) => elements.len(),
+			_ => self.get(name).map_or(0, |_| 1),
+		};
+		let from_end = if index < 0 { len as i64 + index } else { index };
+		let Ok(at) = usize::try_from(from_end) else {
+			return Err(format!("{}[{}]: bad array subscript", name, index));
+		};
+		let mut elements = match self.values.remove(name) {
+			Some(ShellValue::IndexedArray(elements)) => elements,
+			Some(ShellValue::Scalar(value)) => vec![Some(value)],
+			_ => env::var(name).ok().into_iter().map(Some).collect(),
+		};
+		if at >= elements.len() {
+			elements.resize(at + 1, None);
+		}
+		elements[at] = Some(value);
+		env::remove_var(name);
+		self.exported.remove(name);
+		self.values.insert(name.to_string(), ShellValue::IndexedArray(elements));
+		Ok(())
+	}
+
+	// Unset the element at `index` of an array, leaving the others where
+	// they are.
+	pub fn unset_element(&mut self, name: &str, index: i64) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: cannot unset: readonly variable", name));
+		}
+		let Some(ShellValue::IndexedArray(elements)) = self.values.get_mut(name) else {
+			return if index == 0 || index == -1 { self.unset(name) } else { Ok(()) };
+		};
+		let from_end = if index < 0 { elements.len() as i64 + index } else { index };
+		match usize::try_from(from_end) {
+			Ok(at) => {
+				if let Some(element) = elements.get_mut(at) {
+					*element = None;
+				}
+				Ok(())
+			}
+			Err(_) => Err(format!("{}[{}]: bad array subscript", name, index)),
+		}
+	}
+
+	// The value to store in a variable, evaluated for an integer one and
+	// converted for one with a case. Fails for a readonly variable.
+	fn assignable(&mut self, name: &str, value: &str) -> Result<String, String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: readonly variable", name));
+		}
+		let value = if self.integer.contains(name) {
+			arith::evaluate(value, self)?.to_string()
+		} else {
+			value.to_string()
+		};
+		match self.case.get(name) {
+			Some(Case::Lower) => Ok(value.to_lowercase()),
+			Some(Case::Upper) => Ok(value.to_uppercase()),
+			None => Ok(value),
+		}
+	}
+
+	pub fn unset(&mut self, name: &str) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: cannot unset: readonly variable", name));
+		}
+		self.values.remove(name);
+		self.exported.remove(name);
+		env::remove_var(name);
+		Ok(())
+	}
+
+	pub fn set_integer(&mut self, name: &str) {
+		self.integer.insert(name.to_string());
+	}
+
+	pub fn is_integer(&self, name: &str) -> bool {
+		self.integer.contains(name)
+	}
+
+	// Convert every value assigned to a variable from now on to one case,
+	// in place of any case it had.
+	pub fn set_case(&mut self, name: &str, case: Case) {
+		self.case.insert(name.to_string(), case);
+	}
+
+	pub fn set_readonly(&mut self, name: &str) {
+		self.readonly.insert(name.to_string());
+	}
+
+	// The letters `declare` uses for the attributes of a variable, in the
+	// order it prints them.
+	pub fn attributes(&self, name: &str) -> String {
+		let mut attributes = String::new();
+		match self.values.get(name) {
+			Some(ShellValue::IndexedArray(_)) => attributes.push('a'),
+			Some(ShellValue::AssocArray(_)) => attributes.push('A'),
+			_ => (),
+		}
+		if self.integer.contains(name) {
+			attributes.push('i');
+		}
+		if self.readonly.contains(name) {
+			attributes.push('r');
+		}
+		if env::var_os(name).is_some() || self.exported.contains(name) {
+			attributes.push('x');
+		}
+		match self.case.get(name) {
+			Some(Case::Lower) => attributes.push('l'),
+			Some(Case::Upper) => attributes.push('u'),
+			None => (),
+		}
+		attributes
+	}
+
+	pub fn in_function(&self) -> bool {
+		!self.scopes.is_empty()
+	}
+
+	// Start the scope of a function call.
+	pub fn push_scope(&mut self) {
+		self.scopes.push(HashMap::new());
+	}
+
+	// End the innermost function scope, putting back every variable its
+	// function made local.
+	pub fn pop_scope(&mut self) {
+		let Some(scope) = self.scopes.pop() else {
+			return;
+		};
+		for (name, saved) in scope {
+			self.values.remove(&name);
+			self.exported.remove(&name);
+			env::remove_var(&name);
+			match saved.value {
+				Some(ShellValue::Scalar(value)) if saved.in_env => env::set_var(&name, value),
+				Some(value) => {
+					self.values.insert(name.clone(), value);
+				}
+				None if saved.in_env => {
+					self.exported.insert(name.clone());
+				}
+				None => (),
+			}
+			if saved.integer {
+				self.integer.insert(name.clone());
+			} else {
+				self.integer.remove(&name);
+			}
+			match saved.case {
+				Some(case) => self.case.insert(name.clone(), case),
+				None => self.case.remove(&name),
+			};
+			// a readonly variable cannot be made local, so only the local
+			// one can have been readonly
+			self.readonly.remove(&name);
+		}
+	}
+
+	// Make a variable local to the innermost function scope. It starts out
+	// unset and without attributes other than export, and what it hides comes back when the
+	// function returns.
+	pub fn make_local(&mut self, name: &str) -> Result<(), String> {
+		if self.readonly.contains(name) {
+			return Err(format!("{}: readonly variable", name));
+		}
+		let Some(scope) = self.scopes.last_mut() else {
+			return Err("can only be used in a function".to_string());
+		};
+		if scope.contains_key(name) {
+			return Ok(());
+		}
+		let in_env = env::var_os(name).is_some() || self.exported.contains(name);
+		let value = match self.values.get(name) {
+			Some(value) => Some(value.clone()),
+			None => env::var(name).ok().map(ShellValue::Scalar),
+		};
+		let saved = Saved {
+			value,
+			in_env,
+			integer: self.integer.contains(name),
+			case: self.case.get(name).copied(),
+		};
+		scope.insert(name.to_string(), saved);
+		self.values.remove(name);
+		self.integer.remove(name);
+		self.case.remove(name);
+		// a local hiding an exported variable is exported as well
+		env::remove_var(name);
+		if in_env {
+			self.exported.insert(name.to_string());
+		}
+		Ok(())
+	}
+
+	// The name of every variable with a value, sorted.
+	pub fn names(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.values.keys().cloned().chain(env::vars().map(|(name, _)| name)).collect();
+		names.sort();
+		names.dedup();
+		names
+	}
+
+	// Move a variable into the environment. A name without a value is
+	// remembered and exported as soon as it is assigned.
+	pub fn export(&mut self, name: &str) {
+		if let Some(ShellValue::Scalar(value)) = self.values.get(name) {
+			env::set_var(name, value);
+			self.values.remove(name);
+		} else if env::var_os(name).is_none() && !self.values.contains_key(name) {
+			self.exported.insert(name.to_string());
+		}
+	}
+
+	pub fn set_arg0(&mut self, arg0: &str) {
+		self.arg0 = arg0.to_string();
+	}
+
+	pub fn positional(&self) -> &[String] {
+		&self.positional
+	}
+
+	// Swap in new positional parameters, returning the ones they replace.
+	pub fn replace_positional(&mut self, positional: Vec<String>) -> Vec<String> {
+		std::mem::replace(&mut self.positional, positional)
+	}
+
+	// Every exported variable sorted by name, with None for the names that
+	// have not been given a value yet.
+	pub fn exported(&self) -> Vec<(String, Option<String>)> {
+		let mut exported: Vec<(String, Option<String>)> = env::vars()
+			.map(|(name, value)| (name, Some(value)))
+			.chain(self.exported.iter().map(|name| (name.clone(), None)))
+			.collect();
+		exported.sort();
+		exported
+	}
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+	let mut chars = name.chars();
+	match chars.next() {
+		Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+			chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+		}
+		_ => false,
+	}
+}
+
+// A name that can be assigned to: a variable, or an element of one written
+// as `name[subscript]`.
+pub fn is_assignable(name: &str) -> bool {
+	match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+		Some((name, _)) => is_valid_name(name),
+		None => is_valid_name(name),
+	}
+}
+
+// Whether a word is the `name=` or `name+=` in front of the parentheses of
+// an array assignment.
+pub fn is_array_assignment(word: &str) -> bool {
+	let name = word.strip_suffix('=');
+	name.is_some_and(|name| is_valid_name(name.strip_suffix('+').unwrap_or(name)))
+}
+
+// Read the variable reference that starts with the `$` at `chars[start]`,
+// either `$NAME`, `$N`, a special parameter such as `$?` or `${...}`, where
+// the name is everything between the braces.
+// Returns the name and the index just past the reference, or None when the
+// `$` does not start a reference.
+pub fn read_reference(chars: &[char], start: usize) -> Option<(String, usize)> {
+	match chars.get(start + 1) {
+		Some('{') => {
+			let end = find_closing_brace(chars, start + 1)?;
+			let name: String = chars[start + 2..end].iter().collect();
+			Some((name, end + 1))
+		}
+		Some(&ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+			let len = chars[start + 1..]
+				.iter()
+				.take_while(|ch| ch.is_ascii_alphanumeric() || **ch == '_')
+				.count();
+			let name: String = chars[start + 1..start + 1 + len].iter().collect();
+			Some((name, start + len + 1))
+		}
+		// only a single digit without braces, so `$10` is `$1` then `0`
+		Some(&ch) if ch.is_ascii_digit() || "?$!#@*".contains(ch) => Some((ch.to_string(), start + 2)),
+		_ => None,
+	}
+}
+
+// Find the `}` that closes the `{` at `chars[open]`, passing over quoted text
+// and the braces of any reference nested inside.
+fn find_closing_brace(chars: &[char], open: usize) -> Option<usize> {
+	let mut depth = 0;
+	let mut quote = None;
+	let mut i = open + 1;
+	while i < chars.len() {
+		match (chars[i], quote) {
+			('\\', None | Some('"')) => i += 1,
+			('\'' | '"', None) => quote = Some(chars[i]),
+			(ch, Some(open_quote)) if ch == open_quote => quote = None,
+			('{', None) => depth += 1,
+			('}', None) if depth == 0 => return Some(i),
+			('}', None) => depth -= 1,
+			_ => (),
+		}
+		i += 1;
+	}
+	None
+}
+
+// Split the `NAME=value` and `NAME+=value` words off the front of a command. The values are
+// returned as written; the rest of the command follows unchanged.
+pub fn take_assignments(command: &str) -> (Vec<(String, String)>, &str) {
+	let mut assignments: Vec<(String, String)> = Vec::new();
+	let mut rest = command.trim_start();
+
+	// an assignment needs an unquoted name in front of the `=`
+	while let Some(eq) = rest.find('=') {
+		let name = &rest[..eq];
+		if !is_assignable(name.strip_suffix('+').unwrap_or(name)) {
+			break;
+		}
+
+		// the blanks between the parentheses of `name=(...)` are part of it
+		let mut quotes = QuoteTracker::new();
+		let mut depth = 0;
+		let value_len = rest[eq + 1..]
+			.char_indices()
+			.find(|&(_, ch)| {
+				if !quotes.is_unquoted(ch) {
+					return false;
+				}
+				match ch {
+					'(' => depth += 1,
+					')' => depth -= 1,
+					_ => (),
+				}
+				ch.is_whitespace() && depth <= 0
+			})
+			.map(|(idx, _)| idx)
+			.unwrap_or(rest.len() - eq - 1);
+		let value = &rest[eq + 1..eq + 1 + value_len];
+		assignments.push((name.to_string(), value.to_string()));
+		rest = rest[eq + 1 + value_len..].trim_start();
+	}
+
+	(assignments, rest)
+}
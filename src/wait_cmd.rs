@@ -0,0 +1,51 @@
+use nix::unistd::Pid;
+
+use crate::jobs::Jobs;
+
+pub fn wait(jobs: &mut Jobs, args: &[String]) -> i32 {
+	jobs.reap();
+	if args.is_empty() {
+		for id in jobs.ids() {
+			jobs.wait(id, false);
+		}
+		return 0;
+	}
+	if args[0] == "-n" {
+		return jobs.wait_any().map_or(127, |(_, status)| status);
+	}
+
+	let mut status = 0;
+	for arg in args {
+		let id = if arg.starts_with('%') {
+			match jobs.find(Some(arg)) {
+				Ok(id) => id,
+				Err(err) => {
+					shell_error!("wait: {}", err);
+					status = 127;
+					continue;
+				}
+			}
+		} else {
+			let Ok(pid) = arg.parse::<i32>() else {
+				shell_error!("wait: `{}': not a pid or valid job spec", arg);
+				status = 2;
+				continue;
+			};
+			let pid = Pid::from_raw(pid);
+			if let Some(finished) = jobs.finished_status(pid) {
+				status = finished;
+				continue;
+			}
+			match jobs.find_pid(pid) {
+				Some(id) => id,
+				None => {
+					shell_error!("wait: pid {} is not a child of this shell", pid);
+					status = 127;
+					continue;
+				}
+			}
+		};
+		status = jobs.wait(id, false);
+	}
+	status
+}